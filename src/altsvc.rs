@@ -0,0 +1,257 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{anyhow, Context, Result};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use reqwest::{header, Client, RequestBuilder, Response, Url, Version};
+
+// 没有 ma 参数时 Alt-Svc 的有效期，RFC 7838 规定为 24 小时
+const DEFAULT_MAX_AGE: i64 = 86400;
+
+/// 缓存中的一项：服务器通过 Alt-Svc 告知的 HTTP/3 服务
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    // 发现这个服务时使用的协议，h1 或 h2
+    src_alpn: String,
+    host: String,
+    port: u16,
+    // 提供 HTTP/3 的主机和 UDP 端口
+    dst_host: String,
+    dst_port: u16,
+    expires: i64,
+    persist: bool,
+}
+
+/// --alt-svc 指定的缓存文件，格式和 curl 的相同，可以和 curl 共用
+/// 服务器在 Alt-Svc 中提供了 h3 时记录下来，以后的请求直接通过 HTTP/3 发送
+pub struct AltSvc {
+    path: PathBuf,
+    entries: Mutex<Vec<Entry>>,
+    // 只使用 HTTP/3 的客户端
+    client: Client,
+}
+
+impl AltSvc {
+    /// 读取缓存文件，文件不存在时从空的缓存开始
+    pub fn load(path: &Path, client: Client) -> Result<Self> {
+        let entries = match fs::read_to_string(path) {
+            Ok(text) => text.lines().filter_map(parse_line).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to read the Alt-Svc cache {}", path.display())
+                })
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            client,
+        })
+    }
+
+    /// 缓存中有请求的主机提供的 HTTP/3 服务时，改为通过 HTTP/3 发送到它的端口
+    /// 返回改写后的请求和原来的 URL，没有改写时 URL 为 None
+    /// 只使用同一个主机上的服务，证书和 SNI 都不变
+    pub fn route(&self, req: RequestBuilder) -> Result<(RequestBuilder, Option<Url>)> {
+        let (client, req) = req.build_split();
+        let mut req = req?;
+        let url = req.url().clone();
+        let now = Utc::now().timestamp();
+        let entry = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) if url.scheme() == "https" => {
+                let entries = self.entries.lock().unwrap();
+                entries
+                    .iter()
+                    .find(|e| {
+                        e.host.eq_ignore_ascii_case(host)
+                            && e.port == port
+                            && e.dst_host.eq_ignore_ascii_case(host)
+                            && e.expires > now
+                    })
+                    .cloned()
+            }
+            _ => None,
+        };
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return Ok((RequestBuilder::from_parts(client, req), None)),
+        };
+        req.url_mut()
+            .set_port(Some(entry.dst_port))
+            .map_err(|_| anyhow!("Failed to use the Alt-Svc port of {}", url))?;
+        *req.version_mut() = Version::HTTP_3;
+        Ok((RequestBuilder::from_parts(self.client.clone(), req), Some(url)))
+    }
+
+    /// 按照响应的 Alt-Svc 更新缓存并写回文件，clear 表示删除这个主机的所有服务
+    pub fn update(&self, resp: &Response) -> Result<()> {
+        let url = resp.url();
+        let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) if url.scheme() == "https" => (host, port),
+            _ => return Ok(()),
+        };
+        let value = match resp.headers().get(header::ALT_SVC).and_then(|v| v.to_str().ok()) {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        let src_alpn = match resp.version() {
+            Version::HTTP_2 => "h2",
+            _ => "h1",
+        };
+        let now = Utc::now().timestamp();
+        {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|e| !(e.host.eq_ignore_ascii_case(host) && e.port == port));
+            for (dst_host, dst_port, max_age, persist) in parse_header(value) {
+                entries.push(Entry {
+                    src_alpn: src_alpn.into(),
+                    host: host.to_ascii_lowercase(),
+                    port,
+                    dst_host: dst_host.unwrap_or_else(|| host.to_ascii_lowercase()),
+                    dst_port,
+                    expires: now + max_age,
+                    persist,
+                });
+            }
+        }
+        self.save()
+    }
+
+    /// 通过 HTTP/3 发送失败时删除 url 的服务，下次使用 TCP
+    pub fn forget(&self, url: &Url) -> Result<()> {
+        let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => (host, port),
+            _ => return Ok(()),
+        };
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|e| !(e.host.eq_ignore_ascii_case(host) && e.port == port));
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let mut text = String::from("# Alt-Svc cache, the same format as curl --alt-svc\n");
+        for entry in self.entries.lock().unwrap().iter().filter(|e| e.expires > now) {
+            text.push_str(&format_line(entry));
+            text.push('\n');
+        }
+        fs::write(&self.path, text)
+            .with_context(|| format!("Failed to write the Alt-Svc cache {}", self.path.display()))
+    }
+}
+
+/// 解析 Alt-Svc 中的 h3 服务：(主机，端口，有效期，persist)，主机为空时是同一个主机
+/// 例如 h3=":443"; ma=86400, h3-29=":443"，只使用最终版本的 h3
+fn parse_header(value: &str) -> Vec<(Option<String>, u16, i64, bool)> {
+    let mut services = Vec::new();
+    for service in value.split(',') {
+        let mut params = service.split(';').map(str::trim);
+        let (protocol, authority) = match params.next().and_then(|p| p.split_once('=')) {
+            Some((protocol, authority)) => (protocol.trim(), authority.trim().trim_matches('"')),
+            // clear 或者无效的值
+            None => continue,
+        };
+        if protocol != "h3" {
+            continue;
+        }
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => match port.parse() {
+                Ok(port) => (host, port),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let (mut max_age, mut persist) = (DEFAULT_MAX_AGE, false);
+        for param in params {
+            match param.split_once('=') {
+                Some(("ma", value)) => max_age = value.parse().unwrap_or(DEFAULT_MAX_AGE),
+                Some(("persist", value)) => persist = value == "1",
+                _ => {}
+            }
+        }
+        let host = Some(host.to_ascii_lowercase()).filter(|h| !h.is_empty());
+        services.push((host, port, max_age, persist));
+    }
+    services
+}
+
+/// curl 的格式：h2 example.com 443 h3 example.com 443 "20261015 03:00:00" 0 0
+fn parse_line(line: &str) -> Option<Entry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (fields, rest) = line.split_once('"')?;
+    let (expires, rest) = rest.split_once('"')?;
+    let fields: Vec<_> = fields.split_whitespace().collect();
+    let rest: Vec<_> = rest.split_whitespace().collect();
+    if fields.len() != 6 || fields[3] != "h3" {
+        return None;
+    }
+    let expires = NaiveDateTime::parse_from_str(expires, "%Y%m%d %H:%M:%S").ok()?;
+    Some(Entry {
+        src_alpn: fields[0].into(),
+        host: fields[1].to_ascii_lowercase(),
+        port: fields[2].parse().ok()?,
+        dst_host: fields[4].to_ascii_lowercase(),
+        dst_port: fields[5].parse().ok()?,
+        expires: Utc.from_utc_datetime(&expires).timestamp(),
+        persist: rest.first() == Some(&"1"),
+    })
+}
+
+fn format_line(entry: &Entry) -> String {
+    let expires = match Utc.timestamp_opt(entry.expires, 0).single() {
+        Some(time) => time.format("%Y%m%d %H:%M:%S").to_string(),
+        None => "20000101 00:00:00".into(),
+    };
+    format!(
+        "{} {} {} h3 {} {} \"{}\" {} 0",
+        entry.src_alpn,
+        entry.host,
+        entry.port,
+        entry.dst_host,
+        entry.dst_port,
+        expires,
+        entry.persist as u8
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_header_works() {
+        assert_eq!(
+            parse_header(r#"h3=":443"; ma=3600, h3-29=":443", h2="alt.example.com:8443""#),
+            vec![(None, 443, 3600, false)]
+        );
+        assert_eq!(
+            parse_header(r#"h3="Alt.Example.com:8443"; persist=1"#),
+            vec![(Some("alt.example.com".into()), 8443, DEFAULT_MAX_AGE, true)]
+        );
+        assert!(parse_header("clear").is_empty());
+        assert!(parse_header(r#"h3=":port""#).is_empty());
+    }
+
+    #[test]
+    fn cache_line_works() {
+        let line = r#"h2 example.com 443 h3 example.com 8443 "20261015 00:00:00" 0 0"#;
+        let entry = parse_line(line).unwrap();
+        assert_eq!(entry.host, "example.com");
+        assert_eq!(entry.port, 443);
+        assert_eq!(entry.dst_port, 8443);
+        // 2026-10-15 00:00:00 UTC
+        assert_eq!(entry.expires, 1792022400);
+        assert_eq!(format_line(&entry), line);
+        assert_eq!(parse_line("# comment"), None);
+        assert_eq!(
+            parse_line(r#"h1 example.com 443 h2 example.com 443 "20261015 00:00:00" 0 0"#),
+            None
+        );
+    }
+}
@@ -0,0 +1,388 @@
+mod aws;
+mod digest;
+mod keychain;
+mod netrc;
+mod ntlm;
+mod oauth2;
+mod sign;
+
+use std::str::FromStr;
+use anyhow::{anyhow, Context, Result};
+use clap::{ArgEnum, Clap};
+use reqwest::{Client, RequestBuilder, Response, Url};
+
+use crate::redact::Redactor;
+use crate::upload::Upload;
+
+use aws::AwsSigner;
+use oauth2::{OAuth2, OAuth2Flow};
+pub use sign::HmacSigner;
+
+/// 认证相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct AuthArgs {
+    /// Credentials for basic / digest / ntlm auth in the form user:password (you will be
+    /// prompted if the password is omitted), the token for bearer auth, or
+    /// name:value for api key auth, access_key:secret_key for aws auth, or
+    /// client_id[:client_secret] for oauth2 auth
+    #[clap(short, long, global = true)]
+    auth: Option<String>,
+    /// How to use the --auth credentials. ntlm also answers the NTLM challenge of an http
+    /// proxy, but not of the CONNECT tunnel of https requests (only --proxy-auth basic
+    /// credentials can be used there)
+    #[clap(long, global = true, arg_enum, default_value = "basic")]
+    auth_type: AuthType,
+    /// Where to put the api key for --auth-type apikey
+    #[clap(long, global = true, arg_enum, default_value = "header")]
+    auth_placement: AuthPlacement,
+    /// The AWS region used to sign requests for --auth-type aws
+    #[clap(long, global = true, default_value = "us-east-1")]
+    aws_region: String,
+    /// The AWS service name (e.g. s3, execute-api) used to sign requests for --auth-type aws
+    #[clap(long, global = true)]
+    aws_service: Option<String>,
+    /// How to obtain the access token for --auth-type oauth2
+    #[clap(long, global = true, arg_enum, default_value = "client-credentials")]
+    oauth2_flow: OAuth2Flow,
+    /// The token endpoint used to fetch an access token for --auth-type oauth2
+    #[clap(long, global = true)]
+    oauth2_token_url: Option<String>,
+    /// The device authorization endpoint for --oauth2-flow device
+    #[clap(long, global = true)]
+    oauth2_device_url: Option<String>,
+    /// The authorization endpoint for --oauth2-flow pkce
+    #[clap(long, global = true)]
+    oauth2_auth_url: Option<String>,
+    /// The local port to receive the redirect for --oauth2-flow pkce, 0 picks a random port
+    #[clap(long, global = true, default_value = "0")]
+    oauth2_redirect_port: u16,
+    /// The scope to request for --auth-type oauth2
+    #[clap(long, global = true)]
+    oauth2_scope: Option<String>,
+    /// Sign the method, path and body with a shared key, in the form
+    /// hmac-sha256:key (hmac-sha1 and hmac-sha512 are also supported)
+    #[clap(long, global = true)]
+    sign: Option<String>,
+    /// The header carrying the --sign signature, {signature} is replaced with the hex
+    /// signature and {signature_base64} with the base64 one
+    #[clap(long, global = true, default_value = "X-Signature: {signature}")]
+    sign_header: String,
+    /// Read the password (or the bearer token named by --auth) from the system keyring
+    /// instead of prompting, it is prompted for and saved on first use. -a user:keyring
+    /// does the same
+    #[clap(long, global = true)]
+    keyring: bool,
+    /// Look up basic auth credentials for the host in ~/.netrc when --auth is not given
+    #[clap(long, global = true)]
+    netrc: bool,
+}
+
+// --auth-type 支持的认证方式
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum AuthType {
+    Basic,
+    Bearer,
+    Digest,
+    Apikey,
+    Aws,
+    Oauth2,
+    Ntlm,
+}
+
+// --auth-placement，api key 放在header还是query中
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum AuthPlacement {
+    Header,
+    Query,
+}
+
+/// 根据 --auth-type 和 -a/--auth 生成的认证方式
+#[derive(Debug, PartialEq)]
+pub enum Auth {
+    // Authorization: Basic base64(user:password)
+    Basic(Credentials),
+    // Authorization: Bearer <token>
+    Bearer(String),
+    // RFC 7616 digest 认证，需要先拿到服务器的质询
+    Digest(Credentials),
+    // name:value 形式的 api key，放在header或者query中
+    ApiKey(Credentials, AuthPlacement),
+    // AWS Signature V4 签名
+    Aws(AwsSigner),
+    // NTLM 握手，用户名可以写成 DOMAIN\user
+    Ntlm(Credentials),
+    // OAuth2，先获取 access token 再作为 Bearer token 发送
+    OAuth2(OAuth2),
+}
+
+impl Auth {
+    /// 根据命令行参数生成认证方式，没有指定 -a/--auth 时可以从 ~/.netrc 中查找
+    pub fn from_args(args: &AuthArgs, url: &str) -> Result<Option<Self>> {
+        match args.auth {
+            Some(ref value) => {
+                let url: Url = url.parse()?;
+                Ok(Some(Self::new(args, value, url.host_str().unwrap_or_default())?))
+            }
+            None if args.netrc => {
+                let url: Url = url.parse()?;
+                Ok(netrc::lookup(url.host_str().unwrap_or_default())?.map(Self::Basic))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 按照认证方式解析 -a/--auth 的值，需要密码但没有给出时会提示输入或者从钥匙串中读取
+    fn new(args: &AuthArgs, value: &str, host: &str) -> Result<Self> {
+        if args.auth_type == AuthType::Bearer {
+            // 使用钥匙串时 -a 的值是 token 的名字
+            let token = if args.keyring {
+                keychain::password(value, host)?
+            } else {
+                value.to_string()
+            };
+            return Ok(Self::Bearer(token));
+        }
+        let mut credentials = parse_credentials(value)?;
+        // 交互式的 oauth2 登录使用 public client，不需要 client secret
+        let public_client =
+            args.auth_type == AuthType::Oauth2 && args.oauth2_flow != OAuth2Flow::ClientCredentials;
+        if args.keyring || credentials.password.as_deref() == Some("keyring") {
+            credentials.password = Some(keychain::password(&credentials.user, host)?);
+        } else if !public_client {
+            credentials.prompt_password()?;
+        }
+        Ok(match args.auth_type {
+            AuthType::Digest => Self::Digest(credentials),
+            AuthType::Ntlm => Self::Ntlm(credentials),
+            AuthType::Apikey => Self::ApiKey(credentials, args.auth_placement),
+            AuthType::Aws => Self::Aws(AwsSigner {
+                credentials,
+                region: args.aws_region.clone(),
+                service: args
+                    .aws_service
+                    .clone()
+                    .ok_or_else(|| anyhow!("--aws-service is required for --auth-type aws"))?,
+            }),
+            AuthType::Oauth2 => Self::OAuth2(OAuth2 {
+                credentials,
+                flow: args.oauth2_flow,
+                token_url: args.oauth2_token_url.clone().ok_or_else(|| {
+                    anyhow!("--oauth2-token-url is required for --auth-type oauth2")
+                })?,
+                device_url: args.oauth2_device_url.clone(),
+                auth_url: args.oauth2_auth_url.clone(),
+                redirect_port: args.oauth2_redirect_port,
+                scope: args.oauth2_scope.clone(),
+            }),
+            _ => Self::Basic(credentials),
+        })
+    }
+
+    /// 需要和服务器交互才能完成的认证（digest、ntlm 和 oauth2）返回它的 --auth-type，
+    /// 这些认证只能由 send 发送的请求使用
+    pub fn exchange_type(&self) -> Option<&'static str> {
+        match self {
+            Self::Digest(_) => Some("digest"),
+            Self::Ntlm(_) => Some("ntlm"),
+            Self::OAuth2(_) => Some("oauth2"),
+            _ => None,
+        }
+    }
+
+    /// 为请求加上 Authorization header
+    /// digest 和 ntlm 认证要等到收到质询后才能计算，oauth2 需要先获取 token，都在 send 中处理，
+    /// aws 签名需要完整的请求，在 sign 中处理
+    pub fn apply(&self, req: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Basic(c) => req.basic_auth(&c.user, c.password.as_ref()),
+            Self::Bearer(token) => req.bearer_auth(token),
+            Self::Digest(_) | Self::Ntlm(_) | Self::Aws(_) | Self::OAuth2(_) => req,
+            Self::ApiKey(c, placement) => {
+                let value = c.password.as_deref().unwrap_or_default();
+                match placement {
+                    AuthPlacement::Header => req.header(c.user.as_str(), value),
+                    AuthPlacement::Query => req.query(&[(c.user.as_str(), value)]),
+                }
+            }
+        }
+    }
+
+    /// 打印请求时隐藏 header 或者 query 中的 api key 和 aws 的 session token，
+    /// Authorization 总是隐藏的
+    pub fn redact(&self, redactor: &mut Redactor) -> Result<()> {
+        match self {
+            Self::ApiKey(c, AuthPlacement::Header) => redactor.hide_header(&c.user),
+            Self::ApiKey(c, AuthPlacement::Query) => {
+                redactor.hide_query(&c.user);
+                Ok(())
+            }
+            Self::Aws(_) => redactor.hide_header("x-amz-security-token"),
+            _ => Ok(()),
+        }
+    }
+
+    /// --auth-type bearer 指定的 token
+    pub fn bearer_token(&self) -> Option<&str> {
+        match self {
+            Self::Bearer(token) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// 发送之前就能加上的认证信息，包括 aws 签名，签名时 body 还在内存中，可以计算哈希
+    pub fn sign(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        match self {
+            Self::Aws(signer) => {
+                let (client, req) = req.build_split();
+                let mut req = req?;
+                signer.sign(&mut req)?;
+                Ok(RequestBuilder::from_parts(client, req))
+            }
+            _ => Ok(self.apply(req)),
+        }
+    }
+
+    /// 发送已经 sign 过的请求，digest 和 ntlm 在这里完成握手，oauth2 在这里获取 token
+    /// 每次发送之前才用 upload 包装 body，握手中重新发送的请求仍然可以克隆
+    pub async fn send(&self, client: &Client, req: RequestBuilder, upload: &Upload) -> Result<Response> {
+        match self {
+            Self::Digest(credentials) => digest::send(client, req, credentials, upload).await,
+            Self::Ntlm(credentials) => ntlm::send(req, credentials, upload).await,
+            Self::OAuth2(oauth2) => oauth2.send(client, req, upload).await,
+            _ => Ok(upload.apply(req)?.send().await?),
+        }
+    }
+}
+
+/// -a/--auth 指定的认证信息，格式为 user:password
+#[derive(Debug, PartialEq)]
+pub struct Credentials {
+    pub user: String,
+    pub password: Option<String>,
+}
+
+impl FromStr for Credentials {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 只在第一个 : 处分割，密码中可以包含 :
+        let (user, password) = match s.find(':') {
+            Some(pos) => (&s[..pos], Some(s[pos + 1..].to_string())),
+            None => (s, None),
+        };
+        if user.is_empty() {
+            return Err(anyhow!("Failed to parse credentials {}, expect user:password", s));
+        }
+        Ok(Self {
+            user: user.to_string(),
+            password,
+        })
+    }
+}
+
+impl Credentials {
+    /// 只给出了用户名时，在终端中提示输入密码（不回显），避免密码出现在shell历史中
+    pub fn prompt_password(&mut self) -> Result<()> {
+        if self.password.is_none() {
+            let prompt = format!("http: password for {}: ", self.user);
+            let password = rpassword::prompt_password(prompt)
+                .context("Failed to read password, pass it with -a user:password instead")?;
+            self.password = Some(password);
+        }
+        Ok(())
+    }
+}
+
+/// 解析 -a/--auth 参数
+pub fn parse_credentials(s: &str) -> Result<Credentials> {
+    s.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_credentials_works() {
+        assert_eq!(
+            parse_credentials("kim:p:ss").unwrap(),
+            Credentials {
+                user: "kim".into(),
+                password: Some("p:ss".into())
+            }
+        );
+        assert_eq!(
+            parse_credentials("kim").unwrap(),
+            Credentials {
+                user: "kim".into(),
+                password: None
+            }
+        );
+        assert!(parse_credentials(":pass").is_err());
+    }
+
+    fn new_auth(auth_type: AuthType, placement: AuthPlacement, value: &str) -> Auth {
+        let args = AuthArgs {
+            auth: None,
+            auth_type,
+            auth_placement: placement,
+            aws_region: "us-east-1".into(),
+            aws_service: None,
+            oauth2_flow: OAuth2Flow::ClientCredentials,
+            oauth2_token_url: None,
+            oauth2_device_url: None,
+            oauth2_auth_url: None,
+            oauth2_redirect_port: 0,
+            oauth2_scope: None,
+            sign: None,
+            sign_header: String::new(),
+            keyring: false,
+            netrc: false,
+        };
+        Auth::new(&args, value, "abc.xyz").unwrap()
+    }
+
+    #[test]
+    fn auth_apply_works() {
+        let auth = new_auth(AuthType::Basic, AuthPlacement::Header, "kim:pw");
+        let req = auth.apply(reqwest::Client::new().get("http://abc.xyz/"));
+        let req = req.build().unwrap();
+        assert_eq!(req.headers()["authorization"], "Basic a2ltOnB3");
+
+        let auth = new_auth(AuthType::Bearer, AuthPlacement::Header, "abc:def");
+        assert_eq!(auth, Auth::Bearer("abc:def".into()));
+        let req = auth.apply(reqwest::Client::new().get("http://abc.xyz/"));
+        let req = req.build().unwrap();
+        assert_eq!(req.headers()["authorization"], "Bearer abc:def");
+    }
+
+    #[test]
+    fn aws_sign_works() {
+        let auth = Auth::Aws(AwsSigner {
+            credentials: parse_credentials("AKID:secret").unwrap(),
+            region: "us-east-1".into(),
+            service: "s3".into(),
+        });
+        // --expect-continue 把 body 转换成流之前已经计算了哈希
+        let upload = Upload::new(None, false, Some(std::time::Duration::from_secs(1)));
+        let req = reqwest::Client::new().put("http://abc.xyz/key").body("data");
+        let req = upload.apply(auth.sign(req).unwrap()).unwrap().build().unwrap();
+        assert_eq!(
+            req.headers()["x-amz-content-sha256"],
+            "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7"
+        );
+        assert!(req.headers()["authorization"].to_str().unwrap().starts_with("AWS4-HMAC-SHA256 "));
+        assert!(req.body().unwrap().as_bytes().is_none());
+    }
+
+    #[test]
+    fn api_key_apply_works() {
+        let auth = new_auth(AuthType::Apikey, AuthPlacement::Header, "X-Api-Key:abc");
+        let req = auth.apply(reqwest::Client::new().get("http://abc.xyz/"));
+        assert_eq!(req.build().unwrap().headers()["x-api-key"], "abc");
+
+        let auth = new_auth(AuthType::Apikey, AuthPlacement::Query, "api_key:abc");
+        let req = auth.apply(reqwest::Client::new().get("http://abc.xyz/?a=1"));
+        assert_eq!(req.build().unwrap().url().as_str(), "http://abc.xyz/?a=1&api_key=abc");
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use md5::Md5;
+use rand::Rng;
+use reqwest::{header, Client, RequestBuilder, Response, StatusCode};
+use sha2::{Digest, Sha256};
+
+use super::Credentials;
+use crate::upload::Upload;
+
+/// digest 认证支持的哈希算法
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Algorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl Algorithm {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "MD5" => Some(Self::Md5),
+            "MD5-SESS" => Some(Self::Md5Sess),
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-256-SESS" => Some(Self::Sha256Sess),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Md5Sess => "MD5-sess",
+            Self::Sha256 => "SHA-256",
+            Self::Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    fn is_sess(&self) -> bool {
+        matches!(self, Self::Md5Sess | Self::Sha256Sess)
+    }
+
+    /// 计算哈希并以小写十六进制输出
+    fn hash(&self, data: &str) -> String {
+        match self {
+            Self::Md5 | Self::Md5Sess => format!("{:x}", Md5::digest(data.as_bytes())),
+            Self::Sha256 | Self::Sha256Sess => format!("{:x}", Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// 服务器在 401 响应的 WWW-Authenticate 中给出的 digest 质询
+#[derive(Debug, PartialEq)]
+pub struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    // 服务器支持 qop=auth 时为 true，否则使用 RFC 2069 的旧算法
+    qop_auth: bool,
+}
+
+impl Challenge {
+    /// 解析 WWW-Authenticate: Digest realm="...", nonce="...", ...
+    /// 不是 digest 质询或者使用了不支持的算法时返回 None
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let scheme = value.get(..6)?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let params = parse_params(&value[6..]);
+        let algorithm = match params.get("algorithm") {
+            Some(a) => Algorithm::parse(a)?,
+            None => Algorithm::Md5,
+        };
+        let qop_auth = params
+            .get("qop")
+            .map(|qop| qop.split(',').any(|q| q.trim() == "auth"))
+            .unwrap_or(false);
+        // 服务器要求 qop 但是不支持 auth（例如只支持 auth-int）时无法处理
+        if params.contains_key("qop") && !qop_auth {
+            return None;
+        }
+        Some(Self {
+            realm: params.get("realm").cloned().unwrap_or_default(),
+            nonce: params.get("nonce")?.clone(),
+            opaque: params.get("opaque").cloned(),
+            algorithm,
+            qop_auth,
+        })
+    }
+
+    /// 按照 RFC 7616 计算 Authorization header 的值
+    pub fn authorization(
+        &self,
+        credentials: &Credentials,
+        method: &str,
+        uri: &str,
+        cnonce: &str,
+        nc: u32,
+    ) -> String {
+        let alg = self.algorithm;
+        let user = &credentials.user;
+        let password = credentials.password.as_deref().unwrap_or_default();
+        let mut ha1 = alg.hash(&format!("{}:{}:{}", user, self.realm, password));
+        if alg.is_sess() {
+            ha1 = alg.hash(&format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = alg.hash(&format!("{}:{}", method, uri));
+        let nc = format!("{:08x}", nc);
+        let response = if self.qop_auth {
+            alg.hash(&format!("{}:{}:{}:{}:auth:{}", ha1, self.nonce, nc, cnonce, ha2))
+        } else {
+            alg.hash(&format!("{}:{}:{}", ha1, self.nonce, ha2))
+        };
+
+        let mut value = format!(
+            r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", algorithm={}, response="{}""#,
+            escape(user),
+            escape(&self.realm),
+            escape(&self.nonce),
+            uri,
+            alg.name(),
+            response
+        );
+        if self.qop_auth {
+            value.push_str(&format!(r#", qop=auth, nc={}, cnonce="{}""#, nc, cnonce));
+        }
+        if let Some(ref opaque) = self.opaque {
+            value.push_str(&format!(r#", opaque="{}""#, escape(opaque)));
+        }
+        value
+    }
+}
+
+/// 解析 key=value, key="quoted, value" 形式的参数列表，key 统一转成小写
+fn parse_params(s: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        // 跳过分隔符和空白
+        while matches!(chars.peek(), Some(c) if *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c == ',' {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        if key.is_empty() {
+            break;
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next()),
+                        '"' => break,
+                        _ => value.push(c),
+                    }
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c == ',' {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+            }
+        }
+        params.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+    params
+}
+
+/// 转义 quoted-string 中的 " 和 \
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// 发送请求，如果服务器返回 401 和 digest 质询，计算认证信息后重新发送一次
+pub async fn send(
+    client: &Client,
+    req: RequestBuilder,
+    credentials: &Credentials,
+    upload: &Upload,
+) -> Result<Response> {
+    // 流式的body无法重新发送，这种情况下只能直接发出请求
+    let retry = match req.try_clone() {
+        Some(retry) => retry,
+        None => return Ok(upload.apply(req)?.send().await?),
+    };
+    let resp = upload.apply(req)?.send().await?;
+    if resp.status() != StatusCode::UNAUTHORIZED {
+        return Ok(resp);
+    }
+    let challenge = resp
+        .headers()
+        .get_all(header::WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(Challenge::parse);
+    let challenge = match challenge {
+        Some(challenge) => challenge,
+        None => return Ok(resp),
+    };
+
+    let mut request = retry.build()?;
+    let uri = match request.url().query() {
+        Some(query) => format!("{}?{}", request.url().path(), query),
+        None => request.url().path().to_string(),
+    };
+    let cnonce = format!("{:016x}", rand::thread_rng().gen::<u64>());
+    let value = challenge.authorization(credentials, request.method().as_str(), &uri, &cnonce, 1);
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        value.parse().map_err(|_| anyhow!("Invalid digest credentials"))?,
+    );
+    let req = upload.apply(RequestBuilder::from_parts(client.clone(), request))?;
+    Ok(req.send().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7616 3.9.1 中的例子
+    const CHALLENGE: &str = r#"Digest
+        realm="http-auth@example.org",
+        qop="auth, auth-int",
+        algorithm=SHA-256,
+        nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v",
+        opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#;
+    const CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    fn mufasa() -> Credentials {
+        Credentials {
+            user: "Mufasa".into(),
+            password: Some("Circle of Life".into()),
+        }
+    }
+
+    #[test]
+    fn parse_challenge_works() {
+        let challenge = Challenge::parse(CHALLENGE).unwrap();
+        assert_eq!(challenge.realm, "http-auth@example.org");
+        assert_eq!(challenge.algorithm, Algorithm::Sha256);
+        assert!(challenge.qop_auth);
+        assert!(Challenge::parse(r#"Basic realm="x""#).is_none());
+        assert!(Challenge::parse(r#"Digest nonce="x", algorithm=SHA-512"#).is_none());
+    }
+
+    #[test]
+    fn authorization_works() {
+        let challenge = Challenge::parse(CHALLENGE).unwrap();
+        let value = challenge.authorization(&mufasa(), "GET", "/dir/index.html", CNONCE, 1);
+        assert!(value.contains(
+            r#"response="753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1""#
+        ));
+        assert!(value.contains("qop=auth, nc=00000001"));
+
+        let challenge = Challenge::parse(&CHALLENGE.replace("SHA-256", "MD5")).unwrap();
+        let value = challenge.authorization(&mufasa(), "GET", "/dir/index.html", CNONCE, 1);
+        assert!(value.contains(r#"response="8ca523f5e9506fed4657c9700eebdbec""#));
+        assert!(value.contains(r#"opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#));
+    }
+}
@@ -0,0 +1,39 @@
+use anyhow::{anyhow, Context, Result};
+use ::keyring::{Entry, Error};
+
+// 在系统钥匙串中保存时使用的服务名
+const SERVICE: &str = "httpie";
+
+/// 钥匙串中的账号名，同一个用户在不同主机上的密码分开保存
+fn account(user: &str, host: &str) -> String {
+    format!("{}@{}", user, host)
+}
+
+/// 从系统钥匙串中读取 user 在 host 上的密码，不存在时提示输入并保存到钥匙串
+pub fn password(user: &str, host: &str) -> Result<String> {
+    let account = account(user, host);
+    let entry = Entry::new(SERVICE, &account)
+        .map_err(|e| anyhow!("Failed to open the keyring entry {}: {}", account, e))?;
+    match entry.get_password() {
+        Ok(password) => Ok(password),
+        Err(Error::NoEntry) => {
+            let prompt = format!("http: password for {} (will be saved to the keyring): ", account);
+            let password = rpassword::prompt_password(prompt).context("Failed to read password")?;
+            entry
+                .set_password(&password)
+                .map_err(|e| anyhow!("Failed to save the password to the keyring: {}", e))?;
+            Ok(password)
+        }
+        Err(e) => Err(anyhow!("Failed to read the password from the keyring: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_works() {
+        assert_eq!(account("kim", "api.example.com"), "kim@api.example.com");
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+use anyhow::{Context, Result};
+
+use super::Credentials;
+
+/// .netrc 文件的位置，优先使用 NETRC 环境变量
+fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("NETRC") {
+        return Some(PathBuf::from(path));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".netrc"))
+}
+
+/// 在 ~/.netrc 中查找主机对应的用户名和密码，文件不存在时返回 None
+pub fn lookup(host: &str) -> Result<Option<Credentials>> {
+    let path = match netrc_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(None),
+    };
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(find(&content, host))
+}
+
+/// 解析 .netrc 的内容，找到 machine 与主机匹配的条目，没有时使用 default 条目
+fn find(content: &str, host: &str) -> Option<Credentials> {
+    let mut tokens = content.split_whitespace();
+    let mut matched: Option<Credentials> = None;
+    let mut default: Option<Credentials> = None;
+    // 当前正在解析的条目，None 表示与主机无关的条目
+    let mut current: Option<&mut Option<Credentials>> = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => {
+                // 只使用第一个匹配的条目
+                current = match tokens.next() {
+                    Some(name) if name == host && matched.is_none() => Some(&mut matched),
+                    _ => None,
+                };
+            }
+            "default" => current = Some(&mut default),
+            "login" | "password" => {
+                let value = tokens.next()?.to_string();
+                if let Some(ref mut entry) = current {
+                    let credentials = entry.get_or_insert_with(|| Credentials {
+                        user: String::new(),
+                        password: None,
+                    });
+                    if token == "login" {
+                        credentials.user = value;
+                    } else {
+                        credentials.password = Some(value);
+                    }
+                }
+            }
+            "account" => {
+                tokens.next();
+            }
+            // macdef 定义的宏直到空行才结束，这里不需要它，直接停止解析
+            "macdef" => break,
+            _ => {}
+        }
+    }
+    matched
+        .or(default)
+        .filter(|credentials| !credentials.user.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETRC: &str = "
+        machine api.example.com
+          login kim
+          password secret
+        machine other.example.com login bob password pw
+        default login anonymous password guest
+    ";
+
+    #[test]
+    fn find_works() {
+        let credentials = find(NETRC, "api.example.com").unwrap();
+        assert_eq!(credentials.user, "kim");
+        assert_eq!(credentials.password.as_deref(), Some("secret"));
+
+        let credentials = find(NETRC, "other.example.com").unwrap();
+        assert_eq!(credentials.user, "bob");
+
+        let credentials = find(NETRC, "unknown.example.com").unwrap();
+        assert_eq!(credentials.user, "anonymous");
+
+        assert!(find("machine a login b password c", "x").is_none());
+    }
+}
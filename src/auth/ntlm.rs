@@ -0,0 +1,381 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use md5::Md5;
+use rand::Rng;
+use reqwest::header::{self, HeaderName};
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use super::Credentials;
+use crate::upload::Upload;
+
+const SIGNATURE: &[u8] = b"NTLMSSP\0";
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const NEGOTIATE_OEM: u32 = 0x0000_0002;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+const NEGOTIATE_FLAGS: u32 = NEGOTIATE_UNICODE
+    | NEGOTIATE_OEM
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+// target info 中 MsvAvTimestamp 的 AvId
+const AV_TIMESTAMP: u16 = 7;
+// 1601-01-01 到 1970-01-01 之间 100 纳秒的个数
+const FILETIME_EPOCH: u64 = 116_444_736_000_000_000;
+
+/// 服务器返回的 CHALLENGE_MESSAGE（type 2）
+#[derive(Debug, PartialEq)]
+struct Challenge {
+    flags: u32,
+    server_challenge: [u8; 8],
+    target_info: Vec<u8>,
+}
+
+/// NEGOTIATE_MESSAGE（type 1），不带 domain 和 workstation
+fn negotiate_message() -> Vec<u8> {
+    let mut msg = SIGNATURE.to_vec();
+    msg.extend_from_slice(&1u32.to_le_bytes());
+    msg.extend_from_slice(&NEGOTIATE_FLAGS.to_le_bytes());
+    // 空的 DomainNameFields 和 WorkstationFields
+    msg.extend_from_slice(&[0; 16]);
+    msg
+}
+
+/// 解析 CHALLENGE_MESSAGE，格式不对时返回 None
+fn parse_challenge(data: &[u8]) -> Option<Challenge> {
+    if data.len() < 32 || &data[..8] != SIGNATURE || read_u32(data, 8)? != 2 {
+        return None;
+    }
+    let mut server_challenge = [0; 8];
+    server_challenge.copy_from_slice(&data[24..32]);
+    // 老的服务器可能没有 TargetInfoFields
+    let target_info = if data.len() >= 48 {
+        let len = read_u16(data, 40)? as usize;
+        let offset = read_u32(data, 44)? as usize;
+        data.get(offset..offset + len)?.to_vec()
+    } else {
+        Vec::new()
+    };
+    Some(Challenge {
+        flags: read_u32(data, 20)?,
+        server_challenge,
+        target_info,
+    })
+}
+
+/// 计算 NTLMv2 响应，生成 AUTHENTICATE_MESSAGE（type 3）
+fn authenticate_message(
+    credentials: &Credentials,
+    challenge: &Challenge,
+    client_challenge: [u8; 8],
+    timestamp: u64,
+) -> Vec<u8> {
+    let (domain, user) = split_domain(&credentials.user);
+    let password = credentials.password.as_deref().unwrap_or_default();
+    let key = ntowf_v2(user, domain, password);
+
+    // 服务器给出了时间戳时应使用它，并且不再发送 LMv2 响应
+    let server_timestamp = av_timestamp(&challenge.target_info);
+    let temp = ntlmv2_temp(
+        server_timestamp.unwrap_or_else(|| timestamp.to_le_bytes()),
+        client_challenge,
+        &challenge.target_info,
+    );
+    let mut nt_response = nt_proof(&key, &challenge.server_challenge, &temp);
+    nt_response.extend_from_slice(&temp);
+    let lm_response = match server_timestamp {
+        Some(_) => vec![0; 24],
+        None => {
+            let mut data = challenge.server_challenge.to_vec();
+            data.extend_from_slice(&client_challenge);
+            let mut lm = hmac_md5(&key, &data);
+            lm.extend_from_slice(&client_challenge);
+            lm
+        }
+    };
+
+    let unicode = challenge.flags & NEGOTIATE_UNICODE != 0;
+    let encode = |s: &str| if unicode { utf16(s) } else { s.as_bytes().to_vec() };
+    let flags = if unicode {
+        NEGOTIATE_FLAGS & !NEGOTIATE_OEM
+    } else {
+        NEGOTIATE_FLAGS & !NEGOTIATE_UNICODE
+    };
+    // 依次为 LmChallengeResponse、NtChallengeResponse、DomainName、UserName、Workstation、
+    // EncryptedRandomSessionKey
+    let payloads = [lm_response, nt_response, encode(domain), encode(user), vec![], vec![]];
+
+    let mut msg = SIGNATURE.to_vec();
+    msg.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = 64u32;
+    for payload in payloads.iter() {
+        let len = payload.len() as u16;
+        msg.extend_from_slice(&len.to_le_bytes());
+        msg.extend_from_slice(&len.to_le_bytes());
+        msg.extend_from_slice(&offset.to_le_bytes());
+        offset += len as u32;
+    }
+    msg.extend_from_slice(&flags.to_le_bytes());
+    for payload in payloads.iter() {
+        msg.extend_from_slice(payload);
+    }
+    msg
+}
+
+/// NTOWFv2 = HMAC_MD5(MD4(UNICODE(password)), UNICODE(Uppercase(user) + domain))
+fn ntowf_v2(user: &str, domain: &str, password: &str) -> Vec<u8> {
+    let hash = Md4::digest(utf16(password));
+    hmac_md5(&hash, &utf16(&format!("{}{}", user.to_uppercase(), domain)))
+}
+
+/// NTLMv2 响应中 NTProofStr 之后的部分（MS-NLMP 中的 temp）
+fn ntlmv2_temp(timestamp: [u8; 8], client_challenge: [u8; 8], target_info: &[u8]) -> Vec<u8> {
+    let mut temp = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    temp.extend_from_slice(&timestamp);
+    temp.extend_from_slice(&client_challenge);
+    temp.extend_from_slice(&[0; 4]);
+    temp.extend_from_slice(target_info);
+    temp.extend_from_slice(&[0; 4]);
+    temp
+}
+
+fn nt_proof(key: &[u8], server_challenge: &[u8], temp: &[u8]) -> Vec<u8> {
+    let mut data = server_challenge.to_vec();
+    data.extend_from_slice(temp);
+    hmac_md5(key, &data)
+}
+
+/// 在 target info 的 AV_PAIR 列表中查找 MsvAvTimestamp
+fn av_timestamp(target_info: &[u8]) -> Option<[u8; 8]> {
+    let mut pos = 0;
+    while pos + 4 <= target_info.len() {
+        let id = read_u16(target_info, pos)?;
+        let len = read_u16(target_info, pos + 2)? as usize;
+        let value = target_info.get(pos + 4..pos + 4 + len)?;
+        match id {
+            // MsvAvEOL
+            0 => break,
+            AV_TIMESTAMP if len == 8 => {
+                let mut timestamp = [0; 8];
+                timestamp.copy_from_slice(value);
+                return Some(timestamp);
+            }
+            _ => pos += 4 + len,
+        }
+    }
+    None
+}
+
+/// 用户名可以写成 DOMAIN\user
+fn split_domain(user: &str) -> (&str, &str) {
+    match user.find('\\') {
+        Some(pos) => (&user[..pos], &user[pos + 1..]),
+        None => ("", user),
+    }
+}
+
+fn hmac_md5(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([*data.get(pos)?, *data.get(pos + 1)?]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// 当前时间，单位为从 1601-01-01 开始的 100 纳秒
+fn filetime() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    FILETIME_EPOCH + now.as_secs() * 10_000_000 + now.subsec_nanos() as u64 / 100
+}
+
+/// 在质询的 header 中找到 NTLM 或 Negotiate 方案，返回方案名和其后 base64 解码的数据
+fn find_scheme(resp: &Response, name: &HeaderName) -> Option<(String, Vec<u8>)> {
+    let values: Vec<&str> = resp
+        .headers()
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    // 同时支持两种方案时优先使用 NTLM
+    ["NTLM", "Negotiate"].iter().find_map(|scheme| {
+        values.iter().find_map(|value| {
+            let value = value.trim();
+            let rest = value.get(scheme.len()..)?;
+            if !value[..scheme.len()].eq_ignore_ascii_case(scheme) {
+                return None;
+            }
+            match rest.trim() {
+                "" => Some((scheme.to_string(), Vec::new())),
+                data if rest.starts_with(' ') => {
+                    Some((scheme.to_string(), base64::decode(data).ok()?))
+                }
+                _ => None,
+            }
+        })
+    })
+}
+
+/// 发送请求，如果服务器（401）或代理（407）要求 NTLM 认证，完成 negotiate / challenge /
+/// authenticate 的握手后重新发送
+/// 握手需要在同一个连接上完成，依赖连接池复用连接；https 请求经过代理时 CONNECT 由 reqwest
+/// 处理，它只支持 Basic 认证，代理要求认证时返回明确的错误
+pub async fn send(
+    req: RequestBuilder,
+    credentials: &Credentials,
+    upload: &Upload,
+) -> Result<Response> {
+    // 流式的body无法重新发送，这种情况下只能直接发出请求
+    let (negotiate, authenticate) = match (req.try_clone(), req.try_clone()) {
+        (Some(negotiate), Some(authenticate)) => (negotiate, authenticate),
+        _ => return Ok(upload.apply(req)?.send().await?),
+    };
+    let resp = upload.apply(req)?.send().await.map_err(tunnel_error)?;
+    let (challenge_header, auth_header) = match resp.status() {
+        StatusCode::UNAUTHORIZED => (header::WWW_AUTHENTICATE, header::AUTHORIZATION),
+        StatusCode::PROXY_AUTHENTICATION_REQUIRED => {
+            (header::PROXY_AUTHENTICATE, header::PROXY_AUTHORIZATION)
+        }
+        _ => return Ok(resp),
+    };
+    let scheme = match find_scheme(&resp, &challenge_header) {
+        Some((scheme, _)) => scheme,
+        None => return Ok(resp),
+    };
+    // 读完响应才能让连接回到连接池，后续的握手才能复用同一个连接
+    resp.bytes().await?;
+
+    let value = format!("{} {}", scheme, base64::encode(negotiate_message()));
+    let resp = upload.apply(negotiate.header(&auth_header, value))?.send().await?;
+    let challenge = find_scheme(&resp, &challenge_header)
+        .and_then(|(_, data)| parse_challenge(&data));
+    let challenge = match challenge {
+        Some(challenge) => challenge,
+        None => return Ok(resp),
+    };
+    resp.bytes().await?;
+
+    let mut client_challenge = [0; 8];
+    rand::thread_rng().fill(&mut client_challenge);
+    let msg = authenticate_message(credentials, &challenge, client_challenge, filetime());
+    let value = format!("{} {}", scheme, base64::encode(msg));
+    let req = upload.apply(authenticate.header(&auth_header, value))?;
+    Ok(req.send().await?)
+}
+
+/// CONNECT 隧道被代理以 407 拒绝时，说明不能用 NTLM 完成代理认证
+fn tunnel_error(e: reqwest::Error) -> anyhow::Error {
+    let mut source = std::error::Error::source(&e);
+    while let Some(err) = source {
+        if err.to_string() == "proxy authentication required" {
+            return anyhow!(
+                "The proxy requires authentication for the CONNECT tunnel of this https request, \
+                 --auth-type ntlm cannot authenticate with it (only --proxy-auth basic credentials are supported)"
+            );
+        }
+        source = err.source();
+    }
+    e.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // MS-NLMP 4.2.4 中 NTLMv2 的例子
+    const SERVER_CHALLENGE: [u8; 8] = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+    const CLIENT_CHALLENGE: [u8; 8] = [0xaa; 8];
+    const TARGET_INFO: [u8; 36] = [
+        0x02, 0x00, 0x0c, 0x00, b'D', 0, b'o', 0, b'm', 0, b'a', 0, b'i', 0, b'n', 0, 0x01, 0x00,
+        0x0c, 0x00, b'S', 0, b'e', 0, b'r', 0, b'v', 0, b'e', 0, b'r', 0, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn ntlmv2_works() {
+        let key = ntowf_v2("User", "Domain", "Password");
+        assert_eq!(hex(&key), "0c868a403bfd7a93a3001ef22ef02e3f");
+        let temp = ntlmv2_temp([0; 8], CLIENT_CHALLENGE, &TARGET_INFO);
+        assert_eq!(
+            hex(&nt_proof(&key, &SERVER_CHALLENGE, &temp)),
+            "68cd0ab851e51c96aabc927bebef6a1c"
+        );
+    }
+
+    #[test]
+    fn messages_work() {
+        // 构造一个 CHALLENGE_MESSAGE
+        let mut data = SIGNATURE.to_vec();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&(NEGOTIATE_UNICODE | NEGOTIATE_NTLM).to_le_bytes());
+        data.extend_from_slice(&SERVER_CHALLENGE);
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[36, 0, 36, 0, 48, 0, 0, 0]);
+        data.extend_from_slice(&TARGET_INFO);
+        let challenge = parse_challenge(&data).unwrap();
+        assert_eq!(challenge.server_challenge, SERVER_CHALLENGE);
+        assert_eq!(challenge.target_info, TARGET_INFO);
+        assert!(parse_challenge(&negotiate_message()).is_none());
+
+        let credentials = Credentials {
+            user: "Domain\\User".into(),
+            password: Some("Password".into()),
+        };
+        let msg = authenticate_message(&credentials, &challenge, CLIENT_CHALLENGE, 0);
+        assert_eq!(&msg[..12], b"NTLMSSP\0\x03\0\0\0");
+        // NtChallengeResponse 以 NTProofStr 开头
+        let offset = read_u32(&msg, 24).unwrap() as usize;
+        assert_eq!(hex(&msg[offset..offset + 16]), "68cd0ab851e51c96aabc927bebef6a1c");
+        // UserName 使用 UTF-16LE 编码
+        let len = read_u16(&msg, 36).unwrap() as usize;
+        let offset = read_u32(&msg, 40).unwrap() as usize;
+        assert_eq!(&msg[offset..offset + len], &utf16("User")[..]);
+    }
+
+    #[tokio::test]
+    async fn tunnel_error_works() {
+        use std::io::{Read, Write};
+        // 拒绝 CONNECT 的代理
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+            let resp = "HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: NTLM\r\n\r\n";
+            let _ = stream.write_all(resp.as_bytes());
+        });
+        let client = reqwest::Client::builder()
+            .proxy(reqwest::Proxy::all(&proxy).unwrap())
+            .build()
+            .unwrap();
+        let credentials = Credentials { user: "User".into(), password: Some("Password".into()) };
+        let upload = Upload::new(None, false, None);
+        let err = send(client.get("https://example.com/"), &credentials, &upload).await.unwrap_err();
+        assert!(err.to_string().contains("--auth-type ntlm cannot authenticate"));
+    }
+}
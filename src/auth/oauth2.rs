@@ -0,0 +1,431 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgEnum;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use super::Credentials;
+use crate::upload::Upload;
+
+// 缓存的 token 在过期前这么多秒就认为已经失效，避免请求途中过期
+const EXPIRY_MARGIN: u64 = 30;
+// 等待用户在浏览器中完成授权的最长时间
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+// --oauth2-flow，获取 access token 的方式
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum OAuth2Flow {
+    // 使用 client id 和 secret 直接获取，适用于服务之间的调用
+    ClientCredentials,
+    // RFC 8628 device code，用户在浏览器中输入验证码
+    Device,
+    // RFC 7636 authorization code + PKCE，浏览器授权后回调本地端口
+    Pkce,
+}
+
+/// OAuth2 认证，client id 和 secret 来自 -a/--auth
+#[derive(Debug, PartialEq)]
+pub struct OAuth2 {
+    pub credentials: Credentials,
+    pub flow: OAuth2Flow,
+    pub token_url: String,
+    // device flow 的 device authorization endpoint
+    pub device_url: Option<String>,
+    // pkce flow 的 authorization endpoint
+    pub auth_url: Option<String>,
+    // pkce flow 本地回调监听的端口，0 表示随机端口
+    pub redirect_port: u16,
+    pub scope: Option<String>,
+}
+
+/// token endpoint 返回的 token
+#[derive(Debug, PartialEq)]
+struct Token {
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+impl OAuth2 {
+    /// 获取 access token 后作为 Bearer token 发送请求
+    /// 使用缓存的 token 被服务器拒绝时，丢弃缓存的 access token 重新获取一次再发送
+    pub async fn send(&self, client: &Client, req: RequestBuilder, upload: &Upload) -> Result<Response> {
+        let (token, cached) = match self.cached_token() {
+            Some(token) => (token, true),
+            None => (self.new_token(client).await?, false),
+        };
+        let retry = if cached { req.try_clone() } else { None };
+        let resp = upload.apply(req.bearer_auth(&token))?.send().await?;
+        match retry {
+            Some(retry) if resp.status() == StatusCode::UNAUTHORIZED => {
+                let token = self.new_token(client).await?;
+                Ok(upload.apply(retry.bearer_auth(&token))?.send().await?)
+            }
+            _ => Ok(resp),
+        }
+    }
+
+    // 缓存文件中区分不同 token 的键
+    fn cache_key(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.token_url,
+            self.credentials.user,
+            self.scope.as_deref().unwrap_or_default()
+        )
+    }
+
+    fn cached_token(&self) -> Option<String> {
+        let cache = load_cache();
+        let entry = cache.get(&self.cache_key())?;
+        let expires_at = entry["expires_at"].as_u64()?;
+        if now() + EXPIRY_MARGIN >= expires_at {
+            return None;
+        }
+        entry["access_token"].as_str().map(|s| s.to_string())
+    }
+
+    fn cached_refresh_token(&self) -> Option<String> {
+        let cache = load_cache();
+        let entry = cache.get(&self.cache_key())?;
+        entry["refresh_token"].as_str().map(|s| s.to_string())
+    }
+
+    /// 获取新的 access token 并写入缓存，有 refresh token 时优先使用它刷新
+    async fn new_token(&self, client: &Client) -> Result<String> {
+        let mut token = None;
+        if let Some(refresh_token) = self.cached_refresh_token() {
+            let form = [("grant_type", "refresh_token"), ("refresh_token", &refresh_token)];
+            // refresh token 失效时重新走一遍授权流程
+            if let Ok(mut t) = parse_token(&self.post_token(client, &form).await?) {
+                // 服务器没有返回新 refresh token 时继续使用原来的
+                t.refresh_token.get_or_insert(refresh_token);
+                token = Some(t);
+            }
+        }
+        let token = match token {
+            Some(token) => token,
+            None => match self.flow {
+                OAuth2Flow::ClientCredentials => {
+                    let mut form = vec![("grant_type", "client_credentials")];
+                    if let Some(ref scope) = self.scope {
+                        form.push(("scope", scope));
+                    }
+                    parse_token(&self.post_token(client, &form).await?)?
+                }
+                OAuth2Flow::Device => self.device_login(client).await?,
+                OAuth2Flow::Pkce => self.pkce_login(client).await?,
+            },
+        };
+        self.store_token(&token);
+        Ok(token.access_token)
+    }
+
+    fn store_token(&self, token: &Token) {
+        let mut entry = Map::new();
+        entry.insert("access_token".into(), json!(token.access_token));
+        // 没有给出有效期的 token 只保存 refresh token
+        if let Some(expires_in) = token.expires_in {
+            entry.insert("expires_at".into(), json!(now() + expires_in));
+        }
+        if let Some(ref refresh_token) = token.refresh_token {
+            entry.insert("refresh_token".into(), json!(refresh_token));
+        }
+        if token.expires_in.is_none() && token.refresh_token.is_none() {
+            return;
+        }
+        let mut cache = load_cache();
+        cache.insert(self.cache_key(), Value::Object(entry));
+        // 缓存写入失败不影响本次请求
+        let _ = store_cache(&cache);
+    }
+
+    /// 请求 token endpoint，返回解析后的 JSON，错误响应也以 JSON 返回
+    /// 有 client secret 时使用 basic auth，否则把 client id 放在表单中（public client）
+    async fn post_token(&self, client: &Client, form: &[(&str, &str)]) -> Result<Value> {
+        self.post_form(client, &self.token_url, form).await
+    }
+
+    async fn post_form(&self, client: &Client, url: &str, form: &[(&str, &str)]) -> Result<Value> {
+        let mut form = form.to_vec();
+        let mut req = client.post(url);
+        match self.credentials.password {
+            Some(ref secret) => req = req.basic_auth(&self.credentials.user, Some(secret)),
+            None => form.push(("client_id", &self.credentials.user)),
+        }
+        let resp = req
+            .form(&form)
+            .send()
+            .await
+            .with_context(|| format!("Failed to request {}", url))?;
+        let status = resp.status();
+        let body = resp.text().await?;
+        serde_json::from_str(&body)
+            .with_context(|| format!("Invalid OAuth2 response ({}): {}", status, body))
+    }
+
+    /// RFC 8628 device authorization grant
+    async fn device_login(&self, client: &Client) -> Result<Token> {
+        let device_url = self
+            .device_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("--oauth2-device-url is required for --oauth2-flow device"))?;
+        let mut form = vec![];
+        if let Some(ref scope) = self.scope {
+            form.push(("scope", scope.as_str()));
+        }
+        let resp = self.post_form(client, device_url, &form).await?;
+        let device_code = resp["device_code"]
+            .as_str()
+            .ok_or_else(|| anyhow!("OAuth2 device authorization failed: {}", resp))?;
+        let user_code = resp["user_code"].as_str().unwrap_or_default();
+        let uri = resp["verification_uri"].as_str().unwrap_or_default();
+        eprintln!("http: open {} and enter the code {}", uri, user_code);
+        open_browser(resp["verification_uri_complete"].as_str().unwrap_or(uri));
+
+        let mut interval = resp["interval"].as_u64().unwrap_or(5);
+        let expires_at = now() + resp["expires_in"].as_u64().unwrap_or(LOGIN_TIMEOUT.as_secs());
+        let form = [
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("device_code", device_code),
+        ];
+        while now() < expires_at {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            let resp = self.post_token(client, &form).await?;
+            match resp["error"].as_str() {
+                Some("authorization_pending") => {}
+                // 服务器要求降低轮询频率
+                Some("slow_down") => interval += 5,
+                _ => return parse_token(&resp),
+            }
+        }
+        Err(anyhow!("OAuth2 device code expired before authorization was granted"))
+    }
+
+    /// RFC 7636 authorization code grant with PKCE
+    /// 在本地监听回调端口，打开浏览器让用户授权，然后使用授权码换取 token
+    async fn pkce_login(&self, client: &Client) -> Result<Token> {
+        let auth_url = self
+            .auth_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("--oauth2-auth-url is required for --oauth2-flow pkce"))?;
+        let listener = TcpListener::bind(("127.0.0.1", self.redirect_port))
+            .await
+            .context("Failed to listen for the OAuth2 redirect")?;
+        let redirect_uri = format!("http://127.0.0.1:{}/callback", listener.local_addr()?.port());
+        let verifier = random_string(64);
+        let state = random_string(16);
+
+        let mut url: Url = auth_url.parse()?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.credentials.user)
+            .append_pair("redirect_uri", &redirect_uri)
+            .append_pair("code_challenge", &code_challenge(&verifier))
+            .append_pair("code_challenge_method", "S256")
+            .append_pair("state", &state);
+        if let Some(ref scope) = self.scope {
+            url.query_pairs_mut().append_pair("scope", scope);
+        }
+        eprintln!("http: open {} to log in", url);
+        open_browser(url.as_str());
+
+        let code = tokio::time::timeout(LOGIN_TIMEOUT, wait_for_code(&listener, &state))
+            .await
+            .map_err(|_| anyhow!("Timed out waiting for the OAuth2 redirect"))??;
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("redirect_uri", &redirect_uri),
+            ("code_verifier", &verifier),
+        ];
+        parse_token(&self.post_token(client, &form).await?)
+    }
+}
+
+/// 等待浏览器回调 /callback?code=...&state=...，返回授权码
+async fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut buf = vec![0; 8192];
+        let n = stream.read(&mut buf).await?;
+        let request = String::from_utf8_lossy(&buf[..n]);
+        // 请求行形如 GET /callback?code=xxx&state=yyy HTTP/1.1
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+        let url: Url = format!("http://127.0.0.1{}", path).parse()?;
+        if url.path() != "/callback" {
+            stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").await?;
+            continue;
+        }
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.to_string())
+        };
+        let (status, message) = match (param("code"), param("error")) {
+            (Some(_), _) if param("state").as_deref() != Some(state) => {
+                ("400 Bad Request", "OAuth2 state mismatch".to_string())
+            }
+            (Some(_), _) => ("200 OK", "Login succeeded, you can close this window".to_string()),
+            (None, error) => (
+                "400 Bad Request",
+                format!("OAuth2 authorization failed: {}", error.unwrap_or_default()),
+            ),
+        };
+        let resp = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            message.len(),
+            message
+        );
+        stream.write_all(resp.as_bytes()).await?;
+        return match param("code") {
+            Some(code) if status == "200 OK" => Ok(code),
+            _ => Err(anyhow!(message)),
+        };
+    }
+}
+
+/// 解析 token endpoint 的响应
+fn parse_token(value: &Value) -> Result<Token> {
+    let access_token = match value["access_token"].as_str() {
+        Some(token) => token.to_string(),
+        None => {
+            return Err(match value["error"].as_str() {
+                Some(error) => anyhow!(
+                    "OAuth2 token request failed: {} {}",
+                    error,
+                    value["error_description"].as_str().unwrap_or_default()
+                ),
+                None => anyhow!("No access_token in OAuth2 token response: {}", value),
+            })
+        }
+    };
+    // 有些服务器会把 expires_in 作为字符串返回
+    let expires_in = match value["expires_in"] {
+        Value::Number(ref n) => n.as_u64(),
+        Value::String(ref s) => s.parse().ok(),
+        _ => None,
+    };
+    Ok(Token {
+        access_token,
+        expires_in,
+        refresh_token: value["refresh_token"].as_str().map(|s| s.to_string()),
+    })
+}
+
+/// PKCE 的 code_challenge = BASE64URL(SHA256(code_verifier))
+fn code_challenge(verifier: &str) -> String {
+    base64::encode_config(Sha256::digest(verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// 尝试用系统浏览器打开链接，失败时用户可以手动打开输出的链接
+fn open_browser(url: &str) {
+    let mut cmd = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let _ = cmd
+        .arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+}
+
+/// 配置目录，优先使用 XDG_CONFIG_HOME，否则为 ~/.config/httpie
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join("httpie"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("httpie"))
+}
+
+fn cache_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("oauth2_tokens.json"))
+}
+
+fn load_cache() -> Map<String, Value> {
+    cache_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn store_cache(cache: &Map<String, Value>) -> Result<()> {
+    let path = cache_path().ok_or_else(|| anyhow!("Cannot find the config directory"))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // token 相当于密码，只允许当前用户读写，创建时就设置好权限，避免写入后到改权限之间被其他用户读到
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path)?;
+    // 已经存在的文件不受 mode 影响，先改权限再写入
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(serde_json::to_string_pretty(cache)?.as_bytes())?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_token_works() {
+        let value = json!({"access_token": "abc", "token_type": "Bearer", "expires_in": 3600});
+        let token = parse_token(&value).unwrap();
+        assert_eq!(token.access_token, "abc");
+        assert_eq!(token.expires_in, Some(3600));
+        let value = json!({"access_token": "abc", "expires_in": "60", "refresh_token": "r"});
+        let token = parse_token(&value).unwrap();
+        assert_eq!(token.expires_in, Some(60));
+        assert_eq!(token.refresh_token.as_deref(), Some("r"));
+        assert_eq!(parse_token(&json!({"access_token": "abc"})).unwrap().expires_in, None);
+        assert!(parse_token(&json!({"error": "invalid_client"})).is_err());
+    }
+
+    #[test]
+    fn code_challenge_works() {
+        // RFC 7636 附录 B 中的例子
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::str::FromStr;
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use reqwest::{Request, RequestBuilder};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use super::AuthArgs;
+
+/// --sign 支持的 HMAC 算法
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// --sign algorithm:key，对 method、path 和 body 计算 HMAC 签名，按模板放入 header
+#[derive(Debug, PartialEq)]
+pub struct HmacSigner {
+    algorithm: Algorithm,
+    key: String,
+    // header 名称和值的模板，模板中的 {signature} 替换为十六进制的签名，
+    // {signature_base64} 替换为 base64 编码的签名
+    header: String,
+    template: String,
+}
+
+impl FromStr for HmacSigner {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, key) = match s.find(':') {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => return Err(anyhow!("Failed to parse {}, expect algorithm:key", s)),
+        };
+        let algorithm = match algorithm.to_ascii_lowercase().as_str() {
+            "hmac-sha1" => Algorithm::Sha1,
+            "hmac-sha256" => Algorithm::Sha256,
+            "hmac-sha512" => Algorithm::Sha512,
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported signing algorithm {}, expect hmac-sha1, hmac-sha256 or hmac-sha512",
+                    algorithm
+                ))
+            }
+        };
+        Ok(Self {
+            algorithm,
+            key: key.to_string(),
+            header: String::new(),
+            template: String::new(),
+        })
+    }
+}
+
+impl HmacSigner {
+    /// 根据 --sign 和 --sign-header 生成签名方式
+    pub fn from_args(args: &AuthArgs) -> Result<Option<Self>> {
+        match args.sign {
+            Some(ref sign) => Ok(Some(Self::new(sign, &args.sign_header)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 解析 --sign 和 --sign-header
+    pub fn new(sign: &str, header: &str) -> Result<Self> {
+        let mut signer: Self = sign.parse()?;
+        let (name, template) = match header.find(':') {
+            Some(pos) => (header[..pos].trim(), header[pos + 1..].trim()),
+            None => return Err(anyhow!("Failed to parse {}, expect Header: template", header)),
+        };
+        if name.is_empty() || !template.contains("{signature") {
+            return Err(anyhow!(
+                "Failed to parse {}, the template should contain {{signature}} or {{signature_base64}}",
+                header
+            ));
+        }
+        signer.header = name.to_string();
+        signer.template = template.to_string();
+        Ok(signer)
+    }
+
+    /// 计算签名并加上 header，需要完整的 body，因此不支持流式上传的文件
+    pub fn apply(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        let request = req
+            .try_clone()
+            .ok_or_else(|| anyhow!("Cannot sign a streaming request body"))?
+            .build()?;
+        let signature = self.signature(&request);
+        let value = self
+            .template
+            .replace("{signature_base64}", &base64::encode(&signature))
+            .replace("{signature}", &hex(&signature));
+        Ok(req.header(self.header.as_str(), value))
+    }
+
+    /// 签名的内容为 METHOD\npath?query\nbody
+    fn signature(&self, request: &Request) -> Vec<u8> {
+        let url = request.url();
+        let mut message = match url.query() {
+            Some(query) => format!("{}\n{}?{}\n", request.method(), url.path(), query),
+            None => format!("{}\n{}\n", request.method(), url.path()),
+        }
+        .into_bytes();
+        if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+            message.extend_from_slice(body);
+        }
+        let key = self.key.as_bytes();
+        match self.algorithm {
+            Algorithm::Sha1 => hmac::<Hmac<Sha1>>(key, &message),
+            Algorithm::Sha256 => hmac::<Hmac<Sha256>>(key, &message),
+            Algorithm::Sha512 => hmac::<Hmac<Sha512>>(key, &message),
+        }
+    }
+}
+
+fn hmac<M: Mac + hmac::digest::KeyInit>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_signer_works() {
+        let signer =
+            HmacSigner::new("hmac-sha256:secret", "X-Signature: sha256={signature}").unwrap();
+        let req = reqwest::Client::new().post("http://abc.xyz/hook?a=1").body("{}");
+        let req = signer.apply(req).unwrap().build().unwrap();
+        // echo -ne 'POST\n/hook?a=1\n{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            req.headers()["x-signature"],
+            "sha256=67e34da6689f017f6bcbb2d6ee4b1268eed71b2f412198f1dfd3dc8f4e4a9946"
+        );
+
+        assert!(HmacSigner::new("md5:secret", "X-Signature: {signature}").is_err());
+        assert!(HmacSigner::new("hmac-sha256:secret", "X-Signature: abc").is_err());
+        assert!(HmacSigner::new("hmac-sha256", "X-Signature: {signature}").is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::io::Write;
+use anyhow::Result;
+use colored::*;
+use mime::Mime;
+
+// 检查控制字符时最多看 body 开头的这么多字节
+const SAMPLE_LEN: usize = 1024;
+// --hex 每一行显示的字节数
+const HEX_WIDTH: usize = 16;
+
+/// 是否是不适合打印到终端的二进制 body：Content-Type 是图片、音视频、压缩包等二进制类型，
+/// 或者 body 的开头有 NUL 字节或较多的控制字符
+pub fn is_binary(mime: Option<&Mime>, body: &[u8]) -> bool {
+    if mime.is_some_and(is_binary_mime) {
+        return true;
+    }
+    let sample = &body[..body.len().min(SAMPLE_LEN)];
+    if sample.contains(&0) {
+        return true;
+    }
+    // 制表符、换行、换页和 ANSI 转义序列使用的 ESC 在文本中很常见
+    let controls = sample
+        .iter()
+        .filter(|&&b| (b < 0x20 && !b"\t\n\r\x0c\x1b".contains(&b)) || b == 0x7f)
+        .count();
+    controls * 10 > sample.len()
+}
+
+fn is_binary_mime(mime: &Mime) -> bool {
+    // image/svg+xml 等带 +json / +xml 后缀的类型是文本
+    if mime.suffix().is_some_and(|s| s == mime::JSON || s == mime::XML) {
+        return false;
+    }
+    match mime.type_() {
+        mime::IMAGE | mime::AUDIO | mime::VIDEO | mime::FONT => true,
+        mime::APPLICATION => matches!(
+            mime.subtype().as_str(),
+            "octet-stream"
+                | "pdf"
+                | "zip"
+                | "gzip"
+                | "x-gzip"
+                | "x-tar"
+                | "x-bzip2"
+                | "x-xz"
+                | "zstd"
+                | "x-7z-compressed"
+                | "x-rar-compressed"
+                | "wasm"
+                | "protobuf"
+                | "x-protobuf"
+                | "msgpack"
+                | "x-msgpack"
+                | "cbor"
+                | "vnd.android.package-archive"
+        ),
+        _ => false,
+    }
+}
+
+/// 终端上只打印一条提示，输出被重定向到文件或者管道时原样写出二进制的 body
+pub fn print_binary(body: &[u8]) -> Result<()> {
+    if atty::isnt(atty::Stream::Stdout) {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(body)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+    print_binary_notice(Some(body.len() as u64));
+    Ok(())
+}
+
+/// 在终端上提示二进制的 body 没有打印，边接收边打印时只知道 Content-Length
+pub fn print_binary_notice(len: Option<u64>) {
+    let msg = match len {
+        Some(len) => format!(
+            "+ Binary body ({} bytes) suppressed, use --output FILE or --hex to see it +",
+            len
+        ),
+        None => "+ Binary body suppressed, use --output FILE or --hex to see it +".to_string(),
+    };
+    println!("{}", msg.yellow());
+}
+
+/// 格式化合法的 JSON，jsonxf 本身不检查 JSON 是否合法，所以先用 serde_json 解析一遍
+/// 可以是换行分隔的多个 JSON（NDJSON）
+pub fn format_json(s: &str) -> Option<String> {
+    let mut values = serde_json::Deserializer::from_str(s).into_iter::<serde_json::Value>();
+    if !values.all(|v| v.is_ok()) {
+        return None;
+    }
+    jsonxf::pretty_print(s).ok()
+}
+
+/// 取出 buf 中所有完整的行（不含换行符），剩下没有换行的部分留在 buf 中
+pub fn take_lines(buf: &mut String) -> Vec<String> {
+    let end = match buf.rfind('\n') {
+        Some(i) => i + 1,
+        None => return Vec::new(),
+    };
+    let lines = buf[..end]
+        .lines()
+        .map(|line| line.to_string())
+        .collect();
+    buf.drain(..end);
+    lines
+}
+
+/// 边接收边生成 --hex 的输出，每一行和 hexdump -C 一样是 offset、十六进制和 ASCII，
+/// 最多显示 limit 个字节，0 表示不限制
+pub struct HexDump {
+    offset: u64,
+    pending: Vec<u8>,
+    limit: u64,
+    truncated: bool,
+}
+
+impl HexDump {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            offset: 0,
+            pending: Vec::new(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    /// 加入收到的数据，返回凑满的行
+    pub fn push(&mut self, mut data: &[u8]) -> Vec<String> {
+        if self.limit > 0 {
+            let left = self.limit - self.offset - self.pending.len() as u64;
+            if data.len() as u64 > left {
+                data = &data[..left as usize];
+                self.truncated = true;
+            }
+        }
+        self.pending.extend_from_slice(data);
+        let full = self.pending.len() / HEX_WIDTH * HEX_WIDTH;
+        let lines = self.pending[..full]
+            .chunks(HEX_WIDTH)
+            .enumerate()
+            .map(|(i, line)| hex_line(self.offset + (i * HEX_WIDTH) as u64, line))
+            .collect();
+        self.offset += full as u64;
+        self.pending.drain(..full);
+        lines
+    }
+
+    /// 超过 limit 的数据被丢弃了，不需要再读取
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// body 读完之后剩下的不满一行的数据
+    pub fn finish(self) -> Option<String> {
+        Some(hex_line(self.offset, &self.pending)).filter(|_| !self.pending.is_empty())
+    }
+}
+
+fn hex_line(offset: u64, bytes: &[u8]) -> String {
+    let mut line = format!("{:08x}  ", offset);
+    for i in 0..HEX_WIDTH {
+        match bytes.get(i) {
+            Some(b) => line.push_str(&format!("{:02x} ", b)),
+            None => line.push_str("   "),
+        }
+        if i == HEX_WIDTH / 2 - 1 {
+            line.push(' ');
+        }
+    }
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+        .collect();
+    line.push_str(&format!(" |{}|", ascii));
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_binary_works() {
+        let mime = |s: &str| s.parse::<Mime>().unwrap();
+        assert!(is_binary(Some(&mime("image/png")), b"text"));
+        assert!(is_binary(Some(&mime("application/octet-stream")), b""));
+        assert!(!is_binary(Some(&mime("image/svg+xml")), b"<svg/>"));
+        assert!(!is_binary(Some(&mime("application/json")), b"{}"));
+        assert!(is_binary(None, b"GIF89a\x00\x01"));
+        assert!(is_binary(Some(&mime("text/plain")), b"\x01\x02\x03 abc"));
+        assert!(!is_binary(None, "第一行\r\n\tsecond \x1b[31mred\x1b[0m".as_bytes()));
+    }
+
+    #[test]
+    fn format_json_works() {
+        assert_eq!(format_json("{\"a\":1}").unwrap(), "{\n  \"a\": 1\n}");
+        assert!(format_json("{\"a\":1}\n{\"b\":2}\n").is_some());
+        assert!(format_json("{\"a\":").is_none());
+        assert!(format_json("plain\ntext").is_none());
+    }
+
+    #[test]
+    fn hex_dump_works() {
+        let mut dump = HexDump::new(0);
+        assert!(dump.push(b"0123456789").is_empty());
+        assert_eq!(
+            dump.push(b"abcdef\x00\n"),
+            vec!["00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|"]
+        );
+        assert_eq!(
+            dump.finish().unwrap(),
+            "00000010  00 0a                                             |..|"
+        );
+        let mut dump = HexDump::new(20);
+        assert_eq!(dump.push(&[b'x'; 16]).len(), 1);
+        assert!(!dump.truncated());
+        assert!(dump.push(&[b'y'; 10]).is_empty());
+        assert!(dump.truncated());
+        assert!(dump.finish().unwrap().ends_with("|yyyy|"));
+    }
+
+    #[test]
+    fn take_lines_works() {
+        let mut buf = "{\"a\":1}\r\n\n{\"b\"".to_string();
+        assert_eq!(take_lines(&mut buf), vec!["{\"a\":1}", ""]);
+        assert_eq!(buf, "{\"b\"");
+        assert!(take_lines(&mut buf).is_empty());
+        buf.push_str(":2}\n");
+        assert_eq!(take_lines(&mut buf), vec!["{\"b\":2}"]);
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+use chardetng::EncodingDetector;
+use colored::*;
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::quiet;
+
+// 在 body 开头的这么多字节中查找 <meta charset> 和 XML 声明
+const SNIFF_LEN: usize = 1024;
+
+/// 边接收边把文本的 body 解码成 UTF-8，第一块数据到达时选择编码：
+/// BOM 优先，然后是 Content-Type 中的 charset，没有 charset 时从 body 中探测
+/// 结束时如果有无法解码的字节，警告并用 U+FFFD 代替
+pub struct TextDecoder {
+    charset: Option<String>,
+    decoder: Option<encoding_rs::Decoder>,
+    encoding: &'static Encoding,
+    replaced: bool,
+}
+
+impl TextDecoder {
+    /// charset 是 Content-Type 中的 charset 参数
+    pub fn new(charset: Option<String>) -> Self {
+        Self {
+            charset,
+            decoder: None,
+            encoding: UTF_8,
+            replaced: false,
+        }
+    }
+
+    /// 解码收到的一块数据，last 表示 body 已经读完
+    pub fn decode(&mut self, chunk: &[u8], last: bool) -> String {
+        let charset = &self.charset;
+        let encoding = &mut self.encoding;
+        let decoder = self.decoder.get_or_insert_with(|| {
+            *encoding = choose(charset.as_deref(), chunk);
+            encoding.new_decoder()
+        });
+        let len = decoder
+            .max_utf8_buffer_length(chunk.len())
+            .unwrap_or(chunk.len() * 3);
+        let mut text = String::with_capacity(len);
+        let (_, _, replaced) = decoder.decode_to_string(chunk, &mut text, last);
+        self.replaced |= replaced;
+        if last && self.replaced {
+            let msg = format!(
+                "http: warning: the body is not valid {}, invalid bytes are shown as U+FFFD",
+                self.encoding.name()
+            );
+            quiet::warn(msg.yellow());
+        }
+        text
+    }
+}
+
+/// 解码整个 body
+pub fn decode(charset: Option<String>, body: &[u8]) -> String {
+    TextDecoder::new(charset).decode(body, true)
+}
+
+/// 选择解码的编码，sample 是 body 开头的数据
+fn choose(charset: Option<&str>, sample: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    match charset {
+        Some(label) => Encoding::for_label(label.trim().as_bytes()).unwrap_or_else(|| {
+            let msg = format!("http: warning: unknown charset {}, decoding the body as UTF-8", label);
+            quiet::warn(msg.yellow());
+            UTF_8
+        }),
+        None => sniff(sample),
+    }
+}
+
+/// 没有 charset 时探测编码：合法的 UTF-8（末尾可能是不完整的字符）就是 UTF-8，
+/// 其次是 HTML 中的 <meta charset> 或者 XML 声明中的 encoding，最后用 chardetng 猜测
+fn sniff(sample: &[u8]) -> &'static Encoding {
+    let valid_utf8 = match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if valid_utf8 {
+        return UTF_8;
+    }
+    if let Some(encoding) = declared(&sample[..sample.len().min(SNIFF_LEN)]) {
+        return encoding;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, true);
+    detector.guess(None, false)
+}
+
+/// body 中声明的编码，例如 <meta charset="gbk"> 或者 <?xml version="1.0" encoding="GB2312"?>
+fn declared(head: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let key = if head.trim_start().starts_with("<?xml") {
+        "encoding="
+    } else {
+        "charset="
+    };
+    let value = head[head.find(key)? + key.len()..].trim_start_matches(['"', '\'']);
+    let end = value
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "-_:.".contains(c)))
+        .unwrap_or(value.len());
+    Encoding::for_label(&value.as_bytes()[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, WINDOWS_1252};
+
+    #[test]
+    fn choose_works() {
+        let (gbk, _, _) = GBK.encode("你好，世界");
+        assert_eq!(choose(Some("gbk"), &gbk), GBK);
+        // iso-8859-1 在 WHATWG 标准中是 windows-1252
+        assert_eq!(choose(Some("ISO-8859-1"), b"caf\xe9"), WINDOWS_1252);
+        assert_eq!(choose(Some("gbk"), b"\xef\xbb\xbfabc"), UTF_8);
+        assert_eq!(choose(Some("no-such-charset"), b"abc"), UTF_8);
+        assert_eq!(choose(None, "第一行".as_bytes()), UTF_8);
+        // 最后一个字符被截断了
+        assert_eq!(choose(None, &"第一行".as_bytes()[..8]), UTF_8);
+        let html = [&b"<html><head><meta charset=\"gb2312\"></head>"[..], &gbk].concat();
+        assert_eq!(choose(None, &html), GBK);
+        let xml = [&b"<?xml version=\"1.0\" encoding='GBK'?><a>"[..], &gbk].concat();
+        assert_eq!(choose(None, &xml), GBK);
+    }
+
+    #[test]
+    fn decode_works() {
+        let (gbk, _, _) = GBK.encode("你好，世界");
+        assert_eq!(decode(Some("gbk".into()), &gbk), "你好，世界");
+        assert_eq!(decode(Some("iso-8859-1".into()), b"caf\xe9"), "café");
+        let mut decoder = TextDecoder::new(None);
+        let text = "第一行".as_bytes();
+        let out = decoder.decode(&text[..4], false) + &decoder.decode(&text[4..], true);
+        assert_eq!(out, "第一行");
+        assert_eq!(decode(Some("utf-8".into()), b"a\xffb"), "a\u{fffd}b");
+    }
+}
@@ -0,0 +1,556 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use clap::Clap;
+use hyper::body::Bytes;
+use mime::Mime;
+use reqwest::{header, redirect, Client, Request, RequestBuilder, Response, StatusCode, Version};
+
+use crate::compress::{self, Decoder};
+use crate::dns::DnsArgs;
+use crate::proxy::ProxyArgs;
+use crate::throttle::{parse_rate, RateLimit};
+use crate::tls::TlsArgs;
+use crate::trailers;
+use crate::unix_socket;
+
+/// 创建HTTP客户端相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct ClientArgs {
+    /// Timeout for the whole request including reading the body, e.g. 30, 1.5s, 500ms, 2m
+    #[clap(long, global = true, parse(try_from_str = parse_duration))]
+    timeout: Option<Duration>,
+    /// Timeout for establishing the connection
+    #[clap(long, global = true, parse(try_from_str = parse_duration))]
+    connect_timeout: Option<Duration>,
+    /// Timeout for waiting for the response headers and between two reads of the body
+    #[clap(long, global = true, parse(try_from_str = parse_duration))]
+    read_timeout: Option<Duration>,
+    /// Abort the whole transfer, including redirects, retries and reading the body,
+    /// after this long
+    #[clap(long, global = true, parse(try_from_str = parse_duration))]
+    pub max_time: Option<Duration>,
+    /// Send HTTP/1.0 requests
+    #[clap(
+        long = "http1.0",
+        global = true,
+        conflicts_with_all = &["http1-1", "http2", "http2-prior-knowledge", "http3"]
+    )]
+    http1_0: bool,
+    /// Only use HTTP/1.1, also over TLS where HTTP/2 would be negotiated
+    #[clap(
+        long = "http1.1",
+        global = true,
+        conflicts_with_all = &["http2", "http2-prior-knowledge", "http3"]
+    )]
+    http1_1: bool,
+    /// Only use HTTP/2 (offered with ALPN, so https URLs only) and fail if the server
+    /// does not negotiate it
+    #[clap(long, global = true, conflicts_with = "http3")]
+    http2: bool,
+    /// Speak HTTP/2 right away without negotiating it, also over cleartext TCP (h2c)
+    #[clap(long, global = true, conflicts_with = "http3")]
+    http2_prior_knowledge: bool,
+    /// Only use HTTP/3 over QUIC (https URLs only), without trying TCP first
+    #[clap(long, global = true)]
+    http3: bool,
+    /// Cache file of the HTTP/3 services that servers advertise in Alt-Svc headers (the
+    /// same format as curl's). Later requests to a cached host are sent over HTTP/3
+    #[clap(long, global = true)]
+    pub alt_svc: Option<PathBuf>,
+    /// Send all requests over this Unix domain socket instead of TCP, e.g.
+    /// --unix-socket /var/run/docker.sock get http://localhost/containers/json
+    #[clap(
+        long,
+        global = true,
+        conflicts_with_all = &["http2-prior-knowledge", "http3", "alt-svc"]
+    )]
+    unix_socket: Option<PathBuf>,
+    /// Send requests from the address of this network interface, e.g. eth1. Uses its first
+    /// IPv4 address, or IPv6 with -6
+    #[clap(long, global = true, conflicts_with_all = &["local-address", "unix-socket"])]
+    interface: Option<String>,
+    /// Send requests from this local address on a multi-homed host, e.g. 192.168.1.5
+    #[clap(long, global = true, conflicts_with = "unix-socket")]
+    local_address: Option<IpAddr>,
+    /// Send TCP keepalive probes after the connection has been idle this long, e.g. 60, so
+    /// that long-lived streaming connections survive NAT and firewall timeouts
+    #[clap(long, global = true, parse(try_from_str = parse_duration))]
+    tcp_keepalive: Option<Duration>,
+    /// Disable Nagle's algorithm (TCP_NODELAY) so that small writes of latency-sensitive
+    /// requests are sent at once. This is the default, the flag overrides --no-tcp-nodelay
+    #[clap(long, global = true, overrides_with = "no-tcp-nodelay")]
+    tcp_nodelay: bool,
+    /// Leave Nagle's algorithm on, which batches small writes
+    #[clap(long, global = true, overrides_with = "tcp-nodelay")]
+    no_tcp_nodelay: bool,
+    /// Limit the transfer speed of request and response bodies to this many bytes per
+    /// second, with an optional k, m or g suffix, e.g. 500k
+    #[clap(long, global = true, parse(try_from_str = parse_rate))]
+    limit_rate: Option<RateLimit>,
+    /// Ask for a compressed response with Accept-Encoding: gzip, deflate, br, zstd
+    #[clap(long, global = true)]
+    compressed: bool,
+    /// Print or write (--output) compressed response bodies as received instead of decoding
+    /// them. --download always saves the body as received
+    #[clap(long, global = true)]
+    no_decompress: bool,
+    /// Send Expect: 100-continue with request bodies and hold the body back for a fixed
+    /// --expect-delay, so that a server rejecting the request (e.g. 401 or 413) within that
+    /// time answers before a large upload is sent. The 100 Continue response itself is not
+    /// waited for (the HTTP client drops it), the body is always sent after the delay
+    #[clap(long, global = true)]
+    expect_continue: bool,
+    /// How long --expect-continue holds the body back before sending it
+    #[clap(long, global = true, parse(try_from_str = parse_duration), default_value = "1s")]
+    expect_delay: Duration,
+    /// Send requests over a connection of their own that keeps the trailers of chunked
+    /// HTTP/1.1 and HTTP/2 responses (e.g. grpc-status, Server-Timing), and print them in a
+    /// Trailers section after the body. Asks for them with TE: trailers
+    #[clap(
+        long,
+        global = true,
+        conflicts_with_all = &[
+            "http3", "alt-svc", "unix-socket", "interface", "local-address", "limit-rate",
+            "expect-continue",
+        ]
+    )]
+    trailers: bool,
+}
+
+impl ClientArgs {
+    /// 根据命令行参数生成HTTP客户端，重定向由 Context::execute 处理
+    pub fn build(&self, proxy: &ProxyArgs, tls: &TlsArgs, dns: &DnsArgs) -> Result<Client> {
+        self.build_with(proxy, tls, dns, self.http3)
+    }
+
+    /// 只使用 HTTP/3 的客户端，用于 --alt-svc 中缓存的服务
+    pub fn build_h3(&self, proxy: &ProxyArgs, tls: &TlsArgs, dns: &DnsArgs) -> Result<Client> {
+        self.build_with(proxy, tls, dns, true)
+    }
+
+    fn build_with(
+        &self,
+        proxy: &ProxyArgs,
+        tls: &TlsArgs,
+        dns: &DnsArgs,
+        http3: bool,
+    ) -> Result<Client> {
+        let mut builder = Client::builder().redirect(redirect::Policy::none());
+        // 请求中的 Accept-Encoding 优先于这里的默认值
+        if self.compressed {
+            let mut headers = header::HeaderMap::new();
+            headers.insert(
+                header::ACCEPT_ENCODING,
+                header::HeaderValue::from_static(compress::ACCEPT_ENCODING),
+            );
+            builder = builder.default_headers(headers);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        // reqwest 默认不设置 keepalive
+        builder = builder.tcp_keepalive(self.tcp_keepalive).tcp_nodelay(self.tcp_nodelay());
+        if http3 {
+            builder = builder.http3_prior_knowledge();
+        } else if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        } else if self.http1_0 || self.http1_1 {
+            builder = builder.http1_only();
+        }
+        builder = dns.apply(builder, tls)?;
+        if let Some(ip) = self.source_address(dns)? {
+            builder = builder.local_address(ip);
+        }
+        let builder = match &self.unix_socket {
+            Some(_) if proxy.has_proxy() => {
+                return Err(anyhow!("--proxy cannot be used with --unix-socket"))
+            }
+            Some(path) => unix_socket::apply(builder, path)?,
+            None => proxy.apply(builder)?,
+        };
+        // QUIC 连接不经过代理
+        let alpn = if http3 { vec![b"h3".to_vec()] } else { self.alpn() };
+        let builder = tls.apply(builder, alpn)?;
+        Ok(builder.build()?)
+    }
+
+    /// 是否由客户端自己直接连接服务器，经过代理或者 --unix-socket 时不是
+    pub fn connects_directly(&self, proxy: &ProxyArgs) -> bool {
+        self.unix_socket.is_none() && !proxy.uses_proxy()
+    }
+
+    /// --interface 或 --local-address 指定的源地址
+    /// hyper 只会连接和源地址同一类型的服务器地址，所以源地址需要符合 -4 / -6
+    pub fn source_address(&self, dns: &DnsArgs) -> Result<Option<IpAddr>> {
+        let ip = match (&self.interface, self.local_address) {
+            (Some(name), _) => interface_address(name, |ip| dns.allows(ip))?,
+            (None, Some(ip)) => ip,
+            (None, None) => return Ok(None),
+        };
+        if !dns.allows(&ip) {
+            return Err(anyhow!("--local-address {} does not match -4 / -6", ip));
+        }
+        Ok(Some(ip))
+    }
+
+    /// 是否设置 TCP_NODELAY，和 reqwest 一样默认打开，--tcp-nodelay 和 --no-tcp-nodelay 以后出现的为准
+    fn tcp_nodelay(&self) -> bool {
+        self.tcp_nodelay || !self.no_tcp_nodelay
+    }
+
+    /// 是否通过 --http1.0 等参数指定了 HTTP 版本，这时不使用 --alt-svc 中缓存的服务
+    pub fn forces_version(&self) -> bool {
+        self.http1_0 || self.http1_1 || self.http2 || self.http2_prior_knowledge || self.http3
+    }
+
+    /// 没有 --alpn 时在 TLS 握手中提供的协议，和 reqwest 默认的一样优先使用 HTTP/2
+    fn alpn(&self) -> Vec<Vec<u8>> {
+        if self.http1_0 || self.http1_1 {
+            vec![b"http/1.1".to_vec()]
+        } else if self.http2 || self.http2_prior_knowledge {
+            vec![b"h2".to_vec()]
+        } else {
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        }
+    }
+
+    /// 按照 --http1.0 / --http2 / --http3 设置请求的 HTTP 版本
+    /// reqwest 不支持通过 Upgrade 在明文连接上使用 HTTP/2，所以 --http2 只能用于 https，
+    /// 明文连接需要使用 --http2-prior-knowledge
+    pub fn set_version(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        if !self.http1_0 && !self.http2 && !self.http3 {
+            return Ok(req);
+        }
+        let (client, req) = req.build_split();
+        let mut req = req?;
+        if self.http1_0 {
+            *req.version_mut() = Version::HTTP_10;
+        } else if req.url().scheme() != "https" {
+            if self.http2 {
+                return Err(anyhow!(
+                    "--http2 needs an https URL, use --http2-prior-knowledge for cleartext {}",
+                    req.url()
+                ));
+            }
+            return Err(anyhow!("--http3 needs an https URL, {} is cleartext", req.url()));
+        } else if self.http3 {
+            *req.version_mut() = Version::HTTP_3;
+        }
+        Ok(RequestBuilder::from_parts(client, req))
+    }
+
+    /// 指定了 --http2 时检查服务器是否真的使用了 HTTP/2
+    fn check_version(&self, resp: Response) -> Result<Response> {
+        if self.http2 && resp.version() != Version::HTTP_2 {
+            return Err(anyhow!(
+                "{} did not negotiate HTTP/2 (--http2), it answered with {:?}",
+                resp.url(),
+                resp.version()
+            ));
+        }
+        Ok(resp)
+    }
+
+    /// 说明 reqwest 的超时错误是哪个超时参数触发的，保留原来的错误以便判断是否需要重试
+    pub fn explain_error(&self, err: anyhow::Error) -> anyhow::Error {
+        let (connect, url) = match err.downcast_ref::<reqwest::Error>() {
+            Some(e) if e.is_timeout() => (e.is_connect(), e.url().map(|url| url.to_string())),
+            _ => return err,
+        };
+        let url = url.unwrap_or_default();
+        let msg = match (connect, self.connect_timeout, self.timeout) {
+            (true, Some(t), _) => format!(
+                "Timed out connecting to {} after {} (--connect-timeout)",
+                url,
+                format_duration(t)
+            ),
+            (_, _, Some(t)) => {
+                format!("Request to {} timed out after {} (--timeout)", url, format_duration(t))
+            }
+            _ => return err,
+        };
+        err.context(msg)
+    }
+
+    /// 等待响应 header，超过 --read-timeout 时报错
+    pub async fn wait_response<F>(&self, fut: F) -> Result<Response>
+    where
+        F: std::future::Future<Output = Result<Response>>,
+    {
+        let resp = match self.read_timeout {
+            Some(t) => tokio::time::timeout(t, fut).await.map_err(|_| read_timeout_error(t))?,
+            None => fut.await,
+        };
+        resp.map_err(|e| self.explain_error(e)).and_then(|resp| self.check_version(resp))
+    }
+
+    /// 读取响应的整个 body 并解码 Content-Encoding，每次读取之间超过 --read-timeout 时报错
+    pub async fn read_body(&self, mut resp: Response, progress: &Progress) -> Result<Vec<u8>> {
+        let mut decoder = self.decoder(&resp)?;
+        let mut body = Vec::new();
+        while let Some(chunk) = self.next_decoded(&mut resp, &mut decoder, progress).await? {
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    /// 按照 Content-Encoding 解码 body 的解码器，--no-decompress 或者不支持的编码时为 None
+    /// 206 的 body 只是编码之后的一部分，无法单独解码
+    pub fn decoder(&self, resp: &Response) -> Result<Option<Decoder>> {
+        if self.no_decompress || resp.status() == StatusCode::PARTIAL_CONTENT {
+            return Ok(None);
+        }
+        match resp.headers().get(header::CONTENT_ENCODING).and_then(|v| v.to_str().ok()) {
+            Some(encoding) => Ok(Decoder::new(encoding)?),
+            None => Ok(None),
+        }
+    }
+
+    /// 是否指定了 --trailers
+    pub fn trailers(&self) -> bool {
+        self.trailers
+    }
+
+    /// 通过 trailers::send 发送 --trailers 的请求，HTTP 客户端的默认 header 在这里加上
+    pub async fn send_trailers(&self, mut req: Request, tls: &TlsArgs, dns: &DnsArgs) -> Result<Response> {
+        if self.compressed && !req.headers().contains_key(header::ACCEPT_ENCODING) {
+            req.headers_mut().insert(
+                header::ACCEPT_ENCODING,
+                header::HeaderValue::from_static(compress::ACCEPT_ENCODING),
+            );
+        }
+        trailers::send(req, tls, dns, self.alpn(), self.http2_prior_knowledge).await
+    }
+
+    /// --expect-continue 时发送 body 之前固定等待的时间
+    pub fn expect_continue(&self) -> Option<Duration> {
+        Some(self.expect_delay).filter(|_| self.expect_continue)
+    }
+
+    /// 上传时使用的 --limit-rate 限速，和下载分开计算
+    pub fn upload_limit(&self) -> Option<RateLimit> {
+        self.limit_rate.as_ref().map(RateLimit::fresh)
+    }
+
+    /// 读取 body 的下一块数据，超过 --read-timeout 没有数据时报错，--limit-rate 时按照速度等待
+    pub async fn next_chunk(
+        &self,
+        resp: &mut Response,
+        progress: &Progress,
+    ) -> Result<Option<Bytes>> {
+        let chunk = match self.read_timeout {
+            Some(t) => tokio::time::timeout(t, resp.chunk())
+                .await
+                .map_err(|_| read_timeout_error(t))?,
+            None => resp.chunk().await,
+        };
+        let chunk = chunk.map_err(|e| self.explain_error(e.into()))?;
+        if let Some(ref chunk) = chunk {
+            progress.add(chunk.len());
+            if let Some(ref limit) = self.limit_rate {
+                limit.take(chunk.len()).await;
+            }
+        }
+        Ok(chunk)
+    }
+
+    /// 读取并解码 body 的下一块数据，body 读完之后取出解码器中剩下的数据
+    pub async fn next_decoded(
+        &self,
+        resp: &mut Response,
+        decoder: &mut Option<Decoder>,
+        progress: &Progress,
+    ) -> Result<Option<Bytes>> {
+        match self.next_chunk(resp, progress).await? {
+            Some(chunk) => match decoder {
+                Some(decoder) => Ok(Some(decoder.decode(&chunk)?.into())),
+                None => Ok(Some(chunk)),
+            },
+            None => match decoder.take() {
+                Some(decoder) => Ok(Some(decoder.finish()?.into())),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+/// 网卡上第一个可用的地址，没有 -6 时优先使用 IPv4
+/// 不使用 IPv6 的链路本地地址，绑定时需要 scope id
+#[cfg(unix)]
+fn interface_address(name: &str, allows: impl Fn(&IpAddr) -> bool) -> Result<IpAddr> {
+    let mut addrs: Vec<IpAddr> = nix::ifaddrs::getifaddrs()?
+        .filter(|ifaddr| ifaddr.interface_name == name)
+        .filter_map(|ifaddr| {
+            let addr = ifaddr.address?;
+            if let Some(v4) = addr.as_sockaddr_in() {
+                return Some(IpAddr::from(std::net::Ipv4Addr::from(v4.ip())));
+            }
+            let v6 = addr.as_sockaddr_in6()?.ip();
+            Some(IpAddr::V6(v6)).filter(|_| v6.segments()[0] & 0xffc0 != 0xfe80)
+        })
+        .filter(|ip| allows(ip))
+        .collect();
+    addrs.sort_by_key(|ip| ip.is_ipv6());
+    addrs
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow!("Network interface {} has no usable address (--interface)", name))
+}
+
+#[cfg(not(unix))]
+fn interface_address(_name: &str, _allows: impl Fn(&IpAddr) -> bool) -> Result<IpAddr> {
+    Err(anyhow!("--interface is not supported on this platform, use --local-address"))
+}
+
+/// 响应的 Content-Type 中的 charset
+pub fn charset(resp: &Response) -> Option<String> {
+    let content_type = resp.headers().get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let mime: Mime = content_type.parse().ok()?;
+    Some(mime.get_param("charset")?.as_str().to_string())
+}
+
+/// 记录开始的时间和已经接收的字节数，用于 --max-time 中止时报告进度
+pub struct Progress {
+    start: Instant,
+    received: AtomicU64,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            received: AtomicU64::new(0),
+        }
+    }
+
+    pub fn add(&self, n: usize) {
+        self.received.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// --max-time 到期时的错误信息
+    pub fn deadline_error(&self, max_time: Duration) -> anyhow::Error {
+        anyhow!(
+            "Aborted after {} (--max-time), received {} bytes in {:.2}s",
+            format_duration(max_time),
+            self.received.load(Ordering::Relaxed),
+            self.start.elapsed().as_secs_f64()
+        )
+    }
+}
+
+/// 超过 --read-timeout 没有收到数据
+#[derive(Debug)]
+pub struct ReadTimeout(Duration);
+
+impl std::fmt::Display for ReadTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No data received for {} (--read-timeout)", format_duration(self.0))
+    }
+}
+
+impl std::error::Error for ReadTimeout {}
+
+fn read_timeout_error(t: Duration) -> anyhow::Error {
+    ReadTimeout(t).into()
+}
+
+/// 解析时长，不带单位时为秒，支持 ms、s、m 后缀和小数
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let (num, scale) = if let Some(num) = s.strip_suffix("ms") {
+        (num, 0.001)
+    } else if let Some(num) = s.strip_suffix('s') {
+        (num, 1.0)
+    } else if let Some(num) = s.strip_suffix('m') {
+        (num, 60.0)
+    } else {
+        (s, 1.0)
+    };
+    match num.trim().parse::<f64>() {
+        Ok(n) if n >= 0.0 && n.is_finite() => Ok(Duration::from_secs_f64(n * scale)),
+        _ => Err(anyhow!("Failed to parse duration {}, expect e.g. 30, 1.5s, 500ms or 2m", s)),
+    }
+}
+
+/// 输出时长，例如 1.5s、500ms
+pub fn format_duration(d: Duration) -> String {
+    if d < Duration::from_secs(1) {
+        format!("{}ms", d.as_millis())
+    } else {
+        format!("{}s", d.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_works() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert!(parse_duration("-1").is_err());
+        assert!(parse_duration("abc").is_err());
+    }
+
+    #[test]
+    fn alpn_works() {
+        let alpn = |args: &[&str]| ClientArgs::parse_from(args).alpn();
+        assert_eq!(alpn(&["http"]), vec![b"h2".to_vec(), b"http/1.1".to_vec()]);
+        assert_eq!(alpn(&["http", "--http1.1"]), vec![b"http/1.1".to_vec()]);
+        assert_eq!(alpn(&["http", "--http1.0"]), vec![b"http/1.1".to_vec()]);
+        assert_eq!(alpn(&["http", "--http2"]), vec![b"h2".to_vec()]);
+        assert_eq!(alpn(&["http", "--http2-prior-knowledge"]), vec![b"h2".to_vec()]);
+        assert!(ClientArgs::try_parse_from(["http", "--http1.1", "--http2"]).is_err());
+    }
+
+    #[test]
+    fn source_address_works() {
+        let args = ClientArgs::parse_from(["http", "--local-address", "192.168.1.5"]);
+        let dns = DnsArgs::parse_from(["http"]);
+        assert_eq!(args.source_address(&dns).unwrap(), Some("192.168.1.5".parse().unwrap()));
+        assert!(args.source_address(&DnsArgs::parse_from(["http", "-6"])).is_err());
+        assert_eq!(ClientArgs::parse_from(["http"]).source_address(&dns).unwrap(), None);
+        let conflict = ["http", "--interface", "eth1", "--local-address", "::1"];
+        assert!(ClientArgs::try_parse_from(conflict).is_err());
+    }
+
+    #[test]
+    fn tcp_nodelay_works() {
+        assert!(ClientArgs::parse_from(["http"]).tcp_nodelay());
+        assert!(!ClientArgs::parse_from(["http", "--no-tcp-nodelay"]).tcp_nodelay());
+        assert!(ClientArgs::parse_from(["http", "--tcp-nodelay"]).tcp_nodelay());
+        assert!(ClientArgs::parse_from(["http", "--no-tcp-nodelay", "--tcp-nodelay"]).tcp_nodelay());
+        assert!(!ClientArgs::parse_from(["http", "--tcp-nodelay", "--no-tcp-nodelay"]).tcp_nodelay());
+        let proxy = ProxyArgs::parse_from(["http"]);
+        let (tls, dns) = (TlsArgs::parse_from(["http"]), DnsArgs::parse_from(["http"]));
+        for flag in ["--tcp-nodelay", "--no-tcp-nodelay"] {
+            assert!(ClientArgs::parse_from(["http", flag]).build(&proxy, &tls, &dns).is_ok());
+        }
+    }
+
+    #[test]
+    fn trailers_works() {
+        assert!(!ClientArgs::parse_from(["http"]).trailers());
+        assert!(ClientArgs::parse_from(["http", "--trailers", "--http2-prior-knowledge"]).trailers());
+        // 这些参数需要 reqwest 的连接或者上传方式
+        for flag in ["--http3", "--unix-socket=/tmp/a.sock", "--limit-rate=1k", "--expect-continue"] {
+            assert!(ClientArgs::try_parse_from(["http", "--trailers", flag]).is_err());
+        }
+    }
+
+    #[test]
+    fn format_duration_works() {
+        assert_eq!(format_duration(Duration::from_millis(500)), "500ms");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_duration(Duration::from_secs(30)), "30s");
+    }
+}
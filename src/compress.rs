@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+use colored::*;
+use flate2::write::{GzEncoder, MultiGzDecoder, ZlibDecoder};
+use flate2::Compression;
+use futures::{Stream, StreamExt};
+use hyper::body::Bytes;
+use indicatif::HumanBytes;
+
+use crate::quiet;
+
+/// 用 gzip 压缩内存中的 body
+pub fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    let compressed = encoder.finish()?;
+    report(data.len() as u64, compressed.len() as u64);
+    Ok(compressed)
+}
+
+/// 边读取边用 gzip 压缩流式的 body，读完之后报告压缩前后的大小
+pub fn gzip_stream<S>(stream: S) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+{
+    let encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let state = Some((Box::pin(stream), encoder, 0u64, 0u64));
+    futures::stream::unfold(state, |state| async move {
+        let (mut stream, mut encoder, mut read, written) = state?;
+        loop {
+            let chunk = match stream.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => break,
+            };
+            read += chunk.len() as u64;
+            if let Err(e) = encoder.write_all(&chunk) {
+                return Some((Err(e), None));
+            }
+            // 压缩器攒够数据之前不一定有输出
+            if !encoder.get_ref().is_empty() {
+                let out = std::mem::take(encoder.get_mut());
+                let written = written + out.len() as u64;
+                return Some((Ok(Bytes::from(out)), Some((stream, encoder, read, written))));
+            }
+        }
+        let out = match encoder.finish() {
+            Ok(out) => out,
+            Err(e) => return Some((Err(e), None)),
+        };
+        report(read, written + out.len() as u64);
+        Some((Ok(Bytes::from(out)), None))
+    })
+}
+
+/// 可以解码的 Content-Encoding，--compressed 时在 Accept-Encoding 中请求
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+
+/// 边接收边解码响应的 body，接收完之后报告解码前后的大小
+pub struct Decoder {
+    name: &'static str,
+    inner: Inner,
+    received: u64,
+    decoded: u64,
+}
+
+// 解码器都写入一个 Vec，每次写入之后取出解码的数据
+enum Inner {
+    Gzip(MultiGzDecoder<Vec<u8>>),
+    Deflate(ZlibDecoder<Vec<u8>>),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+}
+
+impl Decoder {
+    /// Content-Encoding 对应的解码器，不支持的编码返回 None，这时 body 保持原样
+    pub fn new(encoding: &str) -> io::Result<Option<Self>> {
+        let (name, inner) = match encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => ("gzip", Inner::Gzip(MultiGzDecoder::new(Vec::new()))),
+            "deflate" => ("deflate", Inner::Deflate(ZlibDecoder::new(Vec::new()))),
+            "br" => (
+                "br",
+                Inner::Brotli(Box::new(brotli::DecompressorWriter::new(Vec::new(), 4096))),
+            ),
+            "zstd" => ("zstd", Inner::Zstd(zstd::stream::write::Decoder::new(Vec::new())?)),
+            _ => return Ok(None),
+        };
+        Ok(Some(Self {
+            name,
+            inner,
+            received: 0,
+            decoded: 0,
+        }))
+    }
+
+    /// 解码收到的一块数据，返回已经解码出来的部分
+    pub fn decode(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        self.received += chunk.len() as u64;
+        match self.inner {
+            Inner::Gzip(ref mut d) => d.write_all(chunk)?,
+            Inner::Deflate(ref mut d) => d.write_all(chunk)?,
+            Inner::Brotli(ref mut d) => d.write_all(chunk)?,
+            Inner::Zstd(ref mut d) => d.write_all(chunk)?,
+        }
+        Ok(self.take())
+    }
+
+    /// body 接收完之后取出剩下的数据
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        match self.inner {
+            Inner::Gzip(ref mut d) => d.try_finish()?,
+            Inner::Deflate(ref mut d) => d.try_finish()?,
+            Inner::Brotli(ref mut d) => d.close()?,
+            Inner::Zstd(ref mut d) => d.flush()?,
+        }
+        let rest = self.take();
+        let msg = format!(
+            "Decoded the {} response body from {} to {}",
+            self.name,
+            HumanBytes(self.received),
+            HumanBytes(self.decoded)
+        );
+        quiet::info(msg.cyan());
+        Ok(rest)
+    }
+
+    fn take(&mut self) -> Vec<u8> {
+        let out = match self.inner {
+            Inner::Gzip(ref mut d) => d.get_mut(),
+            Inner::Deflate(ref mut d) => d.get_mut(),
+            Inner::Brotli(ref mut d) => d.get_mut(),
+            Inner::Zstd(ref mut d) => d.get_mut(),
+        };
+        self.decoded += out.len() as u64;
+        std::mem::take(out)
+    }
+}
+
+fn report(before: u64, after: u64) {
+    let msg = format!(
+        "Compressed the request body with gzip from {} to {}",
+        HumanBytes(before),
+        HumanBytes(after)
+    );
+    quiet::info(msg.cyan());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn gunzip(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn gzip_stream_works() {
+        let data = b"hello gzip ".repeat(10_000);
+        let chunks: Vec<io::Result<Bytes>> =
+            data.chunks(4096).map(|c| Ok(Bytes::copy_from_slice(c))).collect();
+        let out: Vec<Bytes> = gzip_stream(futures::stream::iter(chunks))
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(gunzip(&out.concat()), data);
+        assert_eq!(gunzip(&gzip(&data).unwrap()), data);
+    }
+
+    #[test]
+    fn decoder_works() {
+        let data = b"hello decoder ".repeat(1000);
+        let zstd = zstd::encode_all(&data[..], 3).unwrap();
+        let mut brotli = Vec::new();
+        brotli::BrotliCompress(&mut &data[..], &mut brotli, &Default::default()).unwrap();
+        for (encoding, encoded) in [("gzip", gzip(&data).unwrap()), ("zstd", zstd), ("br", brotli)] {
+            let mut decoder = Decoder::new(encoding).unwrap().unwrap();
+            let mut out = Vec::new();
+            for chunk in encoded.chunks(100) {
+                out.extend(decoder.decode(chunk).unwrap());
+            }
+            out.extend(decoder.finish().unwrap());
+            assert_eq!(out, data, "{}", encoding);
+        }
+        assert!(Decoder::new("identity").unwrap().is_none());
+    }
+}
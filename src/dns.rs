@@ -0,0 +1,664 @@
+mod doh;
+mod happy_eyeballs;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use clap::Clap;
+use colored::*;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::{header, ClientBuilder, Request, RequestBuilder, Url};
+use tokio::sync::OnceCell;
+
+use crate::client::parse_duration;
+use crate::quiet;
+use crate::tls::TlsArgs;
+use doh::Doh;
+
+/// 域名解析和连接目标相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct DnsArgs {
+    /// Connect to this address instead of resolving the host, like an /etc/hosts entry,
+    /// e.g. --resolve api.example.com:443:10.0.0.5 (several addresses separated by commas,
+    /// IPv6 in brackets). The URL, Host header and certificate check stay the same.
+    /// The override applies to the host on every port
+    #[clap(long, global = true, multiple_occurrences = true, number_of_values = 1)]
+    resolve: Vec<ResolveSpec>,
+    /// Connect to DST_HOST:DST_PORT for requests to SRC_HOST:SRC_PORT, keeping the URL,
+    /// SNI and Host header, e.g. --connect-to api.example.com:443:staging-1.internal:8443.
+    /// An empty SRC_HOST or SRC_PORT matches any, an empty DST_HOST or DST_PORT keeps the
+    /// original. Like --resolve, the host mapping applies to every port of SRC_HOST
+    #[clap(
+        long,
+        global = true,
+        multiple_occurrences = true,
+        number_of_values = 1,
+        value_name = "SRC_HOST:SRC_PORT:DST_HOST:DST_PORT"
+    )]
+    connect_to: Vec<ConnectTo>,
+    /// Only connect to IPv4 addresses of the host
+    #[clap(short = '4', long, global = true, conflicts_with = "ipv6")]
+    ipv4: bool,
+    /// Only connect to IPv6 addresses of the host
+    #[clap(short = '6', long, global = true)]
+    ipv6: bool,
+    /// Resolve host names with this DNS-over-HTTPS server (RFC 8484) instead of the system
+    /// resolver, e.g. https://1.1.1.1/dns-query. -v prints the resolved addresses
+    #[clap(long, global = true)]
+    doh_url: Option<Url>,
+    /// Send this server name in the TLS handshake (SNI) and check the certificate against
+    /// it, while still connecting to the host of the URL, e.g. to probe a CDN origin by IP
+    #[clap(long, global = true)]
+    sni: Option<String>,
+    /// Send this Host header instead of the host of the URL, e.g. to reach one virtual
+    /// host of a server. A Host:value item takes precedence
+    #[clap(long, global = true)]
+    host_header: Option<String>,
+    /// Wait this long for the preferred address family (normally IPv6) before also trying
+    /// the other one, e.g. 2s (Happy Eyeballs, 300ms by default). The race is run once per
+    /// host before its first request and the winning address is used for the whole run.
+    /// Not used when connecting through a proxy or --unix-socket
+    #[clap(
+        long,
+        global = true,
+        parse(try_from_str = parse_duration),
+        conflicts_with = "no-happy-eyeballs"
+    )]
+    happy_eyeballs_timeout: Option<Duration>,
+    /// Never fall back to the other address family: only connect to addresses of the
+    /// family listed first (normally IPv6), so that a blackholed IPv6 network shows up as a
+    /// connect error instead of a silent fallback to IPv4
+    #[clap(long, global = true)]
+    no_happy_eyeballs: bool,
+    // TCP 和 QUIC 的客户端共用的 Resolver
+    #[clap(skip)]
+    resolver: OnceLock<Arc<Resolver>>,
+}
+
+impl DnsArgs {
+    /// 使用自己的 Resolver 解析所有的主机，--resolve 也由它处理，这样 -4 / -6 等参数对
+    /// --resolve 指定的地址同样有效
+    pub fn apply(&self, builder: ClientBuilder, tls: &TlsArgs) -> Result<ClientBuilder> {
+        let family = self.family();
+        if let Some(family) = family {
+            for spec in self.resolve.iter() {
+                let mut addrs = self.resolve.iter().filter(|s| s.host == spec.host);
+                if !addrs.any(|s| s.addrs.iter().any(|ip| family.allows(ip))) {
+                    return Err(anyhow!("--resolve gives no {} address for {}", family, spec.host));
+                }
+            }
+        }
+        let resolver = match self.resolver.get() {
+            Some(resolver) => resolver.clone(),
+            None => {
+                let doh = match &self.doh_url {
+                    Some(url) => Some(Arc::new(Doh::new(url.clone(), tls.plain_client()?))),
+                    None => None,
+                };
+                let resolver = Arc::new(Resolver {
+                    resolve: self.resolve.clone(),
+                    family,
+                    doh,
+                    sni: self.sni.as_ref().map(|sni| sni.to_ascii_lowercase()),
+                    single_family: self.no_happy_eyeballs,
+                    ..Default::default()
+                });
+                self.resolver.get_or_init(|| resolver).clone()
+            }
+        };
+        Ok(builder.dns_resolver(resolver))
+    }
+
+    /// 同一个主机解析了不止一次时打印 DNS 缓存的统计
+    pub fn print_stats(&self) {
+        let resolver = match self.resolver.get() {
+            Some(resolver) => resolver,
+            None => return,
+        };
+        let hits = resolver.hits.load(Ordering::Relaxed);
+        if hits == 0 {
+            return;
+        }
+        let msg = format!(
+            "DNS cache: {} lookups of {} hosts, {} served from the cache",
+            resolver.lookups.load(Ordering::Relaxed),
+            resolver.cache.lock().unwrap().len(),
+            hits
+        );
+        quiet::info(msg.cyan());
+    }
+
+    /// 地址是否符合 -4 / -6
+    pub fn allows(&self, ip: &IpAddr) -> bool {
+        self.family().is_none_or(|f| f.allows(ip))
+    }
+
+    /// -4 / -6 限制的地址类型
+    fn family(&self) -> Option<Family> {
+        match (self.ipv4, self.ipv6) {
+            (true, _) => Some(Family::V4),
+            (_, true) => Some(Family::V6),
+            _ => None,
+        }
+    }
+
+    /// 发送前检查和改写请求
+    /// URL 中的 IP 地址不经过 Resolver，在这里检查是否符合 -4 / -6
+    pub fn route(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        let family = self.family();
+        if self.connect_to.is_empty()
+            && family.is_none()
+            && self.sni.is_none()
+            && self.host_header.is_none()
+        {
+            return Ok(req);
+        }
+        let (client, req) = req.build_split();
+        let mut req = req?;
+        let url = req.url().clone();
+        let ip = url.host_str().and_then(|h| h.trim_matches(['[', ']']).parse::<IpAddr>().ok());
+        if let (Some(ip), Some(family)) = (ip, family) {
+            if !family.allows(&ip) {
+                return Err(anyhow!("{} is not an {} address ({})", ip, family, family.flag()));
+            }
+        }
+        if let Some(value) = &self.host_header {
+            if !req.headers().contains_key(header::HOST) {
+                let value = value.parse().map_err(|_| anyhow!("Invalid --host-header {}", value))?;
+                req.headers_mut().insert(header::HOST, value);
+            }
+        }
+        self.connect_to(&mut req, &url)?;
+        self.set_sni(&mut req, &url)?;
+        Ok(RequestBuilder::from_parts(client, req))
+    }
+
+    /// 指定了 --happy-eyeballs-timeout 时，第一次连接一个主机前自己进行连接竞争，这次运行中
+    /// 这个主机只使用胜出的地址。hyper 总是在 300ms 后开始连接另一种地址，reqwest 不能修改
+    /// 探测连接绑定 --interface / --local-address 的源地址 source，经过代理时不应该调用
+    pub async fn happy_eyeballs(&self, req: RequestBuilder, source: Option<IpAddr>) -> Result<RequestBuilder> {
+        let (delay, resolver) = match (self.happy_eyeballs_timeout, self.resolver.get()) {
+            (Some(delay), Some(resolver)) => (delay, resolver),
+            _ => return Ok(req),
+        };
+        let (client, req) = req.build_split();
+        let req = req?;
+        let url = req.url();
+        let (host, port) = match (url.domain(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => (host.to_ascii_lowercase(), port),
+            _ => return Ok(RequestBuilder::from_parts(client, req)),
+        };
+        // 胜出的地址属于实际连接的主机，--connect-to 可能让同一个主机的不同端口连接不同的主机
+        let target = resolver.target(&host);
+        if resolver.winners.lock().unwrap().contains_key(&target) {
+            return Ok(RequestBuilder::from_parts(client, req));
+        }
+        let addrs = self.lookup(&host, port).await?;
+        let winner = happy_eyeballs::race(&addrs, delay, source)
+            .await
+            .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+        let msg = format!("Happy Eyeballs: {} connected first for {}", winner.ip(), host);
+        quiet::info(msg.cyan());
+        resolver.winners.lock().unwrap().insert(target, winner.ip());
+        Ok(RequestBuilder::from_parts(client, req))
+    }
+
+    /// 自己建立连接（连接竞争和 ws 子命令）时解析 URL 中的主机，和 HTTP 客户端一样经过
+    /// Resolver，所以 --resolve、--connect-to、--sni、-4 / -6 和 --doh-url 都有效
+    pub async fn lookup(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+        if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+        let resolver = self
+            .resolver
+            .get()
+            .ok_or_else(|| anyhow!("Failed to resolve {}: the resolver is not set up", host))?;
+        let name: Name = host.parse()?;
+        Ok(resolver
+            .resolve(name)
+            .await
+            .map_err(|e| anyhow!("Failed to resolve {}: {}", host, e))?
+            .map(|addr| SocketAddr::new(addr.ip(), port))
+            .collect())
+    }
+
+    /// 按照请求的主机和端口选择第一个匹配的 --connect-to 规则，和 curl 一样 SRC_HOST 和
+    /// SRC_PORT 都要匹配。DST_HOST 交给 Resolver 在解析这个主机时使用，需要连接另一个端口时
+    /// 改掉 URL 的端口，Host header 保持原来的值，URL 中的主机和 SNI 都不变
+    fn connect_to(&self, req: &mut Request, url: &Url) -> Result<()> {
+        let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => (host, port),
+            _ => return Ok(()),
+        };
+        let rule = self.connect_to.iter().find(|rule| rule.matches(host, port));
+        let dst_host = rule.and_then(|rule| rule.dst_host.clone());
+        // IP 地址不经过域名解析，没有办法换成别的主机
+        if dst_host.is_some() && url.domain().is_none() {
+            return Err(anyhow!(
+                "--connect-to cannot change the host of {}, it is an IP address",
+                url
+            ));
+        }
+        if let (Some(resolver), Some(domain)) = (self.resolver.get(), url.domain()) {
+            resolver.set_target(domain, dst_host);
+        }
+        let rule = match rule {
+            Some(rule) => rule,
+            None => return Ok(()),
+        };
+        if let Some(dst_port) = rule.dst_port.filter(|p| *p != port) {
+            keep_host_header(req, url)?;
+            req.url_mut()
+                .set_port(Some(dst_port))
+                .map_err(|_| anyhow!("Failed to change the port of {}", url))?;
+        }
+        Ok(())
+    }
+
+    /// reqwest 总是用 URL 中的主机作为 SNI，所以 --sni 把 URL 的主机换成 SNI 的名字，
+    /// 再由 Resolver 把这个名字解析为原来的主机，Host header 保持原来的值
+    /// 只改写第一个 https 请求的主机的请求，重定向到其他主机时使用正常的 SNI
+    fn set_sni(&self, req: &mut Request, url: &Url) -> Result<()> {
+        let (sni, resolver) = match (&self.sni, self.resolver.get()) {
+            (Some(sni), Some(resolver)) if url.scheme() == "https" => (sni, resolver),
+            _ => return Ok(()),
+        };
+        let host = match url.host_str() {
+            Some(host) => host.trim_matches(['[', ']']).to_ascii_lowercase(),
+            None => return Ok(()),
+        };
+        if *resolver.sni_target.get_or_init(|| host.clone()) != host {
+            return Ok(());
+        }
+        keep_host_header(req, url)?;
+        req.url_mut()
+            .set_host(Some(sni))
+            .map_err(|e| anyhow!("Invalid --sni {}: {}", sni, e))?;
+        Ok(())
+    }
+}
+
+/// 改写 URL 之前把原来的主机设置为 Host header，已经有 Host header 时不变
+fn keep_host_header(req: &mut Request, url: &Url) -> Result<()> {
+    if !req.headers().contains_key(header::HOST) {
+        let value = authority(url).parse()?;
+        req.headers_mut().insert(header::HOST, value);
+    }
+    Ok(())
+}
+
+/// -4 / -6
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn allows(self, ip: &IpAddr) -> bool {
+        match self {
+            Self::V4 => ip.is_ipv4(),
+            Self::V6 => ip.is_ipv6(),
+        }
+    }
+
+    fn flag(self) -> &'static str {
+        match self {
+            Self::V4 => "-4",
+            Self::V6 => "-6",
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::V4 => write!(f, "IPv4"),
+            Self::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// 按照 --connect-to 把主机换成 DST_HOST 再解析，--resolve 指定了地址的主机不用解析
+/// 按照 -4 / -6 只返回一种地址，指定了 --doh-url 时通过 DoH 查询
+/// 解析的结果在这次运行中一直缓存，发送很多请求时每个主机只解析一次
+#[derive(Debug, Default)]
+struct Resolver {
+    // DnsArgs::connect_to 按照主机和端口为每个请求选择的 DST_HOST，键是请求的主机
+    // 解析时只知道主机名，所以在发送请求之前写入
+    targets: Mutex<HashMap<String, String>>,
+    resolve: Vec<ResolveSpec>,
+    family: Option<Family>,
+    doh: Option<Arc<Doh>>,
+    // --sni 的名字和它代替的主机
+    sni: Option<String>,
+    sni_target: OnceLock<String>,
+    // --no-happy-eyeballs，只返回第一个地址的类型的地址
+    single_family: bool,
+    // --happy-eyeballs-timeout 时每个主机连接竞争胜出的地址
+    winners: Mutex<HashMap<String, IpAddr>>,
+    cache: Mutex<HashMap<String, Arc<OnceCell<Vec<IpAddr>>>>>,
+    // 解析的次数和其中使用缓存的次数
+    lookups: AtomicU64,
+    hits: AtomicU64,
+}
+
+impl Resolver {
+    /// 实际需要解析的主机：--sni 的名字换回它代替的主机，再换成 --connect-to 选择的 DST_HOST
+    fn target(&self, name: &str) -> String {
+        let name = match (&self.sni, self.sni_target.get()) {
+            (Some(sni), Some(target)) if sni.eq_ignore_ascii_case(name) => target,
+            _ => name,
+        };
+        let name = name.to_ascii_lowercase();
+        self.targets.lock().unwrap().get(&name).cloned().unwrap_or(name)
+    }
+
+    /// 记录 --connect-to 为 host 的下一个请求选择的 DST_HOST，None 表示连接 host 本身
+    fn set_target(&self, host: &str, dst_host: Option<String>) {
+        let mut targets = self.targets.lock().unwrap();
+        match dst_host {
+            Some(dst_host) => targets.insert(host.to_ascii_lowercase(), dst_host),
+            None => targets.remove(&host.to_ascii_lowercase()),
+        };
+    }
+
+    /// host 在缓存中的位置，第一次解析时创建，同时进行的解析共用一个查询
+    fn cached(&self, host: &str) -> Arc<OnceCell<Vec<IpAddr>>> {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(cell) = cache.get(host) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return cell.clone();
+        }
+        cache.entry(host.to_string()).or_default().clone()
+    }
+}
+
+impl Resolve for Resolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = self.target(name.as_str());
+        if let Some(ip) = self.winners.lock().unwrap().get(&host) {
+            let addrs: Addrs = Box::new(std::iter::once(SocketAddr::new(*ip, 0)));
+            return Box::pin(std::future::ready(Ok(addrs)));
+        }
+        let pinned: Vec<_> = self
+            .resolve
+            .iter()
+            .filter(|spec| spec.host.eq_ignore_ascii_case(&host))
+            .flat_map(|spec| spec.addrs.iter().copied())
+            .collect();
+        // --resolve 指定的地址不经过缓存
+        let cell = if pinned.is_empty() {
+            Some(self.cached(&host))
+        } else {
+            None
+        };
+        let (family, doh, single_family) = (self.family, self.doh.clone(), self.single_family);
+        Box::pin(async move {
+            let mut ips = match cell {
+                Some(cell) => {
+                    let lookup = lookup(&host, family, doh.as_deref());
+                    let ips = cell.get_or_try_init(|| lookup).await;
+                    ips.map_err(|e| format!("{:#}", e))?.clone()
+                }
+                None => pinned,
+            };
+            if let Some(family) = family {
+                ips.retain(|ip| family.allows(ip));
+                if ips.is_empty() {
+                    let msg = format!("{} has no {} address ({})", host, family, family.flag());
+                    return Err(msg.into());
+                }
+            }
+            // 只有一种地址时 hyper 不会在 300ms 后连接另一种地址
+            if let (true, Some(first)) = (single_family, ips.first()) {
+                let v6 = first.is_ipv6();
+                ips.retain(|ip| ip.is_ipv6() == v6);
+            }
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// 通过 DoH 或者系统的解析器查询 host 的地址
+async fn lookup(host: &str, family: Option<Family>, doh: Option<&Doh>) -> Result<Vec<IpAddr>> {
+    let doh = match doh {
+        Some(doh) => doh,
+        None => {
+            let addrs = tokio::net::lookup_host((host, 0)).await?;
+            return Ok(addrs.map(|addr| addr.ip()).collect());
+        }
+    };
+    let (v4, v6) = (family != Some(Family::V6), family != Some(Family::V4));
+    let ips = doh.lookup(host, v4, v6).await?;
+    let list: Vec<_> = ips.iter().map(|ip| ip.to_string()).collect();
+    let msg = format!("Resolved {} to {} via {}", host, list.join(", "), doh.url);
+    quiet::verbose(msg.cyan());
+    Ok(ips)
+}
+
+/// URL 原来的 host[:port]，默认端口省略
+fn authority(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+/// --resolve 的值：host:port:address[,address...]
+/// reqwest 连接时使用 URL 中的端口，所以 port 只是为了和 curl 的格式兼容
+#[derive(Debug, Clone, PartialEq)]
+struct ResolveSpec {
+    host: String,
+    port: u16,
+    addrs: Vec<IpAddr>,
+}
+
+impl FromStr for ResolveSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!("Invalid --resolve {}, expect host:port:address", s);
+        let mut parts = s.splitn(3, ':');
+        let (host, port, addrs) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(host), Some(port), Some(addrs)) if !host.is_empty() => (host, port, addrs),
+            _ => return Err(err()),
+        };
+        let port = port.parse().map_err(|_| err())?;
+        let addrs = addrs
+            .split(',')
+            .map(|addr| {
+                let addr = addr.trim().trim_start_matches('[').trim_end_matches(']');
+                addr.parse().map_err(|_| anyhow!("Invalid address {} in --resolve {}", addr, s))
+            })
+            .collect::<Result<Vec<IpAddr>>>()?;
+        Ok(Self {
+            host: host.to_ascii_lowercase(),
+            port,
+            addrs,
+        })
+    }
+}
+
+/// --connect-to 的值：SRC_HOST:SRC_PORT:DST_HOST:DST_PORT，和 curl 的格式相同
+/// 为空的部分用 None 表示，IPv6 地址放在方括号中
+#[derive(Debug, Clone, PartialEq)]
+struct ConnectTo {
+    src_host: Option<String>,
+    src_port: Option<u16>,
+    dst_host: Option<String>,
+    dst_port: Option<u16>,
+}
+
+impl ConnectTo {
+    fn matches(&self, host: &str, port: u16) -> bool {
+        self.src_host.as_deref().is_none_or(|h| h.eq_ignore_ascii_case(host))
+            && self.src_port.is_none_or(|p| p == port)
+    }
+}
+
+impl FromStr for ConnectTo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || {
+            anyhow!("Invalid --connect-to {}, expect SRC_HOST:SRC_PORT:DST_HOST:DST_PORT", s)
+        };
+        let fields = split_host_fields(s);
+        if fields.len() != 4 {
+            return Err(err());
+        }
+        let host = |f: &str| Some(f.to_ascii_lowercase()).filter(|h| !h.is_empty());
+        let port = |f: &str| match f {
+            "" => Ok(None),
+            _ => f.parse().map(Some).map_err(|_| err()),
+        };
+        Ok(Self {
+            src_host: host(fields[0]),
+            src_port: port(fields[1])?,
+            dst_host: host(fields[2])
+                .map(|h| h.trim_start_matches('[').trim_end_matches(']').to_string()),
+            dst_port: port(fields[3])?,
+        })
+    }
+}
+
+/// 按冒号分开，方括号中的 IPv6 地址不分开
+fn split_host_fields(s: &str) -> Vec<&str> {
+    let (mut fields, mut start, mut bracket) = (Vec::new(), 0, false);
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => bracket = true,
+            ']' => bracket = false,
+            ':' if !bracket => {
+                fields.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&s[start..]);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_resolve_works() {
+        let spec: ResolveSpec = "API.example.com:443:10.0.0.5".parse().unwrap();
+        assert_eq!(spec.host, "api.example.com");
+        assert_eq!(spec.port, 443);
+        assert_eq!(spec.addrs, vec!["10.0.0.5".parse::<IpAddr>().unwrap()]);
+        let spec: ResolveSpec = "example.com:80:[::1],127.0.0.1".parse().unwrap();
+        assert_eq!(
+            spec.addrs,
+            vec!["::1".parse::<IpAddr>().unwrap(), "127.0.0.1".parse().unwrap()]
+        );
+        assert!("example.com:443".parse::<ResolveSpec>().is_err());
+        assert!("example.com:https:10.0.0.5".parse::<ResolveSpec>().is_err());
+        assert!("example.com:443:backend".parse::<ResolveSpec>().is_err());
+        assert!(":443:10.0.0.5".parse::<ResolveSpec>().is_err());
+    }
+
+    #[test]
+    fn parse_connect_to_works() {
+        let rule: ConnectTo = "API.example.com:443:staging-1.internal:8443".parse().unwrap();
+        assert_eq!(rule.src_host.as_deref(), Some("api.example.com"));
+        assert_eq!(rule.src_port, Some(443));
+        assert_eq!(rule.dst_host.as_deref(), Some("staging-1.internal"));
+        assert_eq!(rule.dst_port, Some(8443));
+        assert!(rule.matches("api.example.com", 443));
+        assert!(!rule.matches("api.example.com", 80));
+        let rule: ConnectTo = "::[::1]:".parse().unwrap();
+        assert_eq!((rule.src_host, rule.src_port), (None, None));
+        assert_eq!((rule.dst_host.as_deref(), rule.dst_port), (Some("::1"), None));
+        let rule: ConnectTo = "[fe80::1]:80::8080".parse().unwrap();
+        assert_eq!(rule.src_host.as_deref(), Some("[fe80::1]"));
+        assert!(rule.matches("[fe80::1]", 80));
+        assert!("example.com:443:backend".parse::<ConnectTo>().is_err());
+        assert!("example.com:https:backend:443".parse::<ConnectTo>().is_err());
+    }
+
+    #[test]
+    fn connect_to_keeps_host_header() {
+        let args = DnsArgs::parse_from(["http", "--connect-to", "example.com:443::8443"]);
+        let client = reqwest::Client::new();
+        let req = args.route(client.get("https://example.com/a")).unwrap().build().unwrap();
+        assert_eq!(req.url().as_str(), "https://example.com:8443/a");
+        assert_eq!(req.headers()[header::HOST], "example.com");
+        let req = args.route(client.get("https://example.com:444/")).unwrap().build().unwrap();
+        assert_eq!(req.url().as_str(), "https://example.com:444/");
+        assert!(req.headers().get(header::HOST).is_none());
+    }
+
+    #[test]
+    fn connect_to_matches_port_works() {
+        let rules = ["http", "--connect-to", "api:443:a:", "--connect-to", "api:80:b:"];
+        let args = DnsArgs::parse_from(rules);
+        let client = args.apply(reqwest::Client::builder(), &TlsArgs::parse_from(["http"]));
+        let client = client.unwrap().build().unwrap();
+        let resolver = args.resolver.get().unwrap();
+        // URL 不变，Resolver 按照请求的端口选择主机
+        let route = |url: &str| args.route(client.get(url)).unwrap().build().unwrap().url().clone();
+        assert_eq!(route("https://api/").as_str(), "https://api/");
+        assert_eq!(resolver.target("api"), "a");
+        assert_eq!(route("http://api/").as_str(), "http://api/");
+        assert_eq!(resolver.target("api"), "b");
+        // 没有匹配的规则时连接原来的主机
+        route("http://api:8080/");
+        assert_eq!(resolver.target("api"), "api");
+    }
+
+    #[test]
+    fn sni_works() {
+        let args = DnsArgs::parse_from(["http", "--sni", "origin.example.com"]);
+        let client = args.apply(reqwest::Client::builder(), &TlsArgs::parse_from(["http"]));
+        let client = client.unwrap().build().unwrap();
+        let req = args.route(client.get("https://10.0.0.5:8443/")).unwrap().build().unwrap();
+        assert_eq!(req.url().as_str(), "https://origin.example.com:8443/");
+        assert_eq!(req.headers()[header::HOST], "10.0.0.5:8443");
+        assert_eq!(args.resolver.get().unwrap().sni_target.get().unwrap(), "10.0.0.5");
+        // 其他主机和 http 请求不变
+        let req = args.route(client.get("https://10.0.0.6/")).unwrap().build().unwrap();
+        assert_eq!(req.url().as_str(), "https://10.0.0.6/");
+        let req = args.route(client.get("http://10.0.0.5/")).unwrap().build().unwrap();
+        assert_eq!(req.url().as_str(), "http://10.0.0.5/");
+        let args = DnsArgs::parse_from(["http", "--host-header", "vhost.example.com"]);
+        let req = args.route(client.get("http://10.0.0.5/")).unwrap().build().unwrap();
+        assert_eq!(req.headers()[header::HOST], "vhost.example.com");
+    }
+
+    #[test]
+    fn cache_works() {
+        let resolver = Resolver::default();
+        let cell = resolver.cached("example.com");
+        cell.set(vec!["10.0.0.5".parse().unwrap()]).unwrap();
+        assert_eq!(resolver.cached("example.com").get(), cell.get());
+        assert!(resolver.cached("example.org").get().is_none());
+        assert_eq!(resolver.lookups.load(Ordering::Relaxed), 3);
+        assert_eq!(resolver.hits.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn family_works() {
+        let args = DnsArgs::parse_from(["http", "-4"]);
+        let client = reqwest::Client::new();
+        assert!(args.route(client.get("http://127.0.0.1/")).is_ok());
+        assert!(args.route(client.get("http://[::1]/")).is_err());
+        let args = DnsArgs::parse_from(["http", "-6", "--resolve", "example.com:80:10.0.0.5"]);
+        let tls = TlsArgs::parse_from(["http"]);
+        assert!(args.apply(reqwest::Client::builder(), &tls).is_err());
+        assert!(DnsArgs::try_parse_from(["http", "-4", "-6"]).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use anyhow::{anyhow, Context, Result};
+use reqwest::{header, Client, Url};
+
+// DNS 记录的类型和类别
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+// RFC 8484 的 DNS 报文格式
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// --doh-url 指定的 DNS over HTTPS 服务，通过 RFC 8484 的 POST 请求查询地址
+#[derive(Debug)]
+pub struct Doh {
+    pub url: Url,
+    client: Client,
+}
+
+impl Doh {
+    pub fn new(url: Url, client: Client) -> Self {
+        Self { url, client }
+    }
+
+    /// 同时查询 A 和 AAAA 记录，只需要一种地址时只查询一种
+    /// 一种查询失败时使用另一种查询到的地址
+    pub async fn lookup(&self, host: &str, v4: bool, v6: bool) -> Result<Vec<IpAddr>> {
+        let (a, aaaa) = tokio::join!(
+            self.query_if(v4, host, TYPE_A),
+            self.query_if(v6, host, TYPE_AAAA)
+        );
+        let addrs: Vec<_> = match (a, aaaa) {
+            (Ok(a), Ok(aaaa)) => a.into_iter().chain(aaaa).collect(),
+            (Ok(addrs), Err(e)) | (Err(e), Ok(addrs)) if addrs.is_empty() => return Err(e),
+            (Ok(addrs), Err(_)) | (Err(_), Ok(addrs)) => addrs,
+            (Err(e), Err(_)) => return Err(e),
+        };
+        if addrs.is_empty() {
+            return Err(anyhow!("{} has no address in DNS", host));
+        }
+        Ok(addrs)
+    }
+
+    async fn query_if(&self, enabled: bool, host: &str, qtype: u16) -> Result<Vec<IpAddr>> {
+        if !enabled {
+            return Ok(Vec::new());
+        }
+        let resp = self
+            .client
+            .post(self.url.clone())
+            .header(header::CONTENT_TYPE, DNS_MESSAGE)
+            .header(header::ACCEPT, DNS_MESSAGE)
+            .body(encode_query(host, qtype)?)
+            .send()
+            .await
+            .with_context(|| format!("DNS over HTTPS query to {} failed", self.url))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("The DoH server {} answered {}", self.url, resp.status()));
+        }
+        parse_answer(&resp.bytes().await?, qtype)
+            .with_context(|| format!("Invalid answer from the DoH server {}", self.url))
+    }
+}
+
+/// 查询报文：ID 为 0（RFC 8484 建议，便于缓存），设置 RD，一个问题
+fn encode_query(host: &str, qtype: u16) -> Result<Vec<u8>> {
+    let mut msg = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow!("Invalid host name {} for DNS", host));
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&qtype.to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(msg)
+}
+
+/// 取出回答中 qtype 类型的地址，递归解析器会把 CNAME 链和最终的地址一起返回
+fn parse_answer(msg: &[u8], qtype: u16) -> Result<Vec<IpAddr>> {
+    let flags = read_u16(msg, 2)?;
+    if flags & 0x8000 == 0 {
+        return Err(anyhow!("Not a DNS response"));
+    }
+    match flags & 0x000f {
+        0 => {}
+        3 => return Err(anyhow!("The host name does not exist (NXDOMAIN)")),
+        rcode => return Err(anyhow!("The DNS server failed with RCODE {}", rcode)),
+    }
+    let (questions, answers) = (read_u16(msg, 4)?, read_u16(msg, 6)?);
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(msg, pos)? + 4;
+    }
+    let mut addrs = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(msg, pos)?;
+        let (rtype, class) = (read_u16(msg, pos)?, read_u16(msg, pos + 2)?);
+        let len = read_u16(msg, pos + 8)? as usize;
+        let data = msg.get(pos + 10..pos + 10 + len).ok_or_else(truncated)?;
+        pos += 10 + len;
+        if rtype != qtype || class != CLASS_IN {
+            continue;
+        }
+        if let Ok(octets) = <[u8; 4]>::try_from(data) {
+            addrs.push(IpAddr::from(octets));
+        } else if let Ok(octets) = <[u8; 16]>::try_from(data) {
+            addrs.push(IpAddr::from(octets));
+        }
+    }
+    Ok(addrs)
+}
+
+/// 跳过报文中的域名，可能以压缩指针结束
+fn skip_name(msg: &[u8], mut pos: usize) -> Result<usize> {
+    loop {
+        let len = *msg.get(pos).ok_or_else(truncated)? as usize;
+        match len {
+            0 => return Ok(pos + 1),
+            _ if len & 0xc0 == 0xc0 => return Ok(pos + 2),
+            _ => pos += 1 + len,
+        }
+    }
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> Result<u16> {
+    let bytes = msg.get(pos..pos + 2).ok_or_else(truncated)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn truncated() -> anyhow::Error {
+    anyhow!("Truncated DNS response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_query_works() {
+        let msg = encode_query("example.com.", TYPE_AAAA).unwrap();
+        assert_eq!(
+            msg,
+            b"\0\0\x01\0\0\x01\0\0\0\0\0\0\x07example\x03com\0\0\x1c\0\x01".to_vec()
+        );
+        assert!(encode_query("a..b", TYPE_A).is_err());
+    }
+
+    #[test]
+    fn parse_answer_works() {
+        // www.example.com CNAME example.com，example.com A 93.184.215.14，名字使用压缩指针
+        let mut msg = b"\0\0\x81\x80\0\x01\0\x02\0\0\0\0".to_vec();
+        msg.extend_from_slice(b"\x03www\x07example\x03com\0\0\x01\0\x01");
+        msg.extend_from_slice(b"\xc0\x0c\0\x05\0\x01\0\0\x0e\x10\0\x02\xc0\x10");
+        msg.extend_from_slice(b"\xc0\x10\0\x01\0\x01\0\0\x0e\x10\0\x04\x5d\xb8\xd7\x0e");
+        assert_eq!(parse_answer(&msg, TYPE_A).unwrap(), vec![IpAddr::from([93, 184, 215, 14])]);
+        assert!(parse_answer(&msg, TYPE_AAAA).unwrap().is_empty());
+        assert!(parse_answer(&msg[..msg.len() - 2], TYPE_A).is_err());
+        // NXDOMAIN
+        msg[3] = 0x83;
+        assert!(parse_answer(&msg, TYPE_A).unwrap_err().to_string().contains("NXDOMAIN"));
+    }
+}
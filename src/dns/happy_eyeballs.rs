@@ -0,0 +1,97 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::{TcpSocket, TcpStream};
+
+/// RFC 8305 的连接竞争：先依次连接首选类型（第一个地址的类型）的地址，delay 之后或者首选的
+/// 地址都失败时开始连接另一种类型的地址，返回最先连接成功的地址，连接随后关闭
+/// 指定了源地址时和 hyper 一样只连接同一类型的地址，连接前绑定这个源地址
+pub async fn race(addrs: &[SocketAddr], delay: Duration, source: Option<IpAddr>) -> io::Result<SocketAddr> {
+    let addrs: Vec<_> = match source {
+        Some(ip) => addrs.iter().copied().filter(|addr| addr.is_ipv6() == ip.is_ipv6()).collect(),
+        None => addrs.to_vec(),
+    };
+    let prefer_v6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, fallback): (Vec<_>, Vec<_>) =
+        addrs.iter().partition(|addr| addr.is_ipv6() == prefer_v6);
+    let preferred = connect_any(preferred, source);
+    tokio::pin!(preferred);
+    if fallback.is_empty() {
+        return preferred.await;
+    }
+    let fallback = connect_any(fallback, source);
+    tokio::pin!(fallback);
+    let timeout = tokio::time::sleep(delay);
+    tokio::pin!(timeout);
+    let failed = tokio::select! {
+        result = &mut preferred => match result {
+            Ok(addr) => return Ok(addr),
+            Err(_) => true,
+        },
+        _ = &mut timeout => false,
+    };
+    if failed {
+        return fallback.await;
+    }
+    tokio::select! {
+        result = &mut preferred => match result {
+            Ok(addr) => Ok(addr),
+            Err(_) => fallback.await,
+        },
+        result = &mut fallback => match result {
+            Ok(addr) => Ok(addr),
+            Err(_) => preferred.await,
+        },
+    }
+}
+
+/// 依次连接，返回第一个连接成功的地址或者最后一个错误
+async fn connect_any(addrs: Vec<&SocketAddr>, source: Option<IpAddr>) -> io::Result<SocketAddr> {
+    let mut err = io::Error::new(io::ErrorKind::NotFound, "no address to connect to");
+    for &addr in addrs {
+        match connect(addr, source).await {
+            Ok(_) => return Ok(addr),
+            Err(e) => err = e,
+        }
+    }
+    Err(err)
+}
+
+async fn connect(addr: SocketAddr, source: Option<IpAddr>) -> io::Result<TcpStream> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    if let Some(ip) = source {
+        socket.bind(SocketAddr::new(ip, 0))?;
+    }
+    socket.connect(addr).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn race_works() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let delay = Duration::from_secs(60);
+        let source = Some("127.0.0.1".parse().unwrap());
+        assert_eq!(race(&[open], delay, source).await.unwrap(), open);
+        assert!(race(&[], delay, None).await.is_err());
+        // 关闭的端口，连接被拒绝，没有 IPv6 的环境中跳过
+        let closed = match TcpListener::bind("[::1]:0") {
+            Ok(listener) => listener.local_addr().unwrap(),
+            Err(_) => return,
+        };
+        // 首选的 IPv6 地址失败后立即开始连接 IPv4
+        assert_eq!(race(&[closed, open], delay, None).await.unwrap(), open);
+        assert_eq!(race(&[open, closed], delay, None).await.unwrap(), open);
+        assert!(race(&[closed], delay, None).await.is_err());
+        // IPv4 的源地址不连接 IPv6 的地址
+        assert!(race(&[closed], delay, source).await.is_err());
+        assert_eq!(race(&[closed, open], delay, source).await.unwrap(), open);
+    }
+}
@@ -0,0 +1,639 @@
+mod bars;
+mod checksum;
+mod segments;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+use anyhow::{anyhow, Context, Result};
+use clap::Clap;
+use colored::*;
+use encoding_rs::Encoding;
+use indicatif::HumanBytes;
+use percent_encoding::percent_decode_str;
+use reqwest::header::{self, HeaderValue};
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+use crate::client::{ClientArgs, Progress};
+use crate::quiet;
+use bars::Bar;
+pub use bars::{progress_bar, Bars};
+use checksum::{Checksum, Verifier};
+pub use segments::Segment;
+
+// URL 中没有文件名时使用的名字
+const DEFAULT_NAME: &str = "index";
+
+/// 下载以及把 body 保存到文件相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct DownloadArgs {
+    /// Save the response body to a file instead of printing it, with a progress bar showing
+    /// the speed, ETA and percent done. The file is named after the Content-Disposition
+    /// header or the URL, with a -1, -2 ... suffix if it already exists. More URLs may be
+    /// given after the first one to download them all
+    #[clap(short, long, global = true)]
+    download: bool,
+    /// Resume a partial download: request the bytes after the existing file (named after
+    /// the URL) with a Range header and append them to it
+    #[clap(short = 'c', long = "continue", global = true, requires = "download")]
+    resume: bool,
+    /// Save downloads into this directory instead of the current one, it is created
+    /// if it does not exist
+    #[clap(long, global = true, requires = "download")]
+    output_dir: Option<PathBuf>,
+    /// Download N byte ranges of the file at the same time and join them, if the server
+    /// accepts Range requests
+    #[clap(long, global = true, requires = "download", conflicts_with = "resume",
+        value_name = "N")]
+    segments: Option<u64>,
+    /// Fail if the SHA-256 digest (hex) of the downloaded file does not match
+    #[clap(long, global = true, requires = "download", value_name = "HEX",
+        parse(try_from_str = checksum::parse_sha256))]
+    expect_sha256: Option<Checksum>,
+    /// Fail if the SHA-512 digest (hex) of the downloaded file does not match
+    #[clap(long, global = true, requires = "download", value_name = "HEX",
+        parse(try_from_str = checksum::parse_sha512))]
+    expect_sha512: Option<Checksum>,
+    /// Fail if the MD5 digest (hex) of the downloaded file does not match
+    #[clap(long, global = true, requires = "download", value_name = "HEX",
+        parse(try_from_str = checksum::parse_md5))]
+    expect_md5: Option<Checksum>,
+    /// The number of URLs downloaded at the same time when several URLs are given (default 4)
+    #[clap(long, global = true, requires = "download", value_name = "N")]
+    jobs: Option<usize>,
+    /// Write the response body to this file instead of printing it, the status line and
+    /// headers are still printed
+    #[clap(short, long, global = true, conflicts_with = "download")]
+    output: Option<PathBuf>,
+    /// Request only these bytes of the body, e.g. 0-1023, 1024- or -500 (the last 500 bytes),
+    /// and check that the server answers 206 Partial Content with a matching Content-Range
+    #[clap(long, global = true, conflicts_with = "download", value_name = "START-END",
+        allow_hyphen_values = true)]
+    range: Option<ByteRange>,
+}
+
+impl DownloadArgs {
+    pub fn enabled(&self) -> bool {
+        self.download
+    }
+
+    /// --range 时加上 Range header
+    pub fn apply_range(&self, req: RequestBuilder) -> RequestBuilder {
+        match self.range {
+            Some(ref range) => req.header(header::RANGE, range.to_string()),
+            None => req,
+        }
+    }
+
+    /// --range 时检查服务器返回的是否是请求的部分，并打印 Content-Range
+    pub fn check_range(&self, resp: &Response) -> Result<()> {
+        let range = match self.range {
+            Some(ref range) => range,
+            None => return Ok(()),
+        };
+        if resp.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(anyhow!(
+                "The server answered {} instead of 206 Partial Content to Range: {} (--range)",
+                resp.status(),
+                range
+            ));
+        }
+        let value = resp.headers().get(header::CONTENT_RANGE).and_then(|v| v.to_str().ok());
+        let content_range = value.and_then(|v| v.parse::<ContentRange>().ok());
+        let (start, end) = content_range
+            .as_ref()
+            .and_then(|content_range| content_range.range)
+            .ok_or_else(|| anyhow!("The 206 response has no valid Content-Range (--range)"))?;
+        let total = content_range.and_then(|content_range| content_range.total);
+        if !range.matches(start, end, total) {
+            return Err(anyhow!(
+                "The server returned Content-Range: {} for Range: {} (--range)",
+                value.unwrap_or_default(),
+                range
+            ));
+        }
+        let total = total.map_or_else(|| "unknown".to_string(), |total| total.to_string());
+        let msg = format!("Content-Range: bytes {}-{} of {}", start, end, total);
+        quiet::info(msg.cyan());
+        Ok(())
+    }
+
+    /// -o/--output 指定的文件
+    pub fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    /// 把 body 流式地解码并写入 -o/--output 指定的文件，不管响应的状态码
+    pub async fn write_output(
+        &self,
+        client: &ClientArgs,
+        mut resp: Response,
+        progress: &Progress,
+        path: &Path,
+    ) -> Result<()> {
+        let mut file = File::create(path)
+            .await
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut decoder = client.decoder(&resp)?;
+        let mut size = 0;
+        while let Some(chunk) = client.next_decoded(&mut resp, &mut decoder, progress).await? {
+            file.write_all(&chunk)
+                .await
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            size += chunk.len() as u64;
+        }
+        file.flush().await?;
+        let msg = format!("Wrote {} to {}", HumanBytes(size), path.display());
+        quiet::info(msg.cyan());
+        Ok(())
+    }
+
+    /// 同时下载的 URL 数量
+    pub fn jobs(&self) -> usize {
+        self.jobs.unwrap_or(4).max(1)
+    }
+
+    /// --continue 时在发送请求之前就要确定文件名，只能使用 URL 中的文件名
+    /// 文件已经有 N 个字节时加上 Range: bytes=N-
+    /// 同时下载多个 URL 时 bars 是共享的进度条
+    pub fn prepare<'a>(
+        &self,
+        req: RequestBuilder,
+        bars: Option<&'a Bars>,
+    ) -> Result<(RequestBuilder, Target<'a>)> {
+        if !self.resume {
+            let target = Target {
+                path: None,
+                offset: 0,
+                bars,
+            };
+            return Ok((req, target));
+        }
+        let (client, req) = req.build_split();
+        let mut req = req?;
+        let path = self.dir().join(file_name(req.url()));
+        let offset = match std::fs::metadata(&path) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        if offset > 0 {
+            let range = HeaderValue::from_str(&format!("bytes={}-", offset))?;
+            req.headers_mut().insert(header::RANGE, range);
+        }
+        let target = Target {
+            path: Some(path),
+            offset,
+            bars,
+        };
+        Ok((RequestBuilder::from_parts(client, req), target))
+    }
+
+    /// --segments 时除了第一段以外的其它段，服务器需要在 200 响应中声明 Accept-Ranges: bytes
+    /// 第一段直接使用已经收到的响应
+    pub fn segments(&self, resp: &Response, target: &Target<'_>) -> Vec<Segment> {
+        let n = match self.segments {
+            Some(n) if n > 1 => n,
+            _ => return Vec::new(),
+        };
+        let accepts_ranges = resp
+            .headers()
+            .get(header::ACCEPT_RANGES)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"bytes"));
+        match resp.content_length() {
+            Some(len) if resp.status() == StatusCode::OK && accepts_ranges => {
+                segments::split(len, n).into_iter().skip(1).collect()
+            }
+            _ => {
+                let msg = "The server does not accept Range requests, downloading in one piece";
+                target.message(msg.yellow());
+                Vec::new()
+            }
+        }
+    }
+
+    /// 需要校验的摘要
+    fn checksums(&self) -> impl Iterator<Item = &Checksum> {
+        self.expect_sha256.iter().chain(&self.expect_sha512).chain(&self.expect_md5)
+    }
+
+    /// 保存下载的目录，没有 --output-dir 时为当前目录
+    fn dir(&self) -> PathBuf {
+        self.output_dir.clone().unwrap_or_default()
+    }
+
+    /// 打开要写入的文件：继续下载时打开 prepare 确定的文件，否则按照 Content-Disposition
+    /// 或者 URL 确定文件名，文件已经存在时依次尝试 name-1.ext、name-2.ext ...
+    async fn open(
+        &self,
+        resp: &Response,
+        target: &Target<'_>,
+        offset: u64,
+    ) -> Result<(File, PathBuf)> {
+        if let Some(ref dir) = self.output_dir {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut options = OpenOptions::new();
+        if let Some(ref path) = target.path {
+            match offset {
+                0 => options.write(true).create(true).truncate(true),
+                _ => options.append(true),
+            };
+            let file = options
+                .open(path)
+                .await
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            return Ok((file, path.clone()));
+        }
+        let name = response_name(resp);
+        options.write(true).create_new(true);
+        let mut n = 0;
+        loop {
+            let path = self.dir().join(numbered(&name, n));
+            match options.open(&path).await {
+                Ok(file) => return Ok((file, path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to create {}", path.display()))
+                }
+            }
+        }
+    }
+
+    /// 把 body 流式地写入文件，同时在 stderr 上显示进度条
+    /// 继续下载时检查 206 响应的 Content-Range 是否正好从文件末尾开始，然后追加到文件
+    /// 指定了 --expect-sha256 等参数时在写入的同时计算摘要，最后校验整个文件
+    /// parts 是 --segments 时其它段的响应，这时各段同时写入文件，写完之后再计算摘要
+    pub async fn save(
+        &self,
+        client: &ClientArgs,
+        mut resp: Response,
+        progress: &Progress,
+        target: Target<'_>,
+        parts: Vec<(Segment, Response)>,
+    ) -> Result<()> {
+        let mut verifier = Verifier::new(self.checksums());
+        if let (true, Some(path)) = (target.is_complete(&resp), &target.path) {
+            verifier.update_from_file(path, target.offset).await?;
+            verifier.verify(path)?;
+            let msg = format!("{} is already fully downloaded", path.display());
+            target.message(msg.cyan());
+            return Ok(());
+        }
+        let offset = target.resume_offset(&resp)?;
+        let (mut file, path) = self.open(&resp, &target, offset).await?;
+        verifier.update_from_file(&path, offset).await?;
+        let len = resp.content_length();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let bar = Bar::new(target.bars, &name, offset, len);
+        let start = Instant::now();
+        let count = parts.len() + 1;
+        if parts.is_empty() {
+            while let Some(chunk) = client.next_chunk(&mut resp, progress).await? {
+                file.write_all(&chunk)
+                    .await
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                verifier.update(&chunk);
+                bar.inc(chunk.len() as u64);
+            }
+            file.flush().await?;
+        } else {
+            let len = len.unwrap_or_default();
+            file.set_len(len).await?;
+            drop(file);
+            segments::write_all(client, resp, parts, progress, &path, &bar).await?;
+            verifier.update_from_file(&path, len).await?;
+        }
+        bar.finish();
+        verifier.verify(&path)?;
+        let elapsed = start.elapsed().as_secs_f64();
+        let size = bar.position() - offset;
+        let detail = match (offset, count) {
+            (0, 1) => String::new(),
+            (0, _) => format!(" in {} segments", count),
+            _ => format!(", resumed after {}", HumanBytes(offset)),
+        };
+        let msg = format!(
+            "Downloaded {} to {} in {:.2}s ({}/s){}",
+            HumanBytes(size),
+            path.display(),
+            elapsed,
+            HumanBytes((size as f64 / elapsed.max(0.001)) as u64),
+            detail
+        );
+        target.message(msg.cyan());
+        Ok(())
+    }
+}
+
+/// --continue 时下载的目标文件和已经下载的字节数，否则收到响应之后才确定文件名
+pub struct Target<'a> {
+    path: Option<PathBuf>,
+    offset: u64,
+    bars: Option<&'a Bars>,
+}
+
+impl Target<'_> {
+    /// 打印一行消息，同时下载多个 URL 时打印在进度条的上方
+    fn message(&self, msg: ColoredString) {
+        match self.bars {
+            Some(_) if quiet::is_quiet() => {}
+            Some(bars) => bars.message(msg),
+            None => quiet::info(msg),
+        }
+    }
+
+    /// 是否把响应的 body 保存到文件，只保存成功的响应，错误和重定向的 body 照常打印
+    /// 继续下载时 416 并且总大小等于文件大小说明文件已经下载完了
+    pub fn wants(&self, resp: &Response) -> bool {
+        resp.status().is_success() || self.is_complete(resp)
+    }
+
+    fn is_complete(&self, resp: &Response) -> bool {
+        self.offset > 0
+            && resp.status() == StatusCode::RANGE_NOT_SATISFIABLE
+            && content_range(resp).and_then(|range| range.total) == Some(self.offset)
+    }
+
+    fn display(&self) -> std::path::Display<'_> {
+        self.path.as_deref().unwrap_or_else(|| Path::new("")).display()
+    }
+
+    /// 从哪个字节开始写入文件，服务器忽略了 Range 返回 200 时从头开始下载
+    fn resume_offset(&self, resp: &Response) -> Result<u64> {
+        if self.offset == 0 {
+            return Ok(0);
+        }
+        if resp.status() != StatusCode::PARTIAL_CONTENT {
+            let msg = format!(
+                "The server does not support resuming ({}), downloading {} again",
+                resp.status(),
+                self.display()
+            );
+            self.message(msg.yellow());
+            return Ok(0);
+        }
+        match content_range(resp).and_then(|range| range.range) {
+            Some((start, _)) if start == self.offset => Ok(start),
+            Some((start, _)) => Err(anyhow!(
+                "The server resumed at byte {} instead of byte {} (--continue)",
+                start,
+                self.offset
+            )),
+            None => Err(anyhow!("The 206 response has no valid Content-Range (--continue)")),
+        }
+    }
+}
+
+/// Content-Range: bytes 100-199/1000，416 响应中为 bytes */1000，总大小未知时为 *
+#[derive(Debug, PartialEq)]
+pub struct ContentRange {
+    pub range: Option<(u64, u64)>,
+    pub total: Option<u64>,
+}
+
+impl FromStr for ContentRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!("Invalid Content-Range {}", s);
+        let (range, total) = s
+            .trim()
+            .strip_prefix("bytes ")
+            .and_then(|rest| rest.split_once('/'))
+            .ok_or_else(err)?;
+        let range = match range.split_once('-') {
+            Some((start, end)) => Some((start.parse()?, end.parse()?)),
+            None if range == "*" => None,
+            None => return Err(err()),
+        };
+        let total = match total {
+            "*" => None,
+            total => Some(total.parse()?),
+        };
+        Ok(Self { range, total })
+    }
+}
+
+/// --range 请求的字节范围：start-end、start- 或者 -suffix（最后 suffix 个字节）
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    Range(u64, Option<u64>),
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Content-Range 是否符合请求的范围，服务器可以把超出文件末尾的 end 截短
+    fn matches(&self, start: u64, end: u64, total: Option<u64>) -> bool {
+        match *self {
+            Self::Range(s, e) => start == s && e.is_none_or(|e| end <= e),
+            Self::Suffix(n) => {
+                total.is_none_or(|total| end + 1 == total && end + 1 - start <= n)
+            }
+        }
+    }
+}
+
+impl FromStr for ByteRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!("Invalid range {}, expected START-END, START- or -SUFFIX", s);
+        let (start, end) = s.trim().split_once('-').ok_or_else(err)?;
+        let parse = |n: &str| n.parse::<u64>().map_err(|_| err());
+        match (start, end) {
+            ("", "") => Err(err()),
+            ("", suffix) => Ok(Self::Suffix(parse(suffix)?)),
+            (start, "") => Ok(Self::Range(parse(start)?, None)),
+            (start, end) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err(err());
+                }
+                Ok(Self::Range(start, Some(end)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Range(start, Some(end)) => write!(f, "bytes={}-{}", start, end),
+            Self::Range(start, None) => write!(f, "bytes={}-", start),
+            Self::Suffix(n) => write!(f, "bytes=-{}", n),
+        }
+    }
+}
+
+/// 响应的 Content-Range
+pub fn content_range(resp: &Response) -> Option<ContentRange> {
+    resp.headers().get(header::CONTENT_RANGE)?.to_str().ok()?.parse().ok()
+}
+
+/// 响应的文件名，优先使用 Content-Disposition 中的文件名，其次是最终的 URL 中的文件名
+fn response_name(resp: &Response) -> String {
+    resp.headers()
+        .get(header::CONTENT_DISPOSITION)
+        .and_then(|value| disposition_filename(&String::from_utf8_lossy(value.as_bytes())))
+        .and_then(|name| {
+            // 只使用最后一段，服务器不能指定目录
+            let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+            sanitize(name)
+        })
+        .unwrap_or_else(|| file_name(resp.url()))
+}
+
+/// URL 路径的最后一段作为文件名，解码后去掉路径分隔符，避免写到下载目录之外
+fn file_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|segment| percent_decode_str(segment).decode_utf8_lossy().replace(['/', '\\'], "_"))
+        .and_then(|name| sanitize(&name))
+        .unwrap_or_else(|| DEFAULT_NAME.to_string())
+}
+
+/// 去掉开头的 . 和空白，不生成隐藏文件，也排除了 . 和 ..
+fn sanitize(name: &str) -> Option<String> {
+    let name = name.trim_start_matches(['.', ' ']).trim_end();
+    Some(name.to_string()).filter(|name| !name.is_empty())
+}
+
+/// 文件名已经存在时使用的第 n 个名字，序号加在扩展名之前，例如 file-1.zip、app-2.tar.gz
+fn numbered(name: &str, n: u32) -> String {
+    if n == 0 {
+        return name.to_string();
+    }
+    let stem = match name.rfind('.') {
+        Some(i) if name[..i].ends_with(".tar") => i - 4,
+        Some(i) if i > 0 => i,
+        _ => name.len(),
+    };
+    format!("{}-{}{}", &name[..stem], n, &name[stem..])
+}
+
+/// Content-Disposition 中的文件名，RFC 5987 编码的 filename* 优先于 filename
+fn disposition_filename(value: &str) -> Option<String> {
+    let params = disposition_params(value);
+    let find = |key: &str| params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+    find("filename*")
+        .and_then(decode_ext_value)
+        .or_else(|| find("filename").map(|v| v.to_string()))
+}
+
+/// 解析 attachment; filename="a.zip"; size=100 中类型之后的参数，名字转换成小写
+/// 引号中的值可以包含 ; 和 \ 转义的字符
+fn disposition_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = value.split_once(';').map(|(_, rest)| rest).unwrap_or_default();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.rsplit(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let after = after.trim_start();
+        let (v, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut v = String::new();
+                let mut end = quoted.len();
+                let mut chars = quoted.char_indices();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => v.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => v.push(c),
+                    }
+                }
+                (v, &quoted[end..])
+            }
+            None => {
+                let end = after.find(';').unwrap_or(after.len());
+                (after[..end].trim().to_string(), &after[end..])
+            }
+        };
+        params.push((key, v));
+        rest = remaining;
+    }
+    params
+}
+
+/// 解码 RFC 5987 的 charset'language'percent-encoded 格式
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let (charset, _lang, encoded) = (parts.next()?, parts.next()?, parts.next()?);
+    let encoding = Encoding::for_label(charset.as_bytes())?;
+    let bytes: Vec<u8> = percent_decode_str(encoded).collect();
+    Some(encoding.decode_without_bom_handling(&bytes).0.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_works() {
+        let name = |url: &str| file_name(&url.parse().unwrap());
+        assert_eq!(name("https://example.com/files/app.tar.gz?v=1"), "app.tar.gz");
+        assert_eq!(name("https://example.com/my%20file.txt"), "my file.txt");
+        assert_eq!(name("https://example.com/a%2F..%2Fb"), "a_.._b");
+        assert_eq!(name("https://example.com/files/"), "index");
+        assert_eq!(name("https://example.com/.."), "index");
+        assert_eq!(name("https://example.com/.bashrc"), "bashrc");
+    }
+
+    #[test]
+    fn numbered_works() {
+        assert_eq!(numbered("file.zip", 0), "file.zip");
+        assert_eq!(numbered("file.zip", 1), "file-1.zip");
+        assert_eq!(numbered("app.tar.gz", 2), "app-2.tar.gz");
+        assert_eq!(numbered("README", 1), "README-1");
+    }
+
+    #[test]
+    fn disposition_filename_works() {
+        let name = disposition_filename;
+        assert_eq!(name("attachment; filename=report.pdf").unwrap(), "report.pdf");
+        assert_eq!(name(r#"attachment; filename="a \"b\"; c.txt""#).unwrap(), r#"a "b"; c.txt"#);
+        let value = r#"attachment; filename="naive.txt"; filename*=UTF-8''na%C3%AFve.txt"#;
+        assert_eq!(name(value).unwrap(), "na\u{ef}ve.txt");
+        assert_eq!(name("attachment; FileName = x.bin ; size=3").unwrap(), "x.bin");
+        assert!(name("inline").is_none());
+        assert!(name("attachment; foo; filename*=bogus").is_none());
+    }
+
+    #[test]
+    fn parse_content_range_works() {
+        let range: ContentRange = "bytes 100-199/1000".parse().unwrap();
+        assert_eq!(range, ContentRange { range: Some((100, 199)), total: Some(1000) });
+        let range: ContentRange = "bytes 0-9/*".parse().unwrap();
+        assert_eq!(range, ContentRange { range: Some((0, 9)), total: None });
+        let range: ContentRange = "bytes */1000".parse().unwrap();
+        assert_eq!(range, ContentRange { range: None, total: Some(1000) });
+        assert!("items 0-9/10".parse::<ContentRange>().is_err());
+        assert!("bytes 0-x/10".parse::<ContentRange>().is_err());
+    }
+
+    #[test]
+    fn byte_range_works() {
+        let range: ByteRange = "0-1023".parse().unwrap();
+        assert_eq!(range.to_string(), "bytes=0-1023");
+        assert!(range.matches(0, 1023, Some(5000)));
+        assert!(range.matches(0, 99, Some(100)));
+        assert!(!range.matches(0, 2047, Some(5000)));
+        let range: ByteRange = "1024-".parse().unwrap();
+        assert_eq!(range, ByteRange::Range(1024, None));
+        assert!(!range.matches(0, 4999, Some(5000)));
+        let range: ByteRange = "-500".parse().unwrap();
+        assert_eq!(range.to_string(), "bytes=-500");
+        assert!(range.matches(4500, 4999, Some(5000)));
+        assert!(!range.matches(0, 499, Some(5000)));
+        assert!("10-1".parse::<ByteRange>().is_err());
+        assert!("-".parse::<ByteRange>().is_err());
+        assert!("abc".parse::<ByteRange>().is_err());
+    }
+}
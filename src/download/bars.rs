@@ -0,0 +1,90 @@
+use colored::ColoredString;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::quiet;
+
+// 知道 Content-Length 时显示百分比和剩余时间，否则只显示已经下载的字节数和速度
+const BAR_TEMPLATE: &str =
+    "{prefix}{percent:>3}% [{wide_bar}] {bytes}/{total_bytes} {bytes_per_sec} ETA {eta}";
+const SPINNER_TEMPLATE: &str = "{prefix}{spinner} {bytes} {bytes_per_sec} {elapsed}";
+
+/// 同时下载多个 URL 时共享的进度条：每个文件一个，最下面是所有文件的总进度
+pub struct Bars {
+    multi: MultiProgress,
+    total: ProgressBar,
+}
+
+impl Bars {
+    pub fn new() -> Self {
+        let multi = MultiProgress::with_draw_target(draw_target());
+        let total = multi.add(progress_bar(Some(0)).with_prefix("total "));
+        Self { multi, total }
+    }
+
+    /// 在进度条上方打印一行消息，直接打印到 stderr 会打乱进度条
+    pub fn message(&self, msg: ColoredString) {
+        self.multi.suspend(|| eprintln!("{}", msg));
+    }
+
+    pub fn finish(&self) {
+        self.total.finish_and_clear();
+    }
+}
+
+/// 一个文件的进度条，同时更新总进度
+pub struct Bar<'a> {
+    file: ProgressBar,
+    bars: Option<&'a Bars>,
+}
+
+impl<'a> Bar<'a> {
+    /// offset 是继续下载之前已经有的字节数，len 是这次要下载的字节数
+    pub fn new(bars: Option<&'a Bars>, name: &str, offset: u64, len: Option<u64>) -> Self {
+        let mut file = progress_bar(len.map(|len| offset + len));
+        if let Some(bars) = bars {
+            file = bars.multi.insert_before(&bars.total, file.with_prefix(format!("{} ", name)));
+            bars.total.inc_length(offset + len.unwrap_or_default());
+            bars.total.inc(offset);
+        }
+        file.set_position(offset);
+        file.reset_eta();
+        Self { file, bars }
+    }
+
+    pub fn inc(&self, n: u64) {
+        self.file.inc(n);
+        if let Some(bars) = self.bars {
+            bars.total.inc(n);
+        }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.file.position()
+    }
+
+    pub fn finish(&self) {
+        self.file.finish_and_clear();
+    }
+}
+
+/// 进度条画在 stderr 上，stderr 不是终端或者 -q 时不显示
+pub fn progress_bar(len: Option<u64>) -> ProgressBar {
+    let bar = match len {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template(BAR_TEMPLATE)
+                .unwrap()
+                .progress_chars("=> "),
+        ),
+        None => ProgressBar::new_spinner()
+            .with_style(ProgressStyle::with_template(SPINNER_TEMPLATE).unwrap()),
+    };
+    bar.set_draw_target(draw_target());
+    bar
+}
+
+fn draw_target() -> ProgressDrawTarget {
+    match quiet::is_quiet() {
+        true => ProgressDrawTarget::hidden(),
+        false => ProgressDrawTarget::stderr(),
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::AsyncReadExt;
+
+/// 校验下载文件使用的摘要算法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    Md5,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha256 => "SHA-256",
+            Self::Sha512 => "SHA-512",
+        }
+    }
+
+    fn flag(self) -> &'static str {
+        match self {
+            Self::Md5 => "--expect-md5",
+            Self::Sha256 => "--expect-sha256",
+            Self::Sha512 => "--expect-sha512",
+        }
+    }
+
+    // 十六进制摘要的长度
+    fn hex_len(self) -> usize {
+        match self {
+            Self::Md5 => 32,
+            Self::Sha256 => 64,
+            Self::Sha512 => 128,
+        }
+    }
+}
+
+/// --expect-sha256 等参数期望的摘要，统一保存为小写的十六进制
+#[derive(Debug)]
+pub struct Checksum {
+    algorithm: Algorithm,
+    expected: String,
+}
+
+impl Checksum {
+    fn parse(algorithm: Algorithm, s: &str) -> Result<Self> {
+        let expected = s.trim().to_ascii_lowercase();
+        if expected.len() != algorithm.hex_len()
+            || !expected.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return Err(anyhow!(
+                "{} must be {} hex digits",
+                algorithm.name(),
+                algorithm.hex_len()
+            ));
+        }
+        Ok(Self {
+            algorithm,
+            expected,
+        })
+    }
+}
+
+pub fn parse_md5(s: &str) -> Result<Checksum> {
+    Checksum::parse(Algorithm::Md5, s)
+}
+
+pub fn parse_sha256(s: &str) -> Result<Checksum> {
+    Checksum::parse(Algorithm::Sha256, s)
+}
+
+pub fn parse_sha512(s: &str) -> Result<Checksum> {
+    Checksum::parse(Algorithm::Sha512, s)
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Md5 => Self::Md5(Md5::new()),
+            Algorithm::Sha256 => Self::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Self::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Md5(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+            Self::Sha512(h) => h.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Self::Md5(h) => format!("{:x}", h.finalize()),
+            Self::Sha256(h) => format!("{:x}", h.finalize()),
+            Self::Sha512(h) => format!("{:x}", h.finalize()),
+        }
+    }
+}
+
+/// 一边下载一边计算所有指定的摘要，下载完成后和期望的值比较
+pub struct Verifier<'a> {
+    hashers: Vec<(&'a Checksum, Hasher)>,
+}
+
+impl<'a> Verifier<'a> {
+    pub fn new(checksums: impl Iterator<Item = &'a Checksum>) -> Self {
+        Self {
+            hashers: checksums.map(|c| (c, Hasher::new(c.algorithm))).collect(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for (_, hasher) in &mut self.hashers {
+            hasher.update(data);
+        }
+    }
+
+    /// 继续下载时先计算文件中已有的前 len 个字节
+    pub async fn update_from_file(&mut self, path: &Path, len: u64) -> Result<()> {
+        if self.hashers.is_empty() {
+            return Ok(());
+        }
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut file = file.take(len);
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.update(&buf[..n]);
+        }
+    }
+
+    /// 摘要不一致时报错，文件保留下来以便检查
+    pub fn verify(self, path: &Path) -> Result<()> {
+        for (checksum, hasher) in self.hashers {
+            let actual = hasher.finalize();
+            if actual != checksum.expected {
+                return Err(anyhow!(
+                    "{} of {} is {}, expected {} ({})",
+                    checksum.algorithm.name(),
+                    path.display(),
+                    actual,
+                    checksum.expected,
+                    checksum.algorithm.flag()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_works() {
+        let md5 = parse_md5("900150983CD24FB0D6963F7D28E17F72").unwrap();
+        let sha256 =
+            parse_sha256("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+                .unwrap();
+        let mut verifier = Verifier::new([&md5, &sha256].iter().copied());
+        verifier.update(b"a");
+        verifier.update(b"bc");
+        assert!(verifier.verify(Path::new("abc")).is_ok());
+
+        let sha512 = parse_sha512(&"0".repeat(128)).unwrap();
+        let mut verifier = Verifier::new(std::iter::once(&sha512));
+        verifier.update(b"abc");
+        let err = verifier.verify(Path::new("abc")).unwrap_err().to_string();
+        assert!(err.starts_with("SHA-512 of abc is ddaf35a1"));
+        assert!(err.ends_with("(--expect-sha512)"));
+
+        assert!(parse_sha256("abc").is_err());
+        assert!(parse_md5(&"g".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::SeekFrom;
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+use reqwest::{header, RequestBuilder, Response, StatusCode};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use super::{content_range, Bar};
+use crate::client::{ClientArgs, Progress};
+
+/// --segments 下载的一段，start 和 end 都包含在内
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Segment {
+    /// 获取这一段的请求
+    pub fn request(&self, req: RequestBuilder) -> RequestBuilder {
+        req.header(header::RANGE, format!("bytes={}-{}", self.start, self.end))
+    }
+
+    fn len(&self) -> u64 {
+        (self.end - self.start).saturating_add(1)
+    }
+
+    /// 服务器必须返回 206，并且 Content-Range 正好是请求的这一段
+    fn check(&self, resp: &Response) -> Result<()> {
+        let range = content_range(resp).and_then(|range| range.range);
+        if resp.status() != StatusCode::PARTIAL_CONTENT || range != Some((self.start, self.end)) {
+            return Err(anyhow!(
+                "The server answered the segment bytes={}-{} with {} {} (--segments)",
+                self.start,
+                self.end,
+                resp.status(),
+                resp.headers()
+                    .get(header::CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("without Content-Range")
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 把 len 个字节平均分成 n 段，余下的字节放在最后一段
+pub fn split(len: u64, n: u64) -> Vec<Segment> {
+    if len == 0 {
+        return Vec::new();
+    }
+    let n = n.clamp(1, len.max(1));
+    let size = len / n;
+    (0..n)
+        .map(|i| Segment {
+            start: i * size,
+            end: if i == n - 1 { len - 1 } else { (i + 1) * size - 1 },
+        })
+        .collect()
+}
+
+/// 把一段写入文件中对应的位置，resp 的 body 比这一段长时（第一段使用原来的响应）只读取这一段
+pub async fn write(
+    client: &ClientArgs,
+    mut resp: Response,
+    progress: &Progress,
+    path: &Path,
+    segment: Segment,
+    bar: &Bar<'_>,
+) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.seek(SeekFrom::Start(segment.start)).await?;
+    let mut remaining = segment.len();
+    while remaining > 0 {
+        let chunk = match client.next_chunk(&mut resp, progress).await? {
+            Some(chunk) => chunk,
+            None => break,
+        };
+        let n = remaining.min(chunk.len() as u64);
+        file.write_all(&chunk[..n as usize])
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        remaining -= n;
+        bar.inc(n);
+    }
+    file.flush().await?;
+    if remaining > 0 {
+        return Err(anyhow!(
+            "The segment bytes={}-{} ended {} bytes early (--segments)",
+            segment.start,
+            segment.end,
+            remaining
+        ));
+    }
+    Ok(())
+}
+
+/// 检查其它段的响应，然后同时下载所有的段，第一段是原来的响应中 parts 之前的部分
+pub async fn write_all(
+    client: &ClientArgs,
+    first: Response,
+    parts: Vec<(Segment, Response)>,
+    progress: &Progress,
+    path: &Path,
+    bar: &Bar<'_>,
+) -> Result<()> {
+    for (segment, resp) in &parts {
+        segment.check(resp)?;
+    }
+    let first_segment = Segment {
+        start: 0,
+        end: parts.first().map_or(u64::MAX, |(segment, _)| segment.start - 1),
+    };
+    let tasks = std::iter::once((first_segment, first))
+        .chain(parts)
+        .map(|(segment, resp)| write(client, resp, progress, path, segment, bar));
+    futures::future::try_join_all(tasks).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_works() {
+        let segment = |start, end| Segment { start, end };
+        assert_eq!(split(10, 3), vec![segment(0, 2), segment(3, 5), segment(6, 9)]);
+        assert_eq!(split(2, 4), vec![segment(0, 0), segment(1, 1)]);
+        assert_eq!(split(5, 1), vec![segment(0, 4)]);
+        assert!(split(0, 4).is_empty());
+    }
+}
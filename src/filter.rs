@@ -0,0 +1,193 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+// 不存在的字段和越界的下标得到 null，和 jq 一样
+static NULL: Value = Value::Null;
+
+/// --filter 指定的路径，支持 jq 的 .items[].name、.[0]、."a b" 和 ["a b"]，
+/// 也可以写成 JSONPath 的 $.items[*].name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    // 对象的字段
+    Key(String),
+    // 数组的下标，负数从末尾开始
+    Index(i64),
+    // 数组的每个元素或者对象的每个值
+    Iter,
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |reason: &str| anyhow!("Invalid --filter {}, {}", s, reason);
+        let expr = s.trim();
+        let mut rest = expr.strip_prefix('$').unwrap_or(expr);
+        if !expr.starts_with('$') && !rest.starts_with('.') {
+            return Err(err("it must start with . or $"));
+        }
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('[') {
+                let end = bracket_end(after).ok_or_else(|| err("missing ]"))?;
+                steps.push(parse_bracket(after[..end].trim()).ok_or_else(|| err("invalid [...]"))?);
+                rest = &after[end + 1..];
+                continue;
+            }
+            let after = rest.strip_prefix('.').ok_or_else(|| err("expected . or ["))?;
+            if after.starts_with('[') || (after.is_empty() && steps.is_empty()) {
+                rest = after;
+            } else if let Some(wildcard) = after.strip_prefix('*') {
+                steps.push(Step::Iter);
+                rest = wildcard;
+            } else if after.starts_with('"') {
+                let end = quoted_end(after).ok_or_else(|| err("unterminated string"))?;
+                let key = serde_json::from_str(&after[..end]).map_err(|_| err("invalid string"))?;
+                steps.push(Step::Key(key));
+                rest = &after[end..];
+            } else {
+                let len = after
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(after.len());
+                if len == 0 {
+                    return Err(err("missing the field name after ."));
+                }
+                steps.push(Step::Key(after[..len].to_string()));
+                rest = &after[len..];
+            }
+        }
+        Ok(Self { steps })
+    }
+}
+
+/// 从 [ 之后开始，对应的 ] 的位置，跳过引号中的 ]
+fn bracket_end(s: &str) -> Option<usize> {
+    let mut i = 0;
+    while let Some(c) = s[i..].chars().next() {
+        match c {
+            ']' => return Some(i),
+            '"' | '\'' => i += quoted_end(&s[i..])?,
+            _ => i += c.len_utf8(),
+        }
+    }
+    None
+}
+
+/// 以引号开始的字符串的长度，包括两边的引号
+fn quoted_end(s: &str) -> Option<usize> {
+    let quote = s.chars().next()?;
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// [] 中的内容：空或者 * 是所有元素，整数是下标，带引号的是字段名
+fn parse_bracket(s: &str) -> Option<Step> {
+    if s.is_empty() || s == "*" {
+        return Some(Step::Iter);
+    }
+    if let Ok(i) = s.parse() {
+        return Some(Step::Index(i));
+    }
+    if let Some(key) = s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        return Some(Step::Key(key.to_string()));
+    }
+    serde_json::from_str(s).ok().map(Step::Key)
+}
+
+impl Filter {
+    /// 对一个 JSON 值应用路径，[] 会得到多个结果，对类型不对的值取字段或者下标是错误
+    pub fn apply(&self, value: &Value) -> Result<Vec<Value>> {
+        let mut values = vec![value];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for value in values {
+                match (step, value) {
+                    (Step::Key(key), Value::Object(map)) => next.push(map.get(key).unwrap_or(&NULL)),
+                    (Step::Index(i), Value::Array(items)) => {
+                        let i = if *i < 0 { items.len() as i64 + i } else { *i };
+                        let item = usize::try_from(i).ok().and_then(|i| items.get(i));
+                        next.push(item.unwrap_or(&NULL));
+                    }
+                    (Step::Key(_), Value::Null) | (Step::Index(_), Value::Null) => next.push(&NULL),
+                    (Step::Iter, Value::Array(items)) => next.extend(items),
+                    (Step::Iter, Value::Object(map)) => next.extend(map.values()),
+                    (Step::Key(key), v) => {
+                        return Err(anyhow!("--filter: cannot get the field {:?} of {}", key, type_name(v)))
+                    }
+                    (Step::Index(i), v) => {
+                        return Err(anyhow!("--filter: cannot get the element {} of {}", i, type_name(v)))
+                    }
+                    (Step::Iter, v) => return Err(anyhow!("--filter: cannot iterate over {}", type_name(v))),
+                }
+            }
+            values = next;
+        }
+        Ok(values.into_iter().cloned().collect())
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_filter_works() {
+        let filter = |s: &str| s.parse::<Filter>().unwrap().steps;
+        let name = || Step::Key("name".into());
+        let items = || Step::Key("items".into());
+        assert_eq!(filter("."), vec![]);
+        assert_eq!(filter("$"), vec![]);
+        assert_eq!(filter(".items[].name"), vec![items(), Step::Iter, name()]);
+        assert_eq!(filter("$.items[*].name"), vec![items(), Step::Iter, name()]);
+        assert_eq!(filter(".[0]"), vec![Step::Index(0)]);
+        assert_eq!(filter(".items[-1]"), vec![items(), Step::Index(-1)]);
+        assert_eq!(filter(r#"."a b"["c]"]['d']"#), vec![
+            Step::Key("a b".into()),
+            Step::Key("c]".into()),
+            Step::Key("d".into()),
+        ]);
+        assert!("items".parse::<Filter>().is_err());
+        assert!(".items[".parse::<Filter>().is_err());
+        assert!(".items.".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn apply_filter_works() {
+        let value = json!({"items": [{"name": "a", "n": 1}, {"name": "b"}], "total": 2});
+        let apply = |s: &str| s.parse::<Filter>().unwrap().apply(&value);
+        assert_eq!(apply(".items[].name").unwrap(), vec![json!("a"), json!("b")]);
+        assert_eq!(apply(".items[].n").unwrap(), vec![json!(1), Value::Null]);
+        assert_eq!(apply(".items[-1].name").unwrap(), vec![json!("b")]);
+        assert_eq!(apply(".items[5].name").unwrap(), vec![Value::Null]);
+        assert_eq!(apply(".total").unwrap(), vec![json!(2)]);
+        assert!(apply(".total[]").is_err());
+        assert!(apply(".items.name").is_err());
+    }
+}
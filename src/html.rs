@@ -0,0 +1,318 @@
+use colored::*;
+use mime::Mime;
+
+// 每一层缩进的空格数
+const INDENT: usize = 2;
+
+// 没有内容也没有结束标签的元素
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source",
+    "track", "wbr",
+];
+
+// 内容原样保留的元素，里面的 < 不是标签
+const RAW: &[&str] = &["script", "style", "pre", "textarea"];
+
+/// 是否是 HTML 的 body
+pub fn is_html(mime: Option<&Mime>) -> bool {
+    mime.is_some_and(|m| matches!(m.essence_str(), "text/html" | "application/xhtml+xml"))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    // 开始或者结束标签，name 是小写的标签名
+    Tag { raw: &'a str, name: String, end: bool },
+    Text(&'a str),
+    // 注释和 <!DOCTYPE>
+    Comment(&'a str),
+}
+
+/// 把 HTML 切分成标签、文本和注释，不检查文档是否合法，无法识别的 < 当作文本
+fn tokenize(s: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let (token, len) = next_token(rest);
+        rest = &rest[len..];
+        // script 等元素的内容一直到对应的结束标签
+        if let Token::Tag { ref name, end: false, .. } = token {
+            if RAW.contains(&name.as_str()) {
+                let close = format!("</{}", name);
+                let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+                tokens.push(token);
+                if end > 0 {
+                    tokens.push(Token::Text(&rest[..end]));
+                }
+                rest = &rest[end..];
+                continue;
+            }
+        }
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// rest 开头的一个 token 和它的长度
+fn next_token(rest: &str) -> (Token<'_>, usize) {
+    if let Some(comment) = rest.strip_prefix("<!--") {
+        let len = comment.find("-->").map(|i| i + 7).unwrap_or(rest.len());
+        return (Token::Comment(&rest[..len]), len);
+    }
+    let bytes = rest.as_bytes();
+    let is_tag = bytes[0] == b'<'
+        && match bytes.get(1) {
+            Some(b'!') | Some(b'?') => true,
+            Some(b'/') => bytes.get(2).is_some_and(|b| b.is_ascii_alphabetic()),
+            Some(b) => b.is_ascii_alphabetic(),
+            None => false,
+        };
+    if !is_tag {
+        // 文本一直到下一个 <，开头的 < 不是标签时也属于文本
+        let first = rest.char_indices().nth(1).map_or(rest.len(), |(i, _)| i);
+        let len = rest[first..].find('<').map(|i| i + first).unwrap_or(rest.len());
+        return (Token::Text(&rest[..len]), len);
+    }
+    let len = tag_len(rest);
+    let raw = &rest[..len];
+    if bytes[1] == b'!' || bytes[1] == b'?' {
+        return (Token::Comment(raw), len);
+    }
+    let end = bytes[1] == b'/';
+    let name: String = raw[if end { 2 } else { 1 }..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == ':')
+        .collect();
+    let name = name.to_ascii_lowercase();
+    (Token::Tag { raw, name, end }, len)
+}
+
+/// 标签的长度，属性值中的 > 不会结束标签
+fn tag_len(s: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return i + 1,
+            None => {}
+        }
+    }
+    s.len()
+}
+
+/// 高亮 HTML：标签名蓝色，属性名绿色，属性值黄色，注释和 DOCTYPE 暗色，文本保持原样
+pub fn highlight(s: &str) -> String {
+    tokenize(s).iter().map(paint).collect()
+}
+
+fn paint(token: &Token) -> String {
+    match token {
+        Token::Text(text) => text.to_string(),
+        Token::Comment(comment) => comment.dimmed().to_string(),
+        Token::Tag { raw, name, end } => {
+            let prefix = if *end { 2 } else { 1 };
+            let head = &raw[..prefix + name.len()];
+            let body = &raw[prefix + name.len()..];
+            let close_len = if body.ends_with("/>") {
+                2
+            } else if body.ends_with('>') {
+                1
+            } else {
+                0
+            };
+            let (attrs, close) = body.split_at(body.len() - close_len);
+            format!("{}{}{}", head.blue(), paint_attrs(attrs), close.blue())
+        }
+    }
+}
+
+/// 高亮标签中的属性，保留原来的空白和引号
+fn paint_attrs(s: &str) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let space = rest.len() - rest.trim_start().len();
+        out.push_str(&rest[..space]);
+        rest = &rest[space..];
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        if name_len == 0 && !rest.starts_with('=') {
+            break;
+        }
+        out.push_str(&rest[..name_len].green().to_string());
+        rest = &rest[name_len..];
+        if let Some(value) = rest.strip_prefix('=') {
+            let len = match value.chars().next() {
+                Some(q @ '"') | Some(q @ '\'') => value[1..].find(q).map(|i| i + 2).unwrap_or(value.len()),
+                _ => value.find(char::is_whitespace).unwrap_or(value.len()),
+            };
+            out.push('=');
+            out.push_str(&value[..len].yellow().to_string());
+            rest = &value[len..];
+        }
+    }
+    out
+}
+
+// 上一个输出的内容，决定下一个内容是否换行
+#[derive(PartialEq)]
+enum Last {
+    Start,
+    // 紧跟在开始标签后面的文本，结束标签写在同一行
+    InlineText,
+    Other,
+}
+
+/// --format-html 时重新缩进并高亮 HTML，每个标签一行，只包含文本的元素写在一行
+/// 省略了结束标签的 li、p、td 等元素在遇到下一个同样的元素时结束，
+/// script、style、pre 和 textarea 的内容原样保留
+pub fn indent(s: &str) -> String {
+    let mut out = String::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut last = Last::Other;
+    for token in tokenize(s) {
+        let inline = match token {
+            // 只有刚打开的元素的结束标签写在同一行
+            Token::Tag { ref name, end: true, .. } => {
+                last != Last::Other && stack.last() == Some(name)
+            }
+            Token::Text(_) => last == Last::Start,
+            _ => false,
+        };
+        let (text, raw) = match token {
+            Token::Text(text) => {
+                let raw = last == Last::Start
+                    && stack.last().is_some_and(|name| RAW.contains(&name.as_str()));
+                let text = if raw { text } else { text.trim() };
+                if text.is_empty() {
+                    continue;
+                }
+                (text, raw)
+            }
+            _ => ("", false),
+        };
+        match token {
+            Token::Tag { ref name, end: false, .. } if implicitly_closes(stack.last(), name) => {
+                stack.pop();
+            }
+            Token::Tag { ref name, end: true, .. } => {
+                // 没有对应开始标签的结束标签不改变缩进
+                if let Some(i) = stack.iter().rposition(|open| open == name) {
+                    stack.truncate(i);
+                }
+            }
+            _ => {}
+        }
+        if !inline {
+            out.push('\n');
+            out.push_str(&" ".repeat(stack.len() * INDENT));
+        }
+        last = Last::Other;
+        match token {
+            Token::Tag { ref name, end: false, raw: tag } => {
+                out.push_str(&paint(&token));
+                if !VOID.contains(&name.as_str()) && !tag.ends_with("/>") {
+                    stack.push(name.clone());
+                    last = Last::Start;
+                }
+            }
+            Token::Text(_) => {
+                out.push_str(text);
+                if inline || raw {
+                    last = Last::InlineText;
+                }
+            }
+            _ => out.push_str(&paint(&token)),
+        }
+    }
+    out.trim_start_matches('\n').to_string()
+}
+
+/// 新的开始标签是否结束了没有写结束标签的上一个元素，例如 <li>a<li>b
+fn implicitly_closes(open: Option<&String>, name: &str) -> bool {
+    let open = match open {
+        Some(open) => open.as_str(),
+        None => return false,
+    };
+    match name {
+        "li" | "p" | "option" | "tr" => open == name,
+        "td" | "th" => open == "td" || open == "th",
+        "dt" | "dd" => open == "dt" || open == "dd",
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_works() {
+        let tokens = tokenize(r#"<a href="x>y">1 < 2</a><script>if (a<b) {}</script>书<b>"#);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Tag {
+                    raw: r#"<a href="x>y">"#,
+                    name: "a".into(),
+                    end: false
+                },
+                Token::Text("1 "),
+                Token::Text("< 2"),
+                Token::Tag {
+                    raw: "</a>",
+                    name: "a".into(),
+                    end: true
+                },
+                Token::Tag {
+                    raw: "<script>",
+                    name: "script".into(),
+                    end: false
+                },
+                Token::Text("if (a<b) {}"),
+                Token::Tag {
+                    raw: "</script>",
+                    name: "script".into(),
+                    end: true
+                },
+                Token::Text("书"),
+                Token::Tag {
+                    raw: "<b>",
+                    name: "b".into(),
+                    end: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn indent_works() {
+        colored::control::set_override(false);
+        let html = "<!DOCTYPE html><html><head><meta charset=utf-8><title>T</title></head>\
+                    <body><!-- list --><ul><li>a<li>书</ul><p class='x'>text<br>more</p>\
+                    <pre>  keep\n  this</pre></body></html>";
+        let expected = "<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset=utf-8>
+    <title>T</title>
+  </head>
+  <body>
+    <!-- list -->
+    <ul>
+      <li>a
+      <li>书
+    </ul>
+    <p class='x'>text
+      <br>
+      more
+    </p>
+    <pre>  keep\n  this</pre>
+  </body>
+</html>";
+        assert_eq!(indent(html), expected);
+        assert_eq!(highlight(html), html);
+    }
+}
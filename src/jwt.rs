@@ -0,0 +1,144 @@
+use chrono::{TimeZone, Utc};
+use colored::*;
+use serde_json::Value;
+
+/// 解码 JWT，返回 header 和 claims，不是 JWT 时返回 None（不校验签名）
+pub fn decode(token: &str) -> Option<(Value, Value)> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let header = decode_part(parts[0])?;
+    // header 中必须有 alg，避免把普通的 a.b.c 字符串当成 JWT
+    header.get("alg")?;
+    let claims = decode_part(parts[1])?;
+    Some((header, claims))
+}
+
+fn decode_part(part: &str) -> Option<Value> {
+    let data = base64::decode_config(part.trim_end_matches('='), base64::URL_SAFE_NO_PAD).ok()?;
+    match serde_json::from_slice(&data).ok()? {
+        value @ Value::Object(_) => Some(value),
+        _ => None,
+    }
+}
+
+/// 在 JSON 中查找所有是 JWT 的字符串，返回它们的路径（例如 data.tokens[0]）和值
+pub fn find_in_json(value: &Value) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    walk(value, String::new(), &mut found);
+    found
+}
+
+fn walk(value: &Value, path: String, found: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(s) if decode(s).is_some() => found.push((path, s.clone())),
+        Value::Array(arr) => {
+            for (i, v) in arr.iter().enumerate() {
+                walk(v, format!("{}[{}]", path, i), found);
+            }
+        }
+        Value::Object(map) => {
+            for (k, v) in map.iter() {
+                let path = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                walk(v, path, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 在响应的 body 中查找 JWT，body 可以是 JSON 或者 JWT 本身（application/jwt）
+pub fn find_in_body(body: &str) -> Vec<(String, String)> {
+    if decode(body).is_some() {
+        return vec![("body".into(), body.trim().to_string())];
+    }
+    match serde_json::from_str(body) {
+        Ok(value) => find_in_json(&value),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// 打印解码后的 JWT，过期时间 exp 用红色（已过期）或者绿色（未过期）高亮
+pub fn print(source: &str, token: &str) {
+    let (header, claims) = match decode(token) {
+        Some(decoded) => decoded,
+        None => return,
+    };
+    println!("{}", format!("JWT in {}:", source).magenta());
+    println!("{}", format!("{:#}", header).cyan());
+    println!("{}", format!("{:#}", claims).cyan());
+    if let Some(exp) = claims.get("exp").and_then(|exp| exp.as_i64()) {
+        println!("{}\n", describe_exp(exp, Utc::now().timestamp()));
+    } else {
+        println!();
+    }
+}
+
+/// 生成 exp 的说明
+fn describe_exp(exp: i64, now: i64) -> ColoredString {
+    let time = match Utc.timestamp_opt(exp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => exp.to_string(),
+    };
+    if exp <= now {
+        format!("exp: {} (expired {} ago)", time, duration(now - exp)).red()
+    } else {
+        format!("exp: {} (expires in {})", time, duration(exp - now)).green()
+    }
+}
+
+fn duration(secs: i64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m{}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h{}m", s / 3600, s % 3600 / 60),
+        s => format!("{}d{}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // jwt.io 的示例: {"alg":"HS256","typ":"JWT"} / {"sub":"1234567890","name":"John Doe","iat":1516239022}
+    const TOKEN: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+        eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+        SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+
+    #[test]
+    fn decode_works() {
+        let (header, claims) = decode(TOKEN).unwrap();
+        assert_eq!(header["alg"], "HS256");
+        assert_eq!(claims["name"], "John Doe");
+        assert!(decode("a.b.c").is_none());
+        assert!(decode("hello").is_none());
+    }
+
+    #[test]
+    fn find_in_body_works() {
+        let body = json!({"data": {"tokens": ["x", TOKEN]}, "access_token": TOKEN}).to_string();
+        let found: Vec<String> = find_in_body(&body).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(found, vec!["access_token", "data.tokens[1]"]);
+        assert_eq!(find_in_body(TOKEN)[0].0, "body");
+        assert!(find_in_body("<html>").is_empty());
+    }
+
+    #[test]
+    fn describe_exp_works() {
+        colored::control::set_override(false);
+        assert_eq!(
+            describe_exp(1516239022, 1516239022 + 7200).to_string(),
+            "exp: 2018-01-18 01:30:22 UTC (expired 2h0m ago)"
+        );
+        assert_eq!(
+            describe_exp(1516239022, 1516239022 - 90).to_string(),
+            "exp: 2018-01-18 01:30:22 UTC (expires in 1m30s)"
+        );
+    }
+}
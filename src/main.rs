@@ -1,9 +1,61 @@
-use std::{str::FromStr, collections::HashMap};
-use clap::{AppSettings, Clap};
-use anyhow::{anyhow, Result};
-use reqwest::{Url, header, Client, Response};
+mod altsvc;
+mod auth;
+mod body;
+mod charset;
+mod client;
+mod compress;
+mod dns;
+mod html;
+mod download;
+mod filter;
+mod jwt;
+mod print;
+mod proxy;
+mod quiet;
+mod redact;
+mod redirect;
+mod retry;
+mod select;
+mod sse;
+mod throttle;
+mod tls;
+mod trailers;
+mod unix_socket;
+mod upload;
+mod ws;
+mod xml;
+mod xpath;
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::{io::Read, io::Write, str::FromStr};
+use clap::{AppSettings, ArgEnum, Clap};
+use anyhow::{anyhow, Context as _, Result};
+use reqwest::{Url, header, Client, Method, RequestBuilder, Response, StatusCode};
+use reqwest::header::{HeaderMap, HeaderName};
+use reqwest::multipart;
+use tokio_util::io::ReaderStream;
 use colored::*;
 use mime::Mime;
+use serde_json::{Map, Value};
+use altsvc::AltSvc;
+use auth::{Auth, AuthArgs, HmacSigner};
+use client::{format_duration, ClientArgs, Progress};
+use dns::DnsArgs;
+use download::{Bars, DownloadArgs};
+use filter::Filter;
+use futures::StreamExt;
+use print::Print;
+use proxy::ProxyArgs;
+use redirect::RedirectArgs;
+use retry::RetryArgs;
+use select::Select;
+use tls::TlsArgs;
+use trailers::Trailers;
+use redact::Redactor;
+use upload::Upload;
+use xpath::XPath;
 
 
 /// A naive httpie implementation with Rust, can you imagine how easy it is?
@@ -13,37 +65,349 @@ use mime::Mime;
 struct Opts { 
     #[clap(subcommand)]
     subcmd: SubCommand,
+    /// Do not read the request body from stdin even if it is piped
+    #[clap(long, global = true)]
+    ignore_stdin: bool,
+    /// Send this string as the request body instead of building it from key=value pairs
+    #[clap(long, global = true)]
+    raw: Option<String>,
+    /// Override the Content-Type of the request body
+    #[clap(long, global = true)]
+    content_type: Option<String>,
+    /// Send the content of this file as the request body, same as an @path item
+    #[clap(long, global = true)]
+    body_file: Option<String>,
+    /// Stream stdin as the request body with chunked transfer encoding as data arrives,
+    /// instead of reading all of it first
+    #[clap(long, global = true, conflicts_with_all = &["ignore-stdin", "raw", "body-file"])]
+    stream_body: bool,
+    /// Compress the request body with gzip and send it with Content-Encoding: gzip
+    #[clap(long, global = true)]
+    compress: bool,
+    #[clap(flatten)]
+    auth: AuthArgs,
+    #[clap(flatten)]
+    client: ClientArgs,
+    #[clap(flatten)]
+    proxy: ProxyArgs,
+    #[clap(flatten)]
+    tls: TlsArgs,
+    #[clap(flatten)]
+    dns: DnsArgs,
+    #[clap(flatten)]
+    download: DownloadArgs,
+    #[clap(flatten)]
+    redirect: RedirectArgs,
+    #[clap(flatten)]
+    retry: RetryArgs,
+    /// Convert the --body-file (YAML, TOML or JSON, detected by extension) before sending
+    #[clap(long, global = true, arg_enum, requires = "body-file")]
+    convert: Option<ConvertTo>,
+    /// Decode and print JWTs found in the Authorization: Bearer header and the response body
+    #[clap(long, global = true)]
+    decode_jwt: bool,
+    /// Hide the value of this header when printing headers, Authorization,
+    /// Proxy-Authorization and Cookie are always hidden
+    #[clap(long, global = true, multiple_occurrences = true, number_of_values = 1)]
+    redact: Vec<String>,
+    /// Show the bytes of header values that are not ASCII as \xNN escapes, instead of
+    /// decoding them as UTF-8 (or ISO-8859-1 when they are not valid UTF-8)
+    #[clap(long, global = true)]
+    show_raw_headers: bool,
+    /// What to print: H request headers, B request body, h response status and headers,
+    /// b response body, e.g. --print=Hh (default hb)
+    #[clap(short, long, global = true, value_name = "WHAT")]
+    print: Option<Print>,
+    /// Print the request line, headers and body before the response, same as --print=HBhb
+    #[clap(short, long, global = true, conflicts_with = "print")]
+    verbose: bool,
+    /// Build the request and print it (same as --print=HB by default) without sending it.
+    /// Signatures and authentication are included (secrets are redacted), except digest, ntlm
+    /// and oauth2 which need to talk to the server
+    #[clap(long, global = true)]
+    offline: bool,
+    /// Print nothing but errors and exit with 3, 4 or 5 for 3xx, 4xx or 5xx responses.
+    /// Warnings are still printed, -qq hides them too
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    quiet: u64,
+    /// Print the response body line by line as it arrives, like tail -f, formatting each
+    /// JSON line on its own instead of waiting for the whole body
+    #[clap(short = 'S', long, global = true)]
+    stream: bool,
+    /// Print the response body as an offset/hex/ASCII dump like hexdump -C
+    #[clap(long, global = true)]
+    hex: bool,
+    /// Dump at most this many bytes of the body with --hex, 0 for no limit
+    #[clap(long, global = true, default_value = "4096", value_name = "BYTES")]
+    hex_limit: u64,
+    /// How to process the output: "format" indents JSON, "colors" highlights it, "all"
+    /// does both (default, colors only on a terminal), "none" prints the body as received.
+    /// An explicit "colors" or "all" keeps the colors when the output is redirected
+    #[clap(long, global = true, arg_enum, value_name = "STYLE")]
+    pretty: Option<Pretty>,
+    /// Re-indent HTML bodies with one tag per line. Whitespace in the document may change,
+    /// the contents of script, style, pre and textarea are kept as they are
+    #[clap(long, global = true)]
+    format_html: bool,
+    /// Print only the text of the elements of an HTML body matching this CSS selector, one
+    /// per line, e.g. 'table.prices td.amount'. End it with ::attr(name) to print an
+    /// attribute instead, e.g. 'a.next::attr(href)'
+    #[clap(long, global = true, value_name = "SELECTOR")]
+    select: Option<Select>,
+    /// Print only the parts of a JSON body selected by this jq-like path, one value per line,
+    /// e.g. '.items[].name', '.data[0]' or '."a key"'. JSONPath such as '$.items[*].name'
+    /// works too
+    #[clap(long, global = true, value_name = "PATH")]
+    filter: Option<Filter>,
+    /// Print only the nodes of an XML body selected by this XPath 1.0 expression, one per
+    /// line, e.g. '//order/@id' or 'count(//item)'. Prefixes declared in the document can be
+    /// used as they are, elements in a default namespace need local-name(), e.g.
+    /// '//*[local-name()='item']'
+    #[clap(long, global = true, value_name = "EXPR")]
+    xpath: Option<XPath>,
+}
+
+// --pretty，是否格式化和高亮输出
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+enum Pretty {
+    All,
+    Colors,
+    Format,
+    None,
+}
+
+impl Pretty {
+    fn format(self) -> bool {
+        matches!(self, Pretty::All | Pretty::Format)
+    }
+
+    fn colors(self) -> bool {
+        matches!(self, Pretty::All | Pretty::Colors)
+    }
 }
 
-// 子命令分别对应不同的HTTP方法，目前只支持get / post
+// --convert 支持的目标格式
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+enum ConvertTo {
+    Json,
+}
+
+// 子命令分别对应不同的HTTP方法，目前支持get / post / put / patch / delete / head / options
+// 其它方法（如 PURGE / REPORT / LINK）可以通过 request 子命令发送，sse 订阅 Server-Sent Events，
+// ws 连接 WebSocket
 #[derive(Clap, Debug)]
 enum SubCommand {
     Get(Get),
     Post(Post),
+    Put(Put),
+    Patch(Patch),
+    Delete(Delete),
+    Head(Head),
+    Options(Options),
+    Request(Request),
+    Sse(Sse),
+    Ws(Ws),
+}
+
+impl SubCommand {
+    /// 子命令请求的URL
+    fn url(&self) -> &str {
+        match self {
+            SubCommand::Get(args) => &args.url,
+            SubCommand::Post(args) => &args.url,
+            SubCommand::Put(args) => &args.url,
+            SubCommand::Patch(args) => &args.url,
+            SubCommand::Delete(args) => &args.url,
+            SubCommand::Head(args) => &args.url,
+            SubCommand::Options(args) => &args.url,
+            SubCommand::Request(args) => &args.url,
+            SubCommand::Sse(args) => &args.url,
+            SubCommand::Ws(args) => &args.url,
+        }
+    }
+
+    /// 子命令的请求参数
+    fn items_mut(&mut self) -> &mut Vec<RequestItem> {
+        match self {
+            SubCommand::Get(args) => &mut args.items,
+            SubCommand::Post(args) => &mut args.items,
+            SubCommand::Put(args) => &mut args.items,
+            SubCommand::Patch(args) => &mut args.items,
+            SubCommand::Delete(args) => &mut args.items,
+            SubCommand::Head(args) => &mut args.items,
+            SubCommand::Options(args) => &mut args.items,
+            SubCommand::Request(args) => &mut args.items,
+            SubCommand::Sse(args) => &mut args.items,
+            SubCommand::Ws(args) => &mut args.items,
+        }
+    }
 }
 
 // get 子命令
-/// feed get with an url and we will retrieve the response for you
+/// feed get with an url and optional key:value headers and we will retrieve
+/// the response for you
 #[derive(Clap, Debug)]
 struct Get {
     // HTTP 请求的URL
     #[clap(parse(try_from_str = parse_url))]
     url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
 }
 
 // post 子命令。需要输入一个URL，和若干个可选的key=value，用于提供json body
+// 以及若干个可选的key:value，用于提供header
 /// feed post with an url and optional key=value pairs. We will post the data
-/// as JSON, and retrieve the response for you
+/// as JSON (or as a form with --form), and retrieve the response for you.
+/// Use key:value to set headers
 #[derive(Clap, Debug)]
 struct Post {
     // HTTP 请求的URL
     #[clap(parse(try_from_str = parse_url))]
     url: String,
-    // HTTP请求的body
-    #[clap(parse(try_from_str = parse_kv_pair))]
-    body: Vec<KvPair>,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+    /// Send key=value pairs as application/x-www-form-urlencoded instead of JSON
+    #[clap(short, long)]
+    form: bool,
+}
+
+// put 子命令。参数与 post 相同
+/// feed put with an url and optional key=value pairs. We will put the data
+/// as JSON, and retrieve the response for you
+#[derive(Clap, Debug)]
+struct Put {
+    // HTTP 请求的URL
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+}
+
+// patch 子命令。参数与 post 相同
+/// feed patch with an url and optional key=value pairs. We will patch the data
+/// as JSON, and retrieve the response for you
+#[derive(Clap, Debug)]
+struct Patch {
+    // HTTP 请求的URL
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+}
+
+// delete 子命令。有些API要求在 DELETE 时携带JSON body，因此 key=value 是可选的
+/// feed delete with an url and optional key=value pairs. If any pairs are given
+/// we will send them as JSON, and retrieve the response for you
+#[derive(Clap, Debug)]
+struct Delete {
+    // HTTP 请求的URL
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+}
+
+// head 子命令
+/// feed head with an url and we will show the status and headers for you,
+/// without downloading the body
+#[derive(Clap, Debug)]
+struct Head {
+    // HTTP 请求的URL
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+}
+
+// options 子命令。可以通过 --preflight 模拟浏览器的 CORS 预检请求
+/// feed options with an url and we will show which methods and CORS headers
+/// the server allows. Use --preflight origin=... method=... headers=... to send
+/// a CORS preflight request
+#[derive(Clap, Debug)]
+struct Options {
+    // HTTP 请求的URL
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+    // CORS 预检参数，支持 origin / method / headers
+    #[clap(
+        long,
+        multiple_occurrences = true,
+        number_of_values = 1,
+        parse(try_from_str = parse_kv_pair)
+    )]
+    preflight: Vec<KvPair>,
+}
+
+// request 子命令。需要输入任意的HTTP方法和URL，以及可选的key=value
+/// feed request with any HTTP method, an url and optional key=value pairs.
+/// If any pairs are given we will send them as JSON, and retrieve the response
+/// for you
+#[derive(Clap, Debug)]
+struct Request {
+    // HTTP 方法，例如 PURGE
+    #[clap(parse(try_from_str = parse_method))]
+    method: Method,
+    // HTTP 请求的URL
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+}
+
+// sse 子命令。保持连接并打印收到的事件，连接断开时带上 Last-Event-ID 重新连接
+/// feed sse with an url and we will subscribe to its Server-Sent Events, print
+/// each event (JSON data is pretty printed) and reconnect with Last-Event-ID
+/// when the connection drops
+#[derive(Clap, Debug)]
+struct Sse {
+    // HTTP 请求的URL
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+    /// Stop when the server closes the connection instead of reconnecting
+    #[clap(long)]
+    no_reconnect: bool,
 }
 
+// ws 子命令。key:value 和 key==value 用于握手请求，收到的消息带着时间打印
+/// feed ws with a ws:// or wss:// url and optional key:value headers. We will
+/// print incoming messages with timestamps, and send each --send message and
+/// each line of stdin as a text message. The handshake uses -a/--auth, --sign,
+/// --resolve, --connect-to, -4/-6 and --sni like other requests; proxies,
+/// --unix-socket and digest / ntlm / oauth2 auth are not supported
+#[derive(Clap, Debug)]
+struct Ws {
+    // WebSocket 的URL，http(s):// 当作 ws(s)://
+    #[clap(parse(try_from_str = parse_url))]
+    url: String,
+    // HTTP请求参数，格式见 RequestItem
+    #[clap(parse(try_from_str = parse_request_item))]
+    items: Vec<RequestItem>,
+    /// Send this text message after connecting, can be repeated
+    #[clap(long, multiple_occurrences = true, number_of_values = 1)]
+    send: Vec<String>,
+    // 是否发送 stdin 的每一行，--ignore-stdin 时只接收
+    #[clap(skip)]
+    stdin: bool,
+}
+
+/// 解析HTTP方法，统一转换成大写
+fn parse_method(s: &str) -> Result<Method> {
+    Ok(Method::from_bytes(s.to_uppercase().as_bytes())?)
+}
 
 fn parse_url(s: &str) -> Result<String> {
     // 这里我们仅仅检查一下URL是否合法
@@ -63,96 +427,1561 @@ impl FromStr for KvPair {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // 使用 = 进行 split，这会得到一个迭代器
-        let mut split = s.split("=");
         let err = || anyhow!(format!("Failed to parse {}", s));
+        // 只在第一个没有被转义的 = 处分割，这样 value 中可以包含 =
+        let pos = find_unescaped(s, &['=']).ok_or_else(err)?;
         Ok(Self {
-            // 从迭代器中取第一个结果作为key，迭代器Some(T)/None
-            // 我们将其转换成 Ok(T) // Err(E), 然后用 ? 处理错误
-            k: (split.next().ok_or_else(err)?).to_string(),
-            // 从迭代器中取第二个结果作为value
-            v: (split.next().ok_or_else(err)?).to_string(),
+            k: unescape(&s[..pos]),
+            v: unescape(&s[pos + 1..]),
         })
     }
 }
 
+/// 查找第一个没有被 \ 转义的分隔符的位置
+fn find_unescaped(s: &str, seps: &[char]) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if seps.contains(&c) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// 去掉分隔符前面的转义符，例如 a\=b 会变成 a=b，其它的 \ 保持不变
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && ESCAPABLE.contains(&next) => {
+                out.push(next);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// 可以用 \ 转义的字符
+const ESCAPABLE: &[char] = &['=', ':', '@', '\\'];
+
 /// 因为我们为KvPair实现了FromStr, 这里可以直接s.parse() 得到KvPair
 fn parse_kv_pair(s: &str) -> Result<KvPair> {
-    Ok(s.parse()?)
+    s.parse()
+}
+
+/// 命令行中的请求参数，根据分隔符区分是header、query还是body
+#[derive(Debug, PartialEq)]
+enum RequestItem {
+    // key:value，作为请求的header
+    Header(KvPair),
+    // key==value，作为URL的query参数
+    Query(KvPair),
+    // key=value，作为JSON body的字符串字段，重复的key会组成数组
+    Data(KvPair),
+    // key:=value，value 作为原始JSON，可以是数字、布尔值、数组或对象
+    Json(String, Value),
+    // field@path，上传文件，body 会以 multipart/form-data 的形式发送
+    File(FileItem),
+    // @path，直接以文件内容作为body，同样可以用 @path;type=... 指定mime类型
+    BodyFile(FileItem),
+    // 直接作为body的数据，来自 --raw 或通过管道输入的 stdin
+    Raw(Vec<u8>),
+    // --stream-body，边读取 stdin 边以 chunked 编码发送
+    Stdin,
+    // 另一个 http:// 或 https:// 开头的URL，--download 时和第一个URL一起下载
+    Url(Url),
+}
+
+/// field@path 形式的文件字段，可以用 field@path;type=image/png 指定mime类型
+/// 作为整个body的 @path 没有 field
+#[derive(Debug, PartialEq)]
+struct FileItem {
+    field: String,
+    path: String,
+    mime: Option<String>,
+}
+
+impl FileItem {
+    /// 生成 multipart 中的一个字段，没有指定mime类型时根据扩展名猜测
+    /// 和 to_body 一样，文件以流的形式发送
+    fn to_part(&self, upload: &Upload) -> Result<multipart::Part> {
+        let (stream, len) = self.open()?;
+        let file_name = std::path::Path::new(&self.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(multipart::Part::stream_with_length(upload.file(stream, len), len)
+            .file_name(file_name)
+            .mime_str(&self.mime_type())?)
+    }
+
+    /// 以文件内容作为请求的body，文件以流的形式发送，不会一次性读入内存
+    fn to_body(&self, req: RequestBuilder, upload: &Upload) -> Result<RequestBuilder> {
+        let (stream, len) = self.open()?;
+        Ok(upload.body(req, stream, Some(len)))
+    }
+
+    /// 打开文件，返回读取文件内容的流和文件的长度
+    fn open(&self) -> Result<(ReaderStream<tokio::fs::File>, u64)> {
+        let file = std::fs::File::open(&self.path)
+            .with_context(|| format!("Failed to read file {}", self.path))?;
+        let len = file.metadata()?.len();
+        Ok((ReaderStream::new(tokio::fs::File::from_std(file)), len))
+    }
+
+    /// 指定的mime类型，没有指定时根据扩展名猜测
+    fn mime_type(&self) -> String {
+        match self.mime {
+            Some(ref m) => m.clone(),
+            None => mime_guess::from_path(&self.path)
+                .first_or_octet_stream()
+                .to_string(),
+        }
+    }
+}
+
+impl FromStr for RequestItem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!(format!("Failed to parse {}", s));
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(Self::Url(s.parse()?));
+        }
+        // 最先出现的分隔符决定了参数的类型，这样 a=b:c 中的 : 属于value
+        // 被 \ 转义的分隔符属于key或value本身
+        let pos = find_unescaped(s, &[':', '=', '@']).ok_or_else(err)?;
+        if pos == 0 && !s.starts_with('@') {
+            return Err(err());
+        }
+        // 同一位置上优先匹配较长的分隔符，例如 == 而不是 =
+        let rest = &s[pos..];
+        let k = unescape(&s[..pos]);
+        if let Some(v) = rest.strip_prefix(":=") {
+            let v = serde_json::from_str(v)
+                .map_err(|e| anyhow!("Failed to parse {}, invalid JSON: {}", s, e))?;
+            return Ok(Self::Json(k, v));
+        }
+        if let Some(v) = rest.strip_prefix('@') {
+            let v = unescape(v);
+            let (path, mime) = match v.rfind(";type=") {
+                Some(i) => (v[..i].to_string(), Some(v[i + 6..].to_string())),
+                None => (v, None),
+            };
+            let file = FileItem {
+                field: k,
+                path,
+                mime,
+            };
+            if file.field.is_empty() {
+                return Ok(Self::BodyFile(file));
+            }
+            return Ok(Self::File(file));
+        }
+        let (sep, kind): (&str, fn(KvPair) -> Self) = if rest.starts_with("==") {
+            ("==", Self::Query)
+        } else if rest.starts_with(':') {
+            (":", Self::Header)
+        } else {
+            ("=", Self::Data)
+        };
+        Ok(kind(KvPair {
+            k,
+            v: unescape(&rest[sep.len()..]),
+        }))
+    }
+}
+
+/// 将命令行参数解析成RequestItem
+fn parse_request_item(s: &str) -> Result<RequestItem> {
+    s.parse()
+}
+
+/// 所有子命令共享的上下文，包括HTTP客户端和全局的请求设置
+struct Context {
+    client: Client,
+    // -a/--auth 和 --auth-type 指定的认证方式
+    auth: Option<Auth>,
+    // --sign 指定的请求签名方式
+    signer: Option<HmacSigner>,
+    // --decode-jwt，解码并打印请求和响应中的 JWT
+    decode_jwt: bool,
+    // 打印 header 时隐藏敏感的值
+    redactor: Redactor,
+    // 是否跟随重定向，以及是否打印中间的响应
+    redirect: RedirectArgs,
+    // 超时等创建客户端时的参数
+    client_args: ClientArgs,
+    // 已经接收的数据量
+    progress: Progress,
+    // 重试的次数和间隔
+    retry: RetryArgs,
+    // TLS 参数，--show-cert / --check-revocation 时打印服务器的证书链和吊销状态
+    tls: TlsArgs,
+    // --alt-svc 缓存的 HTTP/3 服务
+    alt_svc: Option<AltSvc>,
+    // --connect-to 等域名解析参数
+    dns: DnsArgs,
+    // -d/--download，把 body 保存到文件
+    download: DownloadArgs,
+    // --limit-rate 时限速上传
+    upload: Upload,
+    // --print 选择打印的部分
+    print: Print,
+    // --offline，只打印请求，不发送
+    offline: bool,
+    // 是否直接连接服务器，经过代理或者 --unix-socket 时不进行 --happy-eyeballs-timeout 的连接竞争
+    direct: bool,
+    // --interface / --local-address 的源地址
+    source: Option<IpAddr>,
+    // 最后一个响应的状态码，-q 时作为退出码
+    status: AtomicU16,
+    // --stream，按行打印响应的 body
+    stream: bool,
+    // --hex 时最多显示的字节数
+    hex: Option<u64>,
+    // --pretty，颜色在 main 中统一打开或关闭
+    pretty: Pretty,
+    // --format-html，重新缩进 HTML
+    format_html: bool,
+    // --select，只打印 HTML 中匹配的元素
+    select: Option<Select>,
+    // --filter，只打印 JSON 中选中的部分
+    filter: Option<Filter>,
+    // --xpath，只打印 XML 中选中的节点
+    xpath: Option<XPath>,
+}
+
+impl Context {
+    /// 发送请求，按照 --follow / --max-redirects 处理重定向，--all 时打印中间的每一个响应
+    async fn execute(&self, req: RequestBuilder) -> Result<Response> {
+        if self.decode_jwt {
+            self.print_request_jwt(&req);
+        }
+        // 保留一份请求，用于生成重定向后的请求
+        let mut prev = req.try_clone().and_then(|req| req.build().ok());
+        let origin = prev.as_ref().map(|req| req.url().clone());
+        let mut resp = self.send_with_retry(req, true, self.print.request()).await?;
+        let mut redirects = 0;
+        while self.redirect.follow() {
+            let (url, request) = match (redirect::location(&resp), prev.take()) {
+                (Some(url), Some(request)) => (url, request),
+                _ => break,
+            };
+            if redirects >= self.redirect.max_redirects {
+                return Err(anyhow!(
+                    "Too many redirects (--max-redirects {}), last Location: {}",
+                    self.redirect.max_redirects,
+                    url
+                ));
+            }
+            // 重定向到其它网站时不再发送认证信息，除非指定了 --unsafe-redirect-auth
+            let with_auth = self.redirect.unsafe_redirect_auth
+                || matches!(origin, Some(ref origin) if redirect::same_origin(origin, &url));
+            let next = redirect::next_request(
+                &self.client,
+                &request,
+                resp.status(),
+                url,
+                !with_auth,
+                self.redirect.redirect_method,
+            );
+            let next = match next {
+                Some(next) => next,
+                None => break,
+            };
+            if self.redirect.all {
+                print_resp(self, resp).await?;
+            }
+            prev = next.try_clone().and_then(|req| req.build().ok());
+            resp = self.send_with_retry(next, with_auth, false).await?;
+            redirects += 1;
+        }
+        self.status.store(resp.status().as_u16(), Ordering::Relaxed);
+        Ok(resp)
+    }
+
+    /// 发送请求，失败时按照 --retry、--retry-on 和 --retry-methods 重试，print 为 true 时打印第一次发送的请求
+    async fn send_with_retry(
+        &self,
+        mut req: RequestBuilder,
+        with_auth: bool,
+        print: bool,
+    ) -> Result<Response> {
+        let method = req
+            .try_clone()
+            .and_then(|req| req.build().ok())
+            .map(|req| req.method().clone());
+        let mut attempt = 0;
+        loop {
+            // 流式的body无法重新发送，这种情况下不重试
+            let retry = if attempt < self.retry.retry {
+                req.try_clone()
+            } else {
+                None
+            };
+            let result = self.send_once(req, with_auth, print && attempt == 0).await;
+            req = match (retry, method.as_ref()) {
+                (Some(retry), Some(method)) if self.retry.should_retry(method, &result) => retry,
+                _ => return result,
+            };
+            let delay = self.retry.delay(attempt, &result);
+            attempt += 1;
+            quiet::verbose(format!(
+                "http: {}, retrying in {} ({}/{})",
+                retry::describe(&result),
+                format_duration(delay),
+                attempt,
+                self.retry.retry
+            ));
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// 发送之前对请求做的所有改写：HTTP 版本、-4/-6、--connect-to、--host-header 和 --sni，
+    /// --alt-svc，签名，不需要和服务器交互的认证（with_auth 为 true 时），以及 Expect header
+    /// 返回的请求就是将要发送的请求，body 还没有转换成限速的流
+    /// 请求的主机在 --alt-svc 缓存中时同时返回这个主机
+    async fn prepare(&self, req: RequestBuilder, with_auth: bool) -> Result<(RequestBuilder, Option<Url>)> {
+        let req = self.client_args.set_version(req)?;
+        let req = self.dns.route(req)?;
+        // --offline 时不连接服务器
+        let req = match self.direct && !self.offline {
+            true => self.dns.happy_eyeballs(req, self.source).await?,
+            false => req,
+        };
+        let (req, origin) = match &self.alt_svc {
+            Some(alt_svc) if !self.client_args.forces_version() => alt_svc.route(req)?,
+            _ => (req, None),
+        };
+        // 签名需要完整的 body，所以先签名，发送时才把内存中的 body 转换成限速的流
+        let req = match self.signer {
+            Some(ref signer) => signer.apply(req)?,
+            None => req,
+        };
+        let req = match self.auth {
+            Some(ref auth) if with_auth => auth.sign(req)?,
+            _ => req,
+        };
+        // 服务器知道客户端接受 trailer 时才会发送可选的 trailer
+        let req = match self.client_args.trailers() {
+            true => req.header(header::TE, "trailers"),
+            false => req,
+        };
+        Ok((self.upload.expect(req)?, origin))
+    }
+
+    /// ws 子命令的握手请求：和其它请求一样改写主机、签名和加上认证，握手不经过 HTTP 客户端，
+    /// 所以不支持代理、--unix-socket 和需要和服务器交互的认证，这时直接报错
+    fn prepare_ws(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        if !self.direct {
+            return Err(anyhow!(
+                "ws cannot connect through a proxy (--proxy, HTTP_PROXY / HTTPS_PROXY / ALL_PROXY) \
+                 or --unix-socket"
+            ));
+        }
+        if let Some(auth_type) = self.auth.as_ref().and_then(Auth::exchange_type) {
+            return Err(anyhow!("--auth-type {} is not supported by ws", auth_type));
+        }
+        let req = self.dns.route(req)?;
+        let req = match self.signer {
+            Some(ref signer) => signer.apply(req)?,
+            None => req,
+        };
+        match self.auth {
+            Some(ref auth) => auth.sign(req),
+            None => Ok(req),
+        }
+    }
+
+    /// --offline 时打印和发送时一样改写过的请求，不发送
+    async fn print_offline(&self, req: RequestBuilder) -> Result<()> {
+        let (req, _) = self.prepare(req, true).await?;
+        print_request(self, req).map(drop)
+    }
+
+    /// 改写请求后发送一次，print 为 true 时打印改写之后的请求
+    /// 请求的主机在 --alt-svc 缓存中时通过 HTTP/3 发送，并用响应的 Alt-Svc 更新缓存
+    async fn send_once(&self, req: RequestBuilder, with_auth: bool, print: bool) -> Result<Response> {
+        let (req, origin) = self.prepare(req, with_auth).await?;
+        let req = match print {
+            true => print_request(self, req)?,
+            false => req,
+        };
+        let client = &self.client;
+        let upload = &self.upload;
+        let fut = async move {
+            match self.auth {
+                _ if self.client_args.trailers() => {
+                    self.client_args.send_trailers(req.build()?, &self.tls, &self.dns).await
+                }
+                Some(ref auth) if with_auth => auth.send(client, req, upload).await,
+                _ => Ok(upload.apply(req)?.send().await?),
+            }
+        };
+        let result = self.client_args.wait_response(fut).await;
+        let alt_svc = match &self.alt_svc {
+            Some(alt_svc) => alt_svc,
+            None => return result,
+        };
+        match (&result, origin) {
+            (Err(_), Some(origin)) => {
+                alt_svc.forget(&origin)?;
+                result.context(format!(
+                    "The HTTP/3 service cached in --alt-svc for {} failed, it was removed",
+                    origin
+                ))
+            }
+            (Ok(resp), None) => {
+                alt_svc.update(resp)?;
+                result
+            }
+            _ => result,
+        }
+    }
+
+    /// 打印请求的 Authorization: Bearer 中的 JWT，包括通过 --auth-type bearer 指定的 token
+    fn print_request_jwt(&self, req: &RequestBuilder) {
+        let header = req
+            .try_clone()
+            .and_then(|req| req.build().ok())
+            .and_then(|req| {
+                let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+                value.strip_prefix("Bearer ").map(|token| token.to_string())
+            });
+        let token = header.or_else(|| {
+            self.auth
+                .as_ref()
+                .and_then(|auth| auth.bearer_token())
+                .map(|token| token.to_string())
+        });
+        if let Some(token) = token {
+            jwt::print("request Authorization header", &token);
+        }
+    }
 }
 
 /// 处理get 子命令
-async fn get(client: Client, args: &Get) -> Result<()> {
-    let resp = client.get(&args.url).send().await?;
-    Ok(print_resp(resp).await?)
+async fn get(ctx: &Context, args: &Get) -> Result<()> {
+    send(ctx, ctx.client.get(&args.url), &args.items, BodyType::OptionalJson).await
 }
 
 /// 处理 post 子命令
-async fn post(client: Client, args: &Post) -> Result<()> {
-    let mut body = HashMap::new();
-    for pair in args.body.iter() {
-        body.insert(&pair.k, &pair.v);
+async fn post(ctx: &Context, args: &Post) -> Result<()> {
+    let body_type = if args.form {
+        BodyType::Form
+    } else {
+        BodyType::Json
+    };
+    send(ctx, ctx.client.post(&args.url), &args.items, body_type).await
+}
+
+/// 处理 put 子命令
+async fn put(ctx: &Context, args: &Put) -> Result<()> {
+    send(ctx, ctx.client.put(&args.url), &args.items, BodyType::Json).await
+}
+
+/// 处理 patch 子命令
+async fn patch(ctx: &Context, args: &Patch) -> Result<()> {
+    send(ctx, ctx.client.patch(&args.url), &args.items, BodyType::Json).await
+}
+
+/// 处理 delete 子命令
+async fn delete(ctx: &Context, args: &Delete) -> Result<()> {
+    send(ctx, ctx.client.delete(&args.url), &args.items, BodyType::OptionalJson).await
+}
+
+/// 处理 request 子命令
+async fn request(ctx: &Context, args: &Request) -> Result<()> {
+    let req = ctx.client.request(args.method.clone(), &args.url);
+    send(ctx, req, &args.items, BodyType::OptionalJson).await
+}
+
+/// 处理 ws 子命令，连接之后发送 --send 和 stdin 中的消息，直到服务器关闭连接
+async fn ws(ctx: &Context, args: &Ws) -> Result<()> {
+    let req = apply_items(ctx.client.get(&args.url), &args.items, BodyType::OptionalJson, &ctx.upload)?;
+    let mut req = ctx.prepare_ws(req)?;
+    if ctx.offline || ctx.print.request() {
+        req = print_request(ctx, req)?;
+    }
+    if ctx.offline {
+        return Ok(());
+    }
+    let req = req.build()?;
+    if req.body().is_some() {
+        return Err(anyhow!("ws does not send a request body, use --send or stdin to send messages"));
+    }
+    let (socket, resp) = ws::connect(req.url(), req.headers().clone(), &ctx.tls, &ctx.dns).await?;
+    if ctx.print.response_headers {
+        let version = format!("{:?}", resp.version()).blue().bold();
+        println!("{} {}\n", version, resp.status().to_string().blue());
+        print_headers(resp.headers(), &ctx.redactor);
     }
-    let resp = client.post(&args.url).json(&body).send().await?;
-    Ok(print_resp(resp).await?)
+    ws::session(socket, &args.send, args.stdin, ctx.print.response_body).await
+}
+
+/// 处理 sse 子命令，连接断开或者读取出错时等待一段时间后重新连接，
+/// 服务器返回 204、错误的状态码或者不是 text/event-stream 时停止
+async fn sse(ctx: &Context, args: &Sse) -> Result<()> {
+    let req = ctx.client.get(&args.url).header(header::ACCEPT, "text/event-stream");
+    let req = apply_items(req, &args.items, BodyType::OptionalJson, &ctx.upload)?;
+    if ctx.offline {
+        return ctx.print_offline(req).await;
+    }
+    let mut parser = sse::Parser::default();
+    let mut connected = false;
+    loop {
+        let mut req = req.try_clone().ok_or_else(|| anyhow!("Failed to clone the request"))?;
+        if let Some(id) = parser.last_event_id() {
+            req = req.header("Last-Event-ID", id);
+        }
+        let result = match ctx.execute(req).await {
+            // 服务器用 204 表示不要再重新连接
+            Ok(resp)
+                if resp.status() == StatusCode::NO_CONTENT
+                    || !resp.status().is_success()
+                    || !sse::is_event_stream(get_content_type(&resp).as_ref()) =>
+            {
+                return print_resp(ctx, resp).await;
+            }
+            Ok(resp) => {
+                // 重新连接时不再打印响应的 header
+                if !connected {
+                    print_head(ctx, &resp).await;
+                    connected = true;
+                }
+                print_events(ctx, resp, &mut parser).await
+            }
+            // 第一次连接失败时直接报错
+            Err(e) if !connected => return Err(e),
+            Err(e) => Err(e),
+        };
+        if args.no_reconnect {
+            return result;
+        }
+        let delay = parser.retry().unwrap_or(sse::DEFAULT_RETRY);
+        let reason = match result {
+            Ok(()) => "Connection closed".to_string(),
+            Err(e) => format!("{:#}", e),
+        };
+        let msg = format!("{}, reconnecting in {}", reason, format_duration(delay));
+        let msg = match parser.last_event_id() {
+            Some(id) => format!("{} with Last-Event-ID: {}", msg, id),
+            None => msg,
+        };
+        quiet::warn(msg.yellow());
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// 处理 head 子命令，只打印状态码和header
+async fn head(ctx: &Context, args: &Head) -> Result<()> {
+    let req = ctx.client.head(&args.url);
+    let req = apply_items(req, &args.items, BodyType::OptionalJson, &ctx.upload)?;
+    if ctx.offline {
+        return ctx.print_offline(req).await;
+    }
+    let resp = ctx.execute(req).await?;
+    print_head(ctx, &resp).await;
+    Ok(())
+}
+
+/// 处理 options 子命令，高亮 Allow 和 CORS 相关的header
+async fn options(ctx: &Context, args: &Options) -> Result<()> {
+    let headers = preflight_headers(&args.preflight)?;
+    let req = ctx.client.request(Method::OPTIONS, &args.url).headers(headers);
+    let req = apply_items(req, &args.items, BodyType::OptionalJson, &ctx.upload)?;
+    if ctx.offline {
+        return ctx.print_offline(req).await;
+    }
+    let resp = ctx.execute(req).await?;
+    ctx.tls.print_handshake(&ctx.client, resp.url()).await;
+    if ctx.print.response_headers {
+        print_status(&resp);
+        print_cors_headers(ctx, &resp);
+    }
+    print_resp_body(ctx, resp).await
+}
+
+/// 将 --preflight 的 key=value 转换成 CORS 预检请求需要的header
+fn preflight_headers(pairs: &[KvPair]) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for pair in pairs.iter() {
+        let name = match pair.k.as_str() {
+            "origin" => header::ORIGIN,
+            "method" => header::ACCESS_CONTROL_REQUEST_METHOD,
+            "headers" => header::ACCESS_CONTROL_REQUEST_HEADERS,
+            _ => return Err(anyhow!("Unknown preflight option {}", pair.k)),
+        };
+        headers.insert(name, pair.v.parse()?);
+    }
+    Ok(headers)
+}
+
+/// 将请求参数应用到请求上：header 加入请求头，query 加入URL，body 字段组装成body
+/// body 的编码方式由 body_type 决定
+fn apply_items(
+    mut req: RequestBuilder,
+    items: &[RequestItem],
+    body_type: BodyType,
+    upload: &Upload,
+) -> Result<RequestBuilder> {
+    let upload = &upload.for_request();
+    let mut query = Vec::new();
+    for item in items.iter() {
+        match item {
+            RequestItem::Header(pair) => req = req.header(pair.k.as_str(), pair.v.as_str()),
+            RequestItem::Query(pair) => query.push((&pair.k, &pair.v)),
+            _ => {}
+        }
+    }
+    if !query.is_empty() {
+        req = req.query(&query);
+    }
+    let req = upload.compress(apply_body(req, items, body_type, upload)?)?;
+    // 用户指定的 Content-Type 优先于根据body类型自动设置的值
+    Ok(req.headers(content_type_overrides(items)?))
+}
+
+/// 根据 body 字段构建body
+fn apply_body(
+    mut req: RequestBuilder,
+    items: &[RequestItem],
+    body_type: BodyType,
+    upload: &Upload,
+) -> Result<RequestBuilder> {
+    if let Some(raw) = find_raw_body(items)? {
+        return apply_raw_body(req, items, raw, upload);
+    }
+    // 有文件字段时只能以 multipart/form-data 的形式发送
+    let body_type = if items.iter().any(|item| matches!(item, RequestItem::File(_))) {
+        BodyType::Multipart
+    } else {
+        body_type
+    };
+    let mut form = Vec::new();
+    let mut parts = multipart::Form::new();
+    let mut body = Value::Null;
+    let mut repeated = HashSet::new();
+    for item in items.iter() {
+        match item {
+            RequestItem::Header(_) | RequestItem::Query(_) | RequestItem::Url(_) => {}
+            // 表单模式下字段名按原样发送，user[name] 这样的写法由服务端解析
+            RequestItem::Data(pair) => match body_type {
+                BodyType::Form => form.push((&pair.k, &pair.v)),
+                BodyType::Multipart => parts = parts.text(pair.k.clone(), pair.v.clone()),
+                _ => {
+                    let value = Value::String(pair.v.clone());
+                    set_path(&mut body, &parse_path(&pair.k)?, value, &mut repeated)?
+                }
+            },
+            RequestItem::Json(k, v) => match body_type {
+                BodyType::Form | BodyType::Multipart => {
+                    return Err(anyhow!("Raw JSON item {} is not supported in form bodies", k))
+                }
+                _ => set_path(&mut body, &parse_path(k)?, v.clone(), &mut repeated)?,
+            },
+            RequestItem::File(file) => parts = parts.part(file.field.clone(), file.to_part(upload)?),
+            RequestItem::BodyFile(_) | RequestItem::Raw(_) | RequestItem::Stdin => {
+                unreachable!("handled by apply_raw_body")
+            }
+        }
+    }
+    match body_type {
+        BodyType::Form => return Ok(req.form(&form)),
+        BodyType::Multipart if upload.gzip() => {
+            return Err(anyhow!("--compress is not supported for multipart bodies"))
+        }
+        BodyType::Multipart => return Ok(req.multipart(parts)),
+        _ => {}
+    }
+    if body_type == BodyType::Json && body.is_null() {
+        body = Value::Object(Map::new());
+    }
+    if !body.is_null() {
+        req = req.json(&body);
+    }
+    Ok(req)
+}
+
+/// 收集用户通过 Content-Type:... 或 --content-type 指定的 Content-Type
+fn content_type_overrides(items: &[RequestItem]) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for item in items.iter() {
+        if let RequestItem::Header(pair) = item {
+            if pair.k.eq_ignore_ascii_case("content-type") {
+                headers.insert(header::CONTENT_TYPE, pair.v.parse()?);
+            }
+        }
+    }
+    Ok(headers)
+}
+
+/// 如何编码 key=value 组成的body
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BodyType {
+    // 有 body 字段时才发送JSON
+    OptionalJson,
+    // 总是发送JSON，没有 body 字段时发送空的JSON对象
+    Json,
+    // application/x-www-form-urlencoded 表单
+    Form,
+    // multipart/form-data 表单，有文件字段时自动使用
+    Multipart,
+}
+
+/// 找到直接作为body的 @path、--raw 或 stdin，最多只能有一个
+fn find_raw_body(items: &[RequestItem]) -> Result<Option<&RequestItem>> {
+    let mut raws = items
+        .iter()
+        .filter(|item| {
+            matches!(item, RequestItem::BodyFile(_) | RequestItem::Raw(_) | RequestItem::Stdin)
+        });
+    let raw = raws.next();
+    if raws.next().is_some() {
+        return Err(anyhow!(
+            "Only one request body is allowed, it may come from an @file item, --raw or stdin \
+             (use --ignore-stdin to skip stdin)"
+        ));
+    }
+    Ok(raw)
+}
+
+/// 直接指定body时不能再有其它的body字段
+fn apply_raw_body(
+    req: RequestBuilder,
+    items: &[RequestItem],
+    raw: &RequestItem,
+    upload: &Upload,
+) -> Result<RequestBuilder> {
+    let has_fields = items.iter().any(|item| {
+        matches!(
+            item,
+            RequestItem::Data(_) | RequestItem::Json(..) | RequestItem::File(_)
+        )
+    });
+    if has_fields {
+        let source = match raw {
+            RequestItem::BodyFile(file) => format!("@{}", file.path),
+            _ => "--raw or stdin (use --ignore-stdin to skip stdin)".to_string(),
+        };
+        return Err(anyhow!(
+            "Request body from {} cannot be combined with body fields",
+            source
+        ));
+    }
+    match raw {
+        RequestItem::BodyFile(file) => {
+            file.to_body(req.header(header::CONTENT_TYPE, file.mime_type()), upload)
+        }
+        // 和 key=value 一样，默认认为输入的是JSON
+        RequestItem::Raw(data) => Ok(req
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(data.clone())),
+        // 不知道长度，reqwest 会使用 chunked 编码
+        RequestItem::Stdin => Ok(upload.body(
+            req.header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref()),
+            ReaderStream::new(tokio::io::stdin()),
+            None,
+        )),
+        _ => unreachable!("not a raw body"),
+    }
+}
+
+/// 读取YAML / TOML / JSON 格式的body文件，转换成JSON
+fn convert_body_file(path: &str, to: ConvertTo) -> Result<Vec<u8>> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read file {}", path))?;
+    let ext = std::path::Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let value: Value = match ext.as_str() {
+        "yaml" | "yml" => serde_yaml::from_str(&content)?,
+        "toml" => toml::from_str(&content)?,
+        "json" => serde_json::from_str(&content)?,
+        _ => {
+            return Err(anyhow!(
+                "Cannot detect the format of {}, expect .yaml, .toml or .json",
+                path
+            ))
+        }
+    };
+    match to {
+        ConvertTo::Json => Ok(serde_json::to_vec(&value)?),
+    }
+}
+
+/// 如果stdin是通过管道输入的（不是终端），读取其中的内容作为body
+fn read_stdin() -> Result<Option<Vec<u8>>> {
+    if atty::is(atty::Stream::Stdin) {
+        return Ok(None);
+    }
+    let mut data = Vec::new();
+    std::io::stdin().read_to_end(&mut data)?;
+    // 例如 < /dev/null 时没有任何内容，当作没有body
+    Ok(Some(data).filter(|data| !data.is_empty()))
+}
+
+/// body 字段名中的路径片段，例如 items[0][id] 会被解析成
+/// [Key("items"), Index(0), Key("id")]
+#[derive(Debug, PartialEq)]
+enum PathSegment {
+    // 对象的字段
+    Key(String),
+    // 数组的下标
+    Index(usize),
+    // []，追加到数组末尾
+    Append,
+}
+
+/// 解析 user[name] / items[0][id] / tags[] 这样的字段路径
+fn parse_path(key: &str) -> Result<Vec<PathSegment>> {
+    let err = || anyhow!("Invalid field path {}", key);
+    let (first, mut rest) = match key.find('[') {
+        Some(pos) => key.split_at(pos),
+        None => (key, ""),
+    };
+    if first.is_empty() {
+        return Err(err());
+    }
+    let mut path = vec![PathSegment::Key(first.to_string())];
+    while !rest.is_empty() {
+        // 剩余部分必须是若干个 [...]
+        let end = rest.find(']').filter(|_| rest.starts_with('[')).ok_or_else(err)?;
+        let name = &rest[1..end];
+        path.push(if name.is_empty() {
+            PathSegment::Append
+        } else if let Ok(i) = name.parse() {
+            PathSegment::Index(i)
+        } else {
+            PathSegment::Key(name.to_string())
+        });
+        rest = &rest[end + 1..];
+    }
+    Ok(path)
+}
+
+/// 按路径把 value 写入 JSON body，途中缺少的对象或数组会被自动创建
+/// 同一个字段（或者同一个下标）出现多次时（例如 tag=a tag=b），所有的值会依次放入一个数组，
+/// repeated 记录这样收集值的数组的位置，:= 写入的数组不会被当作已经收集的值
+fn set_path(
+    target: &mut Value,
+    path: &[PathSegment],
+    value: Value,
+    repeated: &mut HashSet<String>,
+) -> Result<()> {
+    set_at(target, path, value, String::new(), repeated)
+}
+
+/// location 是 target 在 body 中的位置，形式和 JSON Pointer 一样，例如 /items/0/id
+fn set_at(
+    target: &mut Value,
+    path: &[PathSegment],
+    value: Value,
+    location: String,
+    repeated: &mut HashSet<String>,
+) -> Result<()> {
+    let (segment, rest) = match path.split_first() {
+        Some(v) => v,
+        None if target.is_null() => {
+            *target = value;
+            return Ok(());
+        }
+        None => {
+            match target {
+                Value::Array(arr) if repeated.contains(&location) => arr.push(value),
+                _ => {
+                    *target = Value::Array(vec![target.take(), value]);
+                    repeated.insert(location);
+                }
+            }
+            return Ok(());
+        }
+    };
+    let conflict = || anyhow!("Cannot set {:?}: conflicting types in body", segment);
+    let (slot, location) = match segment {
+        PathSegment::Key(k) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            let obj = target.as_object_mut().ok_or_else(conflict)?;
+            let location = format!("{}/{}", location, k.replace('~', "~0").replace('/', "~1"));
+            (obj.entry(k.clone()).or_insert(Value::Null), location)
+        }
+        PathSegment::Index(i) => {
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let arr = target.as_array_mut().ok_or_else(conflict)?;
+            if arr.len() <= *i {
+                arr.resize(i + 1, Value::Null);
+            }
+            (&mut arr[*i], format!("{}/{}", location, i))
+        }
+        PathSegment::Append => {
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let arr = target.as_array_mut().ok_or_else(conflict)?;
+            arr.push(Value::Null);
+            let location = format!("{}/{}", location, arr.len() - 1);
+            (arr.last_mut().unwrap(), location)
+        }
+    };
+    set_at(slot, rest, value, location, repeated)
+}
+
+/// 应用请求参数，发送请求并打印响应
+async fn send(
+    ctx: &Context,
+    req: RequestBuilder,
+    items: &[RequestItem],
+    body_type: BodyType,
+) -> Result<()> {
+    let req = apply_items(req, items, body_type, &ctx.upload)?;
+    if ctx.offline {
+        return ctx.print_offline(req).await;
+    }
+    let urls: Vec<&Url> = items
+        .iter()
+        .filter_map(|item| match item {
+            RequestItem::Url(url) => Some(url),
+            _ => None,
+        })
+        .collect();
+    if ctx.download.enabled() {
+        return download_all(ctx, req, &urls).await;
+    }
+    if let Some(url) = urls.first() {
+        return Err(anyhow!("Several URLs ({}) can only be given with --download", url));
+    }
+    let resp = ctx.execute(ctx.download.apply_range(req)).await?;
+    print_head(ctx, &resp).await;
+    ctx.download.check_range(&resp)?;
+    if let Some(path) = ctx.download.output() {
+        return ctx.download.write_output(&ctx.client_args, resp, &ctx.progress, path).await;
+    }
+    print_resp_body(ctx, resp).await
+}
+
+/// -d/--download 多个URL时同样的请求发送到每个URL，最多同时下载 --jobs 个
+/// 一个下载失败时其它的下载继续进行，最后报告失败的数量
+async fn download_all(ctx: &Context, req: RequestBuilder, urls: &[&Url]) -> Result<()> {
+    if urls.is_empty() {
+        return download(ctx, req, None).await;
+    }
+    let (client, first) = req.build_split();
+    let first = first?;
+    let mut reqs = Vec::new();
+    for url in urls {
+        let mut req = first
+            .try_clone()
+            .ok_or_else(|| anyhow!("A streamed request body cannot be sent to several URLs"))?;
+        *req.url_mut() = (*url).clone();
+        reqs.push(req);
+    }
+    reqs.insert(0, first);
+    let count = reqs.len();
+    let bars = Bars::new();
+    let failed = futures::stream::iter(reqs)
+        .map(|req| {
+            let url = req.url().clone();
+            let req = RequestBuilder::from_parts(client.clone(), req);
+            let bars = &bars;
+            async move {
+                let result = download(ctx, req, Some(bars)).await;
+                if let Err(ref e) = result {
+                    bars.message(format!("http: {}: {:#}", url, e).red());
+                }
+                result.is_err()
+            }
+        })
+        .buffer_unordered(ctx.download.jobs())
+        .filter(|failed| futures::future::ready(*failed))
+        .count()
+        .await;
+    bars.finish();
+    match failed {
+        0 => Ok(()),
+        _ => Err(anyhow!("{} of {} downloads failed", failed, count)),
+    }
+}
+
+/// -d/--download：打印状态码和header，把成功响应的 body 保存到文件
+/// 同时下载多个URL时不打印响应，失败的响应作为错误
+async fn download(ctx: &Context, req: RequestBuilder, bars: Option<&Bars>) -> Result<()> {
+    let (req, target) = ctx.download.prepare(req, bars)?;
+    // --segments 时用同样的请求加上 Range 获取其它的段
+    let copy = req.try_clone();
+    let resp = ctx.execute(req).await?;
+    if bars.is_none() {
+        print_head(ctx, &resp).await;
+    }
+    if !target.wants(&resp) {
+        if bars.is_some() {
+            return Err(anyhow!("The server answered {}", resp.status()));
+        }
+        return print_resp_body(ctx, resp).await;
+    }
+    let mut parts = Vec::new();
+    if let Some(req) = copy {
+        let fetch = ctx.download.segments(&resp, &target).into_iter().map(|segment| {
+            let req = req.try_clone().map(|req| segment.request(req));
+            async move {
+                let req = req.ok_or_else(|| anyhow!("The request cannot be sent again"))?;
+                Ok::<_, anyhow::Error>((segment, ctx.execute(req).await?))
+            }
+        });
+        parts = futures::future::try_join_all(fetch).await?;
+    }
+    ctx.download.save(&ctx.client_args, resp, &ctx.progress, target, parts).await
 }
 
 // 打印服务器版本号 + 状态码
 fn print_status(resp: &Response) {
-    let status = format!("{:?} {}", resp.version(), resp.status()).blue();
-    println!("{}\n", status);
+    // 实际使用的 HTTP 版本加粗显示
+    let version = format!("{:?}", resp.version()).blue().bold();
+    println!("{} {}\n", version, resp.status().to_string().blue());
 }
 
-// 打印服务器返回的HTTP header
-fn print_headers(resp: &Response) {
+// 打印HTTP header，敏感的值会被隐藏
+fn print_headers(headers: &HeaderMap, redactor: &Redactor) {
+    for (name, value) in headers {
+        println!("{}: {}", name.to_string().green(), redactor.value(name, value));
+    }
+
+    println!();
+}
+
+/// 响应的 Trailer header 中预告的 trailer 名字，例如 grpc-status、server-timing
+fn trailer_names(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::TRAILER)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// --trailers 时在 body 之后打印收到的 trailer。没有 --trailers 时 reqwest 读取 body 会丢弃
+/// trailer，响应预告了 trailer 时提示使用 --trailers
+fn print_trailers(ctx: &Context, (trailers, names): (Option<Trailers>, Vec<String>)) {
+    match trailers {
+        Some(trailers) => {
+            let trailers = trailers.take();
+            if trailers.is_empty() || !ctx.print.response_headers {
+                return;
+            }
+            println!();
+            println!("{}", "Trailers:".magenta().bold());
+            print_headers(&trailers, &ctx.redactor);
+        }
+        None if !names.is_empty() => {
+            let msg = format!(
+                "http: warning: the server announced trailers ({}), use --trailers to show them",
+                names.join(", ")
+            );
+            quiet::warn(msg.yellow());
+        }
+        None => {}
+    }
+}
+
+/// 是否是需要在 OPTIONS 响应中高亮的header
+fn is_cors_header(name: &HeaderName) -> bool {
+    name == header::ALLOW || name.as_str().starts_with("access-control-")
+}
+
+// 打印 OPTIONS 响应的header，Allow 和 Access-Control-* 会被高亮
+fn print_cors_headers(ctx: &Context, resp: &Response) {
     for (name, value) in resp.headers() {
-        println!("{}: {:?}", name.to_string().green(), value);
+        let value = ctx.redactor.value(name, value);
+        if is_cors_header(name) {
+            let line = format!("{}: {}", name, value);
+            println!("{}", line.yellow().bold());
+        } else {
+            println!("{}: {}", name.to_string().green(), value);
+        }
     }
 
-    print!("\n");
+    println!();
+}
+
+/// 是否是 JSON 的 body，包括 application/problem+json 等带 +json 后缀的类型
+fn is_json(m: Option<&Mime>) -> bool {
+    m.is_some_and(|m| m.essence_str() == "application/json" || m.suffix() == Some(mime::JSON))
 }
 
-/// 打印服务器返回的HTTP body
-fn print_body(m: Option<Mime>, body: &String) {
-    match m {
-        // 对于 “application/json” 我们 pretty print
-        Some(v) if v == mime::APPLICATION_JSON => {
-            println!("{}", jsonxf::pretty_print(body).unwrap().cyan());
+/// 是否需要读取整个 body 才能格式化
+fn is_formatted(m: Option<&Mime>) -> bool {
+    is_json(m) || xml::is_xml(m) || html::is_html(m)
+}
+
+/// 打印HTTP body，format 为 true 时格式化 JSON 和 XML，格式不合法时警告并原样打印
+fn print_body(m: Option<Mime>, body: &String, format: bool) {
+    let (formatted, kind) = if is_json(m.as_ref()) {
+        if !format {
+            println!("{}", body.cyan());
+            return;
+        }
+        (body::format_json(body).map(|s| s.cyan().to_string()), "JSON")
+    } else if format && html::is_html(m.as_ref()) {
+        println!("{}", html::highlight(body));
+        return;
+    } else if format && xml::is_xml(m.as_ref()) {
+        (xml::format(body), "XML")
+    } else {
+        println!("{}", body);
+        return;
+    };
+    match formatted {
+        Some(formatted) => println!("{}", formatted),
+        None => {
+            let msg = format!("http: warning: the body is not valid {}, printing it as is", kind);
+            quiet::warn(msg.yellow());
+            println!("{}", body);
         }
-        _ => println!("{}", body),
     }
 }
 
-/// 将服务器返回的content-type 解析成Mime类型
+/// --filter 时对 JSON（或者 NDJSON 的每一行）应用路径，每个结果打印成一个 JSON
+/// body 不是合法的 JSON 时警告并原样打印
+fn print_filtered(filter: &Filter, mime: Option<Mime>, body: &str, format: bool) -> Result<()> {
+    let values: Result<Vec<Value>, _> = serde_json::Deserializer::from_str(body).into_iter().collect();
+    let values = match values {
+        Ok(values) => values,
+        Err(_) => {
+            quiet::warn("http: warning: the body is not valid JSON, printing it as is".yellow());
+            println!("{}", body);
+            return Ok(());
+        }
+    };
+    for value in values {
+        for result in filter.apply(&value)? {
+            print_body(mime.clone(), &result.to_string(), format);
+        }
+    }
+    Ok(())
+}
+
+/// 将服务器返回的content-type 解析成Mime类型，不是合法的类型时为 None
 fn get_content_type(resp: &Response) -> Option<Mime> {
     resp.headers()
         .get(header::CONTENT_TYPE)
-        .map(|v| v.to_str().unwrap().parse().unwrap())
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+/// 打印将要发送的请求：--print 中的 H 打印请求行和 header，B 打印 body
+/// digest、ntlm 和 oauth2 认证的 header 在和服务器交互时才加上，不会打印出来
+fn print_request(ctx: &Context, req: RequestBuilder) -> Result<RequestBuilder> {
+    let (client, req) = req.build_split();
+    let req = req?;
+    if ctx.print.request_headers {
+        let url = req.url();
+        let target = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), ctx.redactor.query(query)),
+            None => url.path().to_string(),
+        };
+        let version = format!("{:?}", req.version());
+        println!("{} {} {}", req.method().as_str().blue().bold(), target.blue(), version.blue());
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        // --host-header、--connect-to 和 --sni 改写 URL 时设置了 Host header
+        if !req.headers().contains_key(header::HOST) {
+            println!("{}: {:?}", "host".green(), host);
+        }
+        print_headers(req.headers(), &ctx.redactor);
+    }
+    if let (true, Some(body)) = (ctx.print.request_body, req.body()) {
+        let mime = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok());
+        match body.as_bytes() {
+            Some(data) if body::is_binary(mime.as_ref(), data) => {
+                let msg = format!("+ Binary request body ({} bytes) +", data.len());
+                println!("{}", msg.yellow());
+            }
+            Some(data) => {
+                print_body(mime, &String::from_utf8_lossy(data).into_owned(), ctx.pretty.format())
+            }
+            // 文件和 stdin 是边读取边发送的
+            None => println!("{}", "+ Streamed request body +".yellow()),
+        }
+        println!();
+    }
+    Ok(RequestBuilder::from_parts(client, req))
+}
+
+/// 打印状态码和header，不读取body
+async fn print_head(ctx: &Context, resp: &Response) {
+    ctx.tls.print_handshake(&ctx.client, resp.url()).await;
+    if !ctx.print.response_headers {
+        return;
+    }
+    print_status(resp);
+    print_headers(resp.headers(), &ctx.redactor);
 }
 
 /// 打印整个响应
-async fn print_resp(resp: Response) -> Result<()> {
-    print_status(&resp);
-    print_headers(&resp);
+async fn print_resp(ctx: &Context, resp: Response) -> Result<()> {
+    print_head(ctx, &resp).await;
+    print_resp_body(ctx, resp).await
+}
+
+/// 读取并打印响应的body，二进制的body不会打印到终端
+/// 响应预告了 trailer 时在 body 之后警告拿不到它们的值，指定了 --decode-jwt 时接着打印 body 中的 JWT
+async fn print_resp_body(ctx: &Context, resp: Response) -> Result<()> {
+    if !ctx.print.response_body {
+        return Ok(());
+    }
     let mime = get_content_type(&resp);
-    let body = resp.text().await?;
-    print_body(mime, &body);
+    let charset = client::charset(&resp);
+    let trailers = (Trailers::of(&resp), trailer_names(resp.headers()));
+    if let Some(limit) = ctx.hex {
+        return hex_resp_body(ctx, resp, limit).await;
+    }
+    if sse::is_event_stream(mime.as_ref()) {
+        return print_events(ctx, resp, &mut sse::Parser::default()).await;
+    }
+    let select = match ctx.select {
+        Some(ref select) if html::is_html(mime.as_ref()) => Some(select),
+        Some(_) => {
+            quiet::warn("http: warning: --select only applies to HTML bodies, printing the body as is".yellow());
+            None
+        }
+        None => None,
+    };
+    let filter = match ctx.filter {
+        Some(ref filter) if is_json(mime.as_ref()) => Some(filter),
+        Some(_) => {
+            quiet::warn("http: warning: --filter only applies to JSON bodies, printing the body as is".yellow());
+            None
+        }
+        None => None,
+    };
+    let xpath = match ctx.xpath {
+        Some(ref xpath) if xml::is_xml(mime.as_ref()) => Some(xpath),
+        Some(_) => {
+            quiet::warn("http: warning: --xpath only applies to XML bodies, printing the body as is".yellow());
+            None
+        }
+        None => None,
+    };
+    // 格式化 JSON、查找 JWT、--select、--filter 和 --xpath 需要整个 body，其它情况边接收边打印
+    if select.is_none() && filter.is_none() && xpath.is_none()
+        && (ctx.stream || !(is_formatted(mime.as_ref()) && ctx.pretty.format() || ctx.decode_jwt))
+    {
+        stream_resp_body(ctx, resp, mime, charset).await?;
+        print_trailers(ctx, trailers);
+        return Ok(());
+    }
+    let body = ctx.client_args.read_body(resp, &ctx.progress).await?;
+    if body::is_binary(mime.as_ref(), &body) {
+        return body::print_binary(&body);
+    }
+    let body = charset::decode(charset, &body);
+    // --select、--filter 和 --xpath 只打印选中的内容，--format-html 时先重新缩进，缩进时同时高亮
+    if let Some(select) = select {
+        for text in select.extract(&body) {
+            println!("{}", text);
+        }
+    } else if let Some(filter) = filter {
+        print_filtered(filter, mime, &body, ctx.pretty.format())?;
+    } else if let Some(xpath) = xpath {
+        match xpath.evaluate(&body)? {
+            Some(values) => values.iter().for_each(|value| println!("{}", value)),
+            None => {
+                quiet::warn("http: warning: the body is not valid XML, printing it as is".yellow());
+                println!("{}", body);
+            }
+        }
+    } else if ctx.format_html && ctx.pretty.format() && html::is_html(mime.as_ref()) {
+        println!("{}", html::indent(&body));
+    } else {
+        print_body(mime, &body, ctx.pretty.format());
+    }
+    print_trailers(ctx, trailers);
+    if ctx.decode_jwt {
+        for (path, token) in jwt::find_in_body(&body) {
+            jwt::print(&format!("response {}", path), &token);
+        }
+    }
+
+    Ok(())
+}
+
+/// --hex 时边接收边打印 body 的十六进制，超过 limit 时停止读取
+async fn hex_resp_body(ctx: &Context, mut resp: Response, limit: u64) -> Result<()> {
+    let mut decoder = ctx.client_args.decoder(&resp)?;
+    let mut dump = body::HexDump::new(limit);
+    while let Some(chunk) = ctx.client_args.next_decoded(&mut resp, &mut decoder, &ctx.progress).await? {
+        for line in dump.push(&chunk) {
+            println!("{}", line);
+        }
+        if dump.truncated() {
+            break;
+        }
+    }
+    let truncated = dump.truncated();
+    if let Some(line) = dump.finish() {
+        println!("{}", line);
+    }
+    if truncated {
+        let msg = format!("+ Only the first {} bytes are shown, use --hex-limit to see more +", limit);
+        println!("{}", msg.yellow());
+    }
+    Ok(())
+}
 
+/// 边接收边解析并打印 Server-Sent Events，直到连接关闭
+async fn print_events(ctx: &Context, mut resp: Response, parser: &mut sse::Parser) -> Result<()> {
+    let mut decoder = ctx.client_args.decoder(&resp)?;
+    // text/event-stream 总是 UTF-8
+    let mut text = charset::TextDecoder::new(Some("utf-8".into()));
+    let mut line = String::new();
+    let mut stdout = std::io::stdout();
+    while let Some(chunk) = ctx.client_args.next_decoded(&mut resp, &mut decoder, &ctx.progress).await? {
+        line.push_str(&text.decode(&chunk, false));
+        for l in body::take_lines(&mut line) {
+            if let Some(event) = parser.feed(&l) {
+                sse::print(&event);
+                stdout.flush()?;
+            }
+        }
+    }
     Ok(())
 }
 
+/// 边接收边打印响应的 body，很大或者不会结束的响应也不会卡住
+/// 根据第一块数据判断是不是二进制，--stream 时按行打印，每一行都立即刷新
+async fn stream_resp_body(
+    ctx: &Context,
+    mut resp: Response,
+    mime: Option<Mime>,
+    charset: Option<String>,
+) -> Result<()> {
+    let len = resp.content_length();
+    let mut decoder = ctx.client_args.decoder(&resp)?;
+    let mut text = charset::TextDecoder::new(charset);
+    // 按行打印时只能格式化 JSON
+    let format = ctx.pretty.format() && is_json(mime.as_ref());
+    let mut stdout = std::io::stdout();
+    let mut binary = None;
+    // --stream 时还没有换行的部分
+    let mut line = String::new();
+    // 最后打印的是不是换行，结束时补上换行
+    let mut newline = false;
+    loop {
+        let chunk = match ctx.client_args.next_decoded(&mut resp, &mut decoder, &ctx.progress).await? {
+            Some(chunk) if chunk.is_empty() => continue,
+            chunk => chunk,
+        };
+        match chunk {
+            Some(ref chunk) if *binary.get_or_insert_with(|| body::is_binary(mime.as_ref(), chunk)) => {
+                if atty::is(atty::Stream::Stdout) {
+                    body::print_binary_notice(len);
+                    return Ok(());
+                }
+                stdout.write_all(chunk)?;
+                stdout.flush()?;
+                continue;
+            }
+            None if binary == Some(true) => return Ok(()),
+            _ => {}
+        }
+        let last = chunk.is_none();
+        let chunk = chunk.unwrap_or_default();
+        let s = text.decode(&chunk, last);
+        if ctx.stream {
+            line.push_str(&s);
+            for l in body::take_lines(&mut line) {
+                print_body(mime.clone(), &l, format);
+                stdout.flush()?;
+            }
+            if last && !line.is_empty() {
+                print_body(mime.clone(), &line, format);
+            }
+        } else if !s.is_empty() {
+            newline = s.ends_with('\n');
+            match is_json(mime.as_ref()) {
+                true => write!(stdout, "{}", s.cyan())?,
+                false => write!(stdout, "{}", s)?,
+            }
+            stdout.flush()?;
+        }
+        if last {
+            break;
+        }
+    }
+    if !ctx.stream && !newline {
+        println!();
+    }
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let opts: Opts = Opts::parse();
-    // 生成一个HTTP客户端
-    let client = Client::new();
-    let result = match opts.subcmd {
-        SubCommand::Get(ref args) => get(client, args).await?,
-        SubCommand::Post(ref args) => post(client, args).await?,
+    let mut opts: Opts = Opts::parse();
+    quiet::set(opts.quiet);
+    quiet::set_verbose(opts.verbose);
+    // 默认只在终端上显示颜色
+    if let Some(pretty) = opts.pretty {
+        colored::control::set_override(pretty.colors());
+    }
+    // ws 把 stdin 的每一行作为消息发送，不会把整个 stdin 作为 body
+    let is_ws = match opts.subcmd {
+        SubCommand::Ws(ref mut args) => {
+            args.stdin = !opts.ignore_stdin;
+            true
+        }
+        _ => false,
+    };
+    let items = opts.subcmd.items_mut();
+    // --raw 和 --body-file 优先于 stdin
+    if let Some(raw) = opts.raw.take() {
+        items.push(RequestItem::Raw(raw.into_bytes()));
+    } else if let Some(path) = opts.body_file.take() {
+        items.push(match opts.convert {
+            Some(to) => RequestItem::Raw(convert_body_file(&path, to)?),
+            None => RequestItem::BodyFile(FileItem {
+                field: "".into(),
+                path,
+                mime: None,
+            }),
+        });
+    } else if opts.stream_body {
+        items.push(RequestItem::Stdin);
+    } else if !opts.ignore_stdin && !is_ws {
+        if let Some(data) = read_stdin()? {
+            items.push(RequestItem::Raw(data));
+        }
+    }
+    if let Some(content_type) = opts.content_type.take() {
+        items.push(RequestItem::Header(KvPair {
+            k: header::CONTENT_TYPE.to_string(),
+            v: content_type,
+        }));
+    }
+    let auth = Auth::from_args(&opts.auth, opts.subcmd.url())?;
+    let signer = HmacSigner::from_args(&opts.auth)?;
+    let alt_svc = match &opts.client.alt_svc {
+        Some(path) => {
+            let client = opts.client.build_h3(&opts.proxy, &opts.tls, &opts.dns)?;
+            Some(AltSvc::load(path, client)?)
+        }
+        None => None,
     };
+    let upload = Upload::new(
+        opts.client.upload_limit(),
+        opts.compress,
+        opts.client.expect_continue(),
+    );
+    let mut redactor = Redactor::new(&opts.redact, opts.show_raw_headers)?;
+    if let Some(ref auth) = auth {
+        auth.redact(&mut redactor)?;
+    }
+    let direct = opts.client.connects_directly(&opts.proxy);
+    let source = opts.client.source_address(&opts.dns)?;
+    // --trailers 自己连接服务器，不经过代理，也不能进行需要多次往返的认证
+    if opts.client.trailers() {
+        if !direct {
+            return Err(anyhow!(
+                "--trailers cannot connect through a proxy (--proxy, HTTP_PROXY / HTTPS_PROXY / ALL_PROXY)"
+            ));
+        }
+        if let Some(auth_type) = auth.as_ref().and_then(Auth::exchange_type) {
+            return Err(anyhow!("--auth-type {} cannot be used with --trailers", auth_type));
+        }
+    }
+    let ctx = Context {
+        // 生成一个HTTP客户端
+        client: opts.client.build(&opts.proxy, &opts.tls, &opts.dns)?,
+        auth,
+        signer,
+        decode_jwt: opts.decode_jwt,
+        redactor,
+        redirect: opts.redirect,
+        client_args: opts.client,
+        progress: Progress::new(),
+        retry: opts.retry,
+        tls: opts.tls,
+        alt_svc,
+        dns: opts.dns,
+        download: opts.download,
+        upload,
+        print: match opts.print {
+            _ if opts.quiet > 0 => Print::none(),
+            Some(print) => print,
+            None if opts.verbose || opts.offline => Print::all(),
+            None => Print::default(),
+        },
+        offline: opts.offline,
+        direct,
+        source,
+        status: AtomicU16::new(0),
+        stream: opts.stream,
+        hex: match opts.hex {
+            true => Some(opts.hex_limit),
+            false => None,
+        },
+        pretty: opts.pretty.unwrap_or(Pretty::All),
+        format_html: opts.format_html,
+        select: opts.select,
+        filter: opts.filter,
+        xpath: opts.xpath,
+    };
+    // 超过 --max-time 时丢弃整个请求的 future，连接随之关闭
+    let result = match ctx.client_args.max_time {
+        Some(t) => tokio::time::timeout(t, run(&ctx, &opts.subcmd))
+            .await
+            .map_err(|_| ctx.progress.deadline_error(t))
+            .and_then(|result| result),
+        None => run(&ctx, &opts.subcmd).await,
+    };
+    ctx.dns.print_stats();
+    result?;
+    if opts.quiet > 0 {
+        // 和 HTTPie 的 --check-status 一样
+        match ctx.status.load(Ordering::Relaxed) {
+            300..=399 => std::process::exit(3),
+            400..=499 => std::process::exit(4),
+            500..=599 => std::process::exit(5),
+            _ => {}
+        }
+    }
+    Ok(())
+}
 
-    Ok(result)
+/// 执行子命令
+async fn run(ctx: &Context, subcmd: &SubCommand) -> Result<()> {
+    match subcmd {
+        SubCommand::Get(ref args) => get(ctx, args).await,
+        SubCommand::Post(ref args) => post(ctx, args).await,
+        SubCommand::Put(ref args) => put(ctx, args).await,
+        SubCommand::Patch(ref args) => patch(ctx, args).await,
+        SubCommand::Delete(ref args) => delete(ctx, args).await,
+        SubCommand::Head(ref args) => head(ctx, args).await,
+        SubCommand::Options(ref args) => options(ctx, args).await,
+        SubCommand::Request(ref args) => request(ctx, args).await,
+        SubCommand::Sse(ref args) => sse(ctx, args).await,
+        SubCommand::Ws(ref args) => ws(ctx, args).await,
+    }
 }
 
 // 仅在cargo test 时才编译
@@ -167,6 +1996,13 @@ mod tests {
         assert!(parse_url("https://httpbin.org/post").is_ok());
     }
 
+    #[test]
+    fn parse_method_works() {
+        assert_eq!(parse_method("get").unwrap(), Method::GET);
+        assert_eq!(parse_method("purge").unwrap().as_str(), "PURGE");
+        assert!(parse_method("bad method").is_err());
+    }
+
     #[test]
     fn parse_kv_pair_works() {
         assert!(parse_kv_pair("a").is_err());
@@ -185,5 +2021,364 @@ mod tests {
                 v: "".into()
             }
         );
+
+        assert_eq!(
+            parse_kv_pair("token=abc=def").unwrap(),
+            KvPair {
+                k: "token".into(),
+                v: "abc=def".into()
+            }
+        );
+
+        assert_eq!(
+            parse_kv_pair(r"a\=b=c\=d").unwrap(),
+            KvPair {
+                k: "a=b".into(),
+                v: "c=d".into()
+            }
+        );
+    }
+
+    #[test]
+    fn unescape_works() {
+        assert_eq!(unescape(r"a\=b\:c"), "a=b:c");
+        assert_eq!(unescape(r"a\\b"), r"a\b");
+        assert_eq!(unescape(r"C:\dir\n"), r"C:\dir\n");
+    }
+
+    #[test]
+    fn parse_request_item_works() {
+        assert!(parse_request_item("a").is_err());
+        assert!(parse_request_item(":a").is_err());
+        assert_eq!(
+            parse_request_item("X-Api-Key:abc").unwrap(),
+            RequestItem::Header(KvPair {
+                k: "X-Api-Key".into(),
+                v: "abc".into()
+            })
+        );
+        assert_eq!(
+            parse_request_item("time=12:00").unwrap(),
+            RequestItem::Data(KvPair {
+                k: "time".into(),
+                v: "12:00".into()
+            })
+        );
+        assert_eq!(
+            parse_request_item(r"foo\:bar:baz").unwrap(),
+            RequestItem::Header(KvPair {
+                k: "foo:bar".into(),
+                v: "baz".into()
+            })
+        );
+        assert_eq!(
+            parse_request_item("search==rust").unwrap(),
+            RequestItem::Query(KvPair {
+                k: "search".into(),
+                v: "rust".into()
+            })
+        );
+        assert_eq!(
+            parse_request_item("a=b==c").unwrap(),
+            RequestItem::Data(KvPair {
+                k: "a".into(),
+                v: "b==c".into()
+            })
+        );
+        assert_eq!(
+            parse_request_item("tags:=[\"a\",\"b\"]").unwrap(),
+            RequestItem::Json("tags".into(), serde_json::json!(["a", "b"]))
+        );
+        assert_eq!(
+            parse_request_item("count:=3").unwrap(),
+            RequestItem::Json("count".into(), serde_json::json!(3))
+        );
+        assert!(parse_request_item("count:=abc").is_err());
+        assert_eq!(
+            parse_request_item("https://example.com/b.zip").unwrap(),
+            RequestItem::Url("https://example.com/b.zip".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_path_works() {
+        assert_eq!(parse_path("a").unwrap(), vec![PathSegment::Key("a".into())]);
+        assert_eq!(
+            parse_path("items[0][id]").unwrap(),
+            vec![
+                PathSegment::Key("items".into()),
+                PathSegment::Index(0),
+                PathSegment::Key("id".into())
+            ]
+        );
+        assert_eq!(
+            parse_path("tags[]").unwrap(),
+            vec![PathSegment::Key("tags".into()), PathSegment::Append]
+        );
+        assert!(parse_path("[0]").is_err());
+        assert!(parse_path("a[b").is_err());
+        assert!(parse_path("a[b]c").is_err());
+    }
+
+    /// 依次写入 (路径, 值)，返回得到的 body
+    fn build_body(fields: &[(&str, Value)]) -> Result<Value> {
+        let (mut body, mut repeated) = (Value::Null, HashSet::new());
+        for (path, value) in fields {
+            set_path(&mut body, &parse_path(path)?, value.clone(), &mut repeated)?;
+        }
+        Ok(body)
+    }
+
+    #[test]
+    fn set_path_works() {
+        let body = build_body(&[
+            ("user[name]", "kim".into()),
+            ("items[1][id]", 3.into()),
+            ("tags[]", "a".into()),
+        ])
+        .unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "user": { "name": "kim" },
+                "items": [null, { "id": 3 }],
+                "tags": ["a"]
+            })
+        );
+        assert!(build_body(&[("user[name]", "kim".into()), ("user[0]", 1.into())]).is_err());
+    }
+
+    #[test]
+    fn set_path_repeated_keys_works() {
+        let body = build_body(&[
+            ("tag", "a".into()),
+            ("tag", "b".into()),
+            ("tag", "c".into()),
+            ("user[id]", 1.into()),
+            ("user[id]", 2.into()),
+        ])
+        .unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "tag": ["a", "b", "c"], "user": { "id": [1, 2] } })
+        );
+        // 重复的下标同样收集到数组中
+        let items = ["a", "b", "c"].iter().map(|v| ("items[0]", Value::from(*v)));
+        let body = build_body(&items.collect::<Vec<_>>()).unwrap();
+        assert_eq!(body, serde_json::json!({ "items": [["a", "b", "c"]] }));
+        // := 写入的数组是一个值，不会被当作已经收集的值
+        let body = build_body(&[("a", serde_json::json!([1])), ("a", "2".into()), ("a", "3".into())]);
+        assert_eq!(body.unwrap(), serde_json::json!({ "a": [[1], "2", "3"] }));
+    }
+
+    #[test]
+    fn apply_items_works() {
+        let items: Vec<RequestItem> = ["a=1", "b:=2", "X-Id:3", "q==4"]
+            .iter()
+            .map(|s| parse_request_item(s).unwrap())
+            .collect();
+        let req = Client::new().post("http://abc.xyz/");
+        let req = apply_items(req, &items, BodyType::Json, &Upload::default()).unwrap().build().unwrap();
+        assert_eq!(req.url().as_str(), "http://abc.xyz/?q=4");
+        assert_eq!(req.headers()["x-id"], "3");
+        let body = req.body().unwrap().as_bytes().unwrap();
+        assert_eq!(body, br#"{"a":"1","b":2}"#);
+    }
+
+    #[test]
+    fn apply_items_form_works() {
+        let items = vec![parse_request_item("a=1").unwrap(), parse_request_item("a=b c").unwrap()];
+        let req = Client::new().post("http://abc.xyz/");
+        let req = apply_items(req, &items, BodyType::Form, &Upload::default()).unwrap().build().unwrap();
+        assert_eq!(
+            req.headers()[header::CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(req.body().unwrap().as_bytes().unwrap(), b"a=1&a=b+c");
+
+        let items = vec![parse_request_item("a:=1").unwrap()];
+        let req = Client::new().post("http://abc.xyz/");
+        assert!(apply_items(req, &items, BodyType::Form, &Upload::default()).is_err());
+    }
+
+    #[test]
+    fn parse_file_item_works() {
+        assert_eq!(
+            parse_request_item("avatar@./photo.png").unwrap(),
+            RequestItem::File(FileItem {
+                field: "avatar".into(),
+                path: "./photo.png".into(),
+                mime: None
+            })
+        );
+        assert_eq!(
+            parse_request_item("avatar@photo.png;type=image/png").unwrap(),
+            RequestItem::File(FileItem {
+                field: "avatar".into(),
+                path: "photo.png".into(),
+                mime: Some("image/png".into())
+            })
+        );
+        assert_eq!(
+            parse_request_item("email=a@b.com").unwrap(),
+            RequestItem::Data(KvPair {
+                k: "email".into(),
+                v: "a@b.com".into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_body_file_item_works() {
+        assert_eq!(
+            parse_request_item("@payload.xml;type=application/xml").unwrap(),
+            RequestItem::BodyFile(FileItem {
+                field: "".into(),
+                path: "payload.xml".into(),
+                mime: Some("application/xml".into())
+            })
+        );
+    }
+
+    #[test]
+    fn apply_body_file_works() {
+        let items = vec![
+            parse_request_item("@Cargo.toml").unwrap(),
+            parse_request_item("q==1").unwrap(),
+        ];
+        let req = Client::new().post("http://abc.xyz/");
+        let req = apply_items(req, &items, BodyType::Json, &Upload::default()).unwrap().build().unwrap();
+        assert_eq!(req.url().as_str(), "http://abc.xyz/?q=1");
+        assert_eq!(req.headers()[header::CONTENT_TYPE], "text/x-toml");
+        let len = std::fs::metadata("Cargo.toml").unwrap().len();
+        assert_eq!(req.headers()[header::CONTENT_LENGTH], len.to_string().as_str());
+
+        let items = vec![
+            parse_request_item("@Cargo.toml").unwrap(),
+            parse_request_item("a=1").unwrap(),
+        ];
+        let req = Client::new().post("http://abc.xyz/");
+        assert!(apply_items(req, &items, BodyType::Json, &Upload::default()).is_err());
+    }
+
+    #[test]
+    fn apply_stdin_body_works() {
+        let items = vec![
+            parse_request_item("X-Id:1").unwrap(),
+            RequestItem::Raw(b"[1,2]".to_vec()),
+        ];
+        let req = Client::new().post("http://abc.xyz/");
+        let req = apply_items(req, &items, BodyType::Json, &Upload::default()).unwrap().build().unwrap();
+        assert_eq!(req.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(req.body().unwrap().as_bytes().unwrap(), b"[1,2]");
+
+        let items = vec![
+            parse_request_item("@Cargo.toml").unwrap(),
+            RequestItem::Raw(b"[1,2]".to_vec()),
+        ];
+        let req = Client::new().post("http://abc.xyz/");
+        assert!(apply_items(req, &items, BodyType::Json, &Upload::default()).is_err());
+
+        // --stream-body 的 body 是流，没有 Content-Length
+        let req = Client::new().post("http://abc.xyz/");
+        let req = apply_items(req, &[RequestItem::Stdin], BodyType::Json, &Upload::default())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(req.body().unwrap().as_bytes().is_none());
+        assert!(req.headers().get(header::CONTENT_LENGTH).is_none());
+    }
+
+    #[test]
+    fn apply_items_content_type_override_works() {
+        let items = vec![
+            parse_request_item("a=1").unwrap(),
+            parse_request_item("content-type:application/vnd.api+json").unwrap(),
+        ];
+        let req = Client::new().post("http://abc.xyz/");
+        let req = apply_items(req, &items, BodyType::Json, &Upload::default()).unwrap().build().unwrap();
+        let values: Vec<_> = req.headers().get_all(header::CONTENT_TYPE).iter().collect();
+        assert_eq!(values, vec!["application/vnd.api+json"]);
+    }
+
+    #[test]
+    fn convert_body_file_works() {
+        let dir = std::env::temp_dir();
+        let yaml = dir.join("httpie-convert-test.yaml");
+        std::fs::write(&yaml, "name: kim\ntags:\n  - a\n  - b\n").unwrap();
+        let toml = dir.join("httpie-convert-test.toml");
+        std::fs::write(&toml, "name = \"kim\"\n[db]\nport = 5432\n").unwrap();
+
+        let body = convert_body_file(yaml.to_str().unwrap(), ConvertTo::Json).unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "name": "kim", "tags": ["a", "b"] }));
+
+        let body = convert_body_file(toml.to_str().unwrap(), ConvertTo::Json).unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({ "name": "kim", "db": { "port": 5432 } }));
+
+        assert!(convert_body_file("README.md", ConvertTo::Json).is_err());
+    }
+
+    #[test]
+    fn apply_items_multipart_works() {
+        let items = vec![
+            parse_request_item("manifest@Cargo.toml").unwrap(),
+            parse_request_item("name=kim").unwrap(),
+        ];
+        let req = Client::new().post("http://abc.xyz/");
+        let req = apply_items(req, &items, BodyType::Json, &Upload::default()).unwrap().build().unwrap();
+        let content_type = req.headers()[header::CONTENT_TYPE].to_str().unwrap();
+        assert!(content_type.starts_with("multipart/form-data; boundary="));
+
+        let items = vec![parse_request_item("f@does-not-exist.txt").unwrap()];
+        let req = Client::new().post("http://abc.xyz/");
+        assert!(apply_items(req, &items, BodyType::Json, &Upload::default()).is_err());
+    }
+
+    #[test]
+    fn trailer_names_works() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::TRAILER, "grpc-status, Grpc-Message".parse().unwrap());
+        headers.append(header::TRAILER, "Server-Timing".parse().unwrap());
+        assert_eq!(trailer_names(&headers), vec!["grpc-status", "grpc-message", "server-timing"]);
+        assert!(trailer_names(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn preflight_headers_works() {
+        let pairs = vec![
+            parse_kv_pair("origin=http://a.com").unwrap(),
+            parse_kv_pair("method=PUT").unwrap(),
+        ];
+        let headers = preflight_headers(&pairs).unwrap();
+        assert_eq!(headers[header::ORIGIN], "http://a.com");
+        assert_eq!(headers[header::ACCESS_CONTROL_REQUEST_METHOD], "PUT");
+        assert!(preflight_headers(&[parse_kv_pair("foo=bar").unwrap()]).is_err());
+    }
+
+    #[test]
+    fn parse_preflight_works() {
+        // --preflight 在 URL 之前时不能把 URL 当作它的值
+        let args = Options::try_parse_from([
+            "options",
+            "--preflight",
+            "origin=http://a.com",
+            "http://host/",
+            "--preflight",
+            "method=PUT",
+        ])
+        .unwrap();
+        assert_eq!(args.url, "http://host/");
+        let keys: Vec<_> = args.preflight.iter().map(|p| p.k.as_str()).collect();
+        assert_eq!(keys, vec!["origin", "method"]);
+        assert!(args.items.is_empty());
+    }
+
+    #[test]
+    fn is_cors_header_works() {
+        assert!(is_cors_header(&header::ALLOW));
+        assert!(is_cors_header(&header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert!(!is_cors_header(&header::CONTENT_TYPE));
     }
 }
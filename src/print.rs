@@ -0,0 +1,91 @@
+use std::str::FromStr;
+use anyhow::{anyhow, Result};
+
+/// --print 选择打印请求和响应的哪些部分，和 HTTPie 一样：
+/// H 是请求的 header，B 是请求的 body，h 是响应的状态和 header，b 是响应的 body
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Print {
+    pub request_headers: bool,
+    pub request_body: bool,
+    pub response_headers: bool,
+    pub response_body: bool,
+}
+
+impl Default for Print {
+    /// 默认只打印响应
+    fn default() -> Self {
+        Self {
+            request_headers: false,
+            request_body: false,
+            response_headers: true,
+            response_body: true,
+        }
+    }
+}
+
+impl Print {
+    /// -v/--verbose 打印请求和响应的所有部分
+    pub fn all() -> Self {
+        Self {
+            request_headers: true,
+            request_body: true,
+            response_headers: true,
+            response_body: true,
+        }
+    }
+
+    /// -q 时什么都不打印
+    pub fn none() -> Self {
+        Self {
+            request_headers: false,
+            request_body: false,
+            response_headers: false,
+            response_body: false,
+        }
+    }
+
+    /// 是否需要打印请求
+    pub fn request(&self) -> bool {
+        self.request_headers || self.request_body
+    }
+}
+
+impl FromStr for Print {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || anyhow!("Invalid --print {}, expected a combination of H, B, h and b", s);
+        if s.is_empty() {
+            return Err(err());
+        }
+        let mut print = Print::none();
+        for c in s.chars() {
+            match c {
+                'H' => print.request_headers = true,
+                'B' => print.request_body = true,
+                'h' => print.response_headers = true,
+                'b' => print.response_body = true,
+                _ => return Err(err()),
+            }
+        }
+        Ok(print)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_print_works() {
+        let print: Print = "Hb".parse().unwrap();
+        assert!(print.request_headers && print.response_body);
+        assert!(!print.request_body && !print.response_headers);
+        assert!(print.request());
+        assert!(!"hb".parse::<Print>().unwrap().request());
+        assert!("".parse::<Print>().is_err());
+        assert!("hx".parse::<Print>().is_err());
+        assert_eq!("HBhb".parse::<Print>().unwrap(), Print::all());
+        assert_eq!("hb".parse::<Print>().unwrap(), Print::default());
+    }
+}
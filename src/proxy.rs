@@ -0,0 +1,192 @@
+use std::env;
+use std::str::FromStr;
+use anyhow::{anyhow, Context, Result};
+use clap::Clap;
+use reqwest::{ClientBuilder, NoProxy, Proxy, Url};
+
+use crate::auth::{parse_credentials, Credentials};
+
+/// 代理相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct ProxyArgs {
+    /// Send requests through this proxy instead of the one from HTTP_PROXY / HTTPS_PROXY,
+    /// e.g. http://127.0.0.1:3128 or socks5://127.0.0.1:9050 (socks5h:// resolves host
+    /// names on the proxy). Prefix with "http:" or "https:" to proxy only that scheme,
+    /// e.g. https:http://127.0.0.1:3128
+    #[clap(long, global = true, multiple_occurrences = true, number_of_values = 1)]
+    proxy: Vec<ProxySpec>,
+    /// Basic auth credentials user:password for the proxy (you will be prompted if the
+    /// password is omitted), also used for the CONNECT tunnel of https requests
+    #[clap(long, global = true, parse(try_from_str = parse_credentials))]
+    proxy_auth: Option<Credentials>,
+    /// Comma separated hosts that are connected to directly instead of through the proxy,
+    /// e.g. localhost,.corp.example.com,10.0.0.0/8 ("*" for all hosts). Overrides the
+    /// NO_PROXY environment variable
+    #[clap(long, global = true)]
+    no_proxy: Option<String>,
+}
+
+impl ProxyArgs {
+    /// 把代理设置到 ClientBuilder 上
+    /// 没有 --proxy 时 reqwest 使用 HTTP_PROXY / HTTPS_PROXY 和 NO_PROXY 环境变量，但 reqwest
+    /// 不能给环境变量中的代理加上认证或者换掉 NO_PROXY，所以指定了 --proxy-auth 或 --no-proxy
+    /// 时自己读取环境变量
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        let auth = match &self.proxy_auth {
+            Some(credentials) => Some(prompt_password(credentials)?),
+            None => None,
+        };
+        let specs = if !self.proxy.is_empty() {
+            self.proxy.clone()
+        } else if auth.is_some() || self.no_proxy.is_some() {
+            env_proxies()?
+        } else {
+            return Ok(builder);
+        };
+        for spec in specs.iter() {
+            let mut proxy = spec.proxy()?.no_proxy(self.bypass());
+            if let Some((user, password)) = &auth {
+                proxy = proxy.basic_auth(user, password);
+            }
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder)
+    }
+
+    /// 是否通过 --proxy 指定了代理
+    pub fn has_proxy(&self) -> bool {
+        !self.proxy.is_empty()
+    }
+
+    /// 是否可能经过代理连接：--proxy，或者 reqwest 会读取的代理环境变量
+    pub fn uses_proxy(&self) -> bool {
+        let vars = ["HTTP_PROXY", "http_proxy", "HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"];
+        self.has_proxy() || vars.iter().any(|var| env::var_os(var).is_some_and(|v| !v.is_empty()))
+    }
+
+    /// 不经过代理的主机，--no-proxy 优先于 NO_PROXY 环境变量
+    fn bypass(&self) -> Option<NoProxy> {
+        match &self.no_proxy {
+            Some(hosts) => NoProxy::from_string(hosts),
+            None => NoProxy::from_env(),
+        }
+    }
+}
+
+/// 只给出了用户名时，在终端中提示输入代理的密码
+fn prompt_password(credentials: &Credentials) -> Result<(String, String)> {
+    let password = match &credentials.password {
+        Some(password) => password.clone(),
+        None => {
+            let prompt = format!("http: proxy password for {}: ", credentials.user);
+            rpassword::prompt_password(prompt)
+                .context("Failed to read password, pass it with --proxy-auth user:password")?
+        }
+    };
+    Ok((credentials.user.clone(), password))
+}
+
+/// HTTP_PROXY / HTTPS_PROXY（或者小写的 http_proxy / https_proxy）中配置的代理
+fn env_proxies() -> Result<Vec<ProxySpec>> {
+    let mut specs = Vec::new();
+    let vars = [
+        ("http", ["HTTP_PROXY", "http_proxy"]),
+        ("https", ["HTTPS_PROXY", "https_proxy"]),
+    ];
+    for (scheme, vars) in vars {
+        let value = vars.iter().filter_map(|var| env::var(var).ok()).find(|v| !v.is_empty());
+        if let Some(value) = value {
+            specs.push(ProxySpec::from_env(scheme, &value)?);
+        }
+    }
+    Ok(specs)
+}
+
+/// --proxy 的值：[http:|https:]URL，不指定 scheme 时代理所有请求
+/// URL 可以是 http、https、socks5 和 socks5h（由代理解析域名，适合 Tor）代理
+#[derive(Debug, Clone, PartialEq)]
+struct ProxySpec {
+    scheme: Option<String>,
+    url: Url,
+}
+
+impl ProxySpec {
+    fn proxy(&self) -> Result<Proxy> {
+        let url = self.url.as_str();
+        let proxy = match self.scheme.as_deref() {
+            Some("http") => Proxy::http(url)?,
+            Some("https") => Proxy::https(url)?,
+            _ => Proxy::all(url)?,
+        };
+        Ok(proxy)
+    }
+
+    /// 环境变量中的代理地址可以省略 http://
+    fn from_env(scheme: &str, value: &str) -> Result<Self> {
+        let url = if value.contains("://") {
+            value.to_string()
+        } else {
+            format!("http://{}", value)
+        };
+        format!("{}:{}", scheme, url).parse()
+    }
+}
+
+impl FromStr for ProxySpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, url) = match s.split_once(':') {
+            Some((scheme, url)) if !url.starts_with("//") => (Some(scheme), url),
+            _ => (None, s),
+        };
+        if let Some(scheme) = scheme {
+            if scheme != "http" && scheme != "https" {
+                return Err(anyhow!("Unknown --proxy scheme {}, expect http or https", scheme));
+            }
+        }
+        let url: Url = url
+            .parse()
+            .map_err(|e| anyhow!("Failed to parse proxy URL {}: {}", url, e))?;
+        if !["http", "https", "socks5", "socks5h"].contains(&url.scheme()) {
+            return Err(anyhow!(
+                "Unsupported proxy {}, expect an http, https, socks5 or socks5h URL",
+                url
+            ));
+        }
+        Ok(Self {
+            scheme: scheme.map(|s| s.to_string()),
+            url,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proxy_works() {
+        let spec: ProxySpec = "http://127.0.0.1:3128".parse().unwrap();
+        assert_eq!(spec.scheme, None);
+        assert_eq!(spec.url.as_str(), "http://127.0.0.1:3128/");
+        let spec: ProxySpec = "https:http://proxy.corp:8080".parse().unwrap();
+        assert_eq!(spec.scheme.as_deref(), Some("https"));
+        assert_eq!(spec.url.host_str(), Some("proxy.corp"));
+        let spec: ProxySpec = "socks5h://127.0.0.1:9050".parse().unwrap();
+        assert_eq!(spec.scheme, None);
+        assert_eq!(spec.url.scheme(), "socks5h");
+        assert!("ftp:http://proxy.corp".parse::<ProxySpec>().is_err());
+        assert!("ftp://proxy.corp".parse::<ProxySpec>().is_err());
+        assert!("http://".parse::<ProxySpec>().is_err());
+    }
+
+    #[test]
+    fn proxy_from_env_works() {
+        let spec = ProxySpec::from_env("https", "proxy.corp:3128").unwrap();
+        assert_eq!(spec.scheme.as_deref(), Some("https"));
+        assert_eq!(spec.url.as_str(), "http://proxy.corp:3128/");
+        let spec = ProxySpec::from_env("http", "socks5://127.0.0.1:1080").unwrap();
+        assert_eq!(spec.url.scheme(), "socks5");
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// -q 的次数，整个程序共用，各个模块打印提示和警告时检查
+static LEVEL: AtomicU64 = AtomicU64::new(0);
+
+// 是否指定了 -v
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn set(level: u64) {
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// 是否指定了 -q，这时不打印响应，也不显示进度条
+pub fn is_quiet() -> bool {
+    LEVEL.load(Ordering::Relaxed) > 0
+}
+
+/// 在 stderr 上打印提示信息，例如下载完成、压缩前后的大小，-q 时不打印
+pub fn info(msg: impl Display) {
+    if !is_quiet() {
+        eprintln!("{}", msg);
+    }
+}
+
+/// 在 stderr 上打印只有 -v 时才需要的细节，例如重试和 DoH 的解析结果，-q 时不打印
+pub fn verbose(msg: impl Display) {
+    if VERBOSE.load(Ordering::Relaxed) {
+        info(msg);
+    }
+}
+
+/// 在 stderr 上打印警告，-qq 时才不打印
+pub fn warn(msg: impl Display) {
+    if LEVEL.load(Ordering::Relaxed) < 2 {
+        eprintln!("{}", msg);
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::Result;
+use reqwest::header::{self, HeaderName, HeaderValue};
+
+const REDACTED: &str = "<redacted>";
+
+/// 打印 header 时隐藏敏感的值，默认隐藏 Authorization、Proxy-Authorization 和 Cookie，
+/// 可以用 --redact 指定更多的 header，api key 等认证信息也会隐藏
+#[derive(Debug)]
+pub struct Redactor {
+    names: Vec<HeaderName>,
+    // 打印请求行时隐藏的 query 参数
+    query: Vec<String>,
+    // --show-raw-headers，不是 ASCII 的字节显示成 \xNN
+    raw: bool,
+}
+
+impl Redactor {
+    pub fn new(extra: &[String], raw: bool) -> Result<Self> {
+        let mut names = vec![header::AUTHORIZATION, header::PROXY_AUTHORIZATION, header::COOKIE];
+        for name in extra.iter() {
+            names.push(name.parse()?);
+        }
+        Ok(Self {
+            names,
+            query: Vec::new(),
+            raw,
+        })
+    }
+
+    /// 再隐藏一个 header
+    pub fn hide_header(&mut self, name: &str) -> Result<()> {
+        self.names.push(name.parse()?);
+        Ok(())
+    }
+
+    /// 隐藏请求行中的一个 query 参数
+    pub fn hide_query(&mut self, name: &str) {
+        self.query.push(name.to_string());
+    }
+
+    /// 返回用于打印的 query，隐藏的参数只保留名字
+    pub fn query(&self, query: &str) -> String {
+        query
+            .split('&')
+            .map(|pair| {
+                let name = pair.split('=').next().unwrap_or_default();
+                let decoded = percent_encoding::percent_decode_str(name).decode_utf8_lossy();
+                match self.query.iter().any(|q| *q == decoded) {
+                    true => format!("{}={}", name, REDACTED),
+                    false => pair.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// 返回用于打印的 header 值，和 {:?} 一样带引号
+    /// 认证 header 保留认证方式，Cookie 保留 cookie 的名字，方便排查问题
+    pub fn value(&self, name: &HeaderName, value: &HeaderValue) -> String {
+        if !self.names.contains(name) {
+            return match self.raw {
+                true => format!("{:?}", value),
+                false => format!("{:?}", decode(value)),
+            };
+        }
+        let value = decode(value);
+        let value = value.as_str();
+        let redacted = if name == header::AUTHORIZATION || name == header::PROXY_AUTHORIZATION {
+            match value.find(' ') {
+                Some(pos) => format!("{} {}", &value[..pos], REDACTED),
+                None => REDACTED.to_string(),
+            }
+        } else if name == header::COOKIE {
+            value
+                .split(';')
+                .map(|cookie| match cookie.find('=') {
+                    Some(pos) => format!("{}={}", cookie[..pos].trim(), REDACTED),
+                    None => REDACTED.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("; ")
+        } else {
+            REDACTED.to_string()
+        };
+        format!("{:?}", redacted)
+    }
+}
+
+/// 把 header 的值解码成文本，不是 UTF-8 时按照 HTTP 以前的规定当作 ISO-8859-1
+pub fn decode(value: &HeaderValue) -> String {
+    match std::str::from_utf8(value.as_bytes()) {
+        Ok(s) => s.to_string(),
+        Err(_) => value.as_bytes().iter().map(|&b| b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_works() {
+        let redactor = Redactor::new(&["X-Api-Key".into()], false).unwrap();
+        let value = |name: HeaderName, value: &'static str| {
+            redactor.value(&name, &HeaderValue::from_static(value))
+        };
+        assert_eq!(value(header::AUTHORIZATION, "Bearer abc"), r#""Bearer <redacted>""#);
+        assert_eq!(value(header::COOKIE, "a=1; b=2"), r#""a=<redacted>; b=<redacted>""#);
+        assert_eq!(value(HeaderName::from_static("x-api-key"), "abc"), r#""<redacted>""#);
+        assert_eq!(value(header::ACCEPT, "*/*"), r#""*/*""#);
+        assert!(Redactor::new(&["bad header".into()], false).is_err());
+    }
+
+    #[test]
+    fn hide_works() {
+        let mut redactor = Redactor::new(&[], false).unwrap();
+        redactor.hide_header("X-Api-Key").unwrap();
+        redactor.hide_query("api_key");
+        let value = redactor.value(&HeaderName::from_static("x-api-key"), &HeaderValue::from_static("abc"));
+        assert_eq!(value, r#""<redacted>""#);
+        assert_eq!(redactor.query("a=1&api_key=abc&api_keys=2"), "a=1&api_key=<redacted>&api_keys=2");
+        assert_eq!(redactor.query("api%5Fkey=abc"), "api%5Fkey=<redacted>");
+    }
+
+    #[test]
+    fn non_ascii_value_works() {
+        let utf8 = HeaderValue::from_bytes("attachment; filename=\"报告.pdf\"".as_bytes()).unwrap();
+        let latin1 = HeaderValue::from_bytes(b"caf\xe9").unwrap();
+        let redactor = Redactor::new(&[], false).unwrap();
+        assert_eq!(
+            redactor.value(&header::CONTENT_DISPOSITION, &utf8),
+            r#""attachment; filename=\"报告.pdf\"""#
+        );
+        assert_eq!(redactor.value(&header::SERVER, &latin1), r#""café""#);
+        let redactor = Redactor::new(&[], true).unwrap();
+        assert_eq!(redactor.value(&header::SERVER, &latin1), r#""caf\xe9""#);
+        assert_eq!(redactor.value(&header::COOKIE, &latin1), r#""<redacted>""#);
+    }
+}
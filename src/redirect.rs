@@ -0,0 +1,179 @@
+use clap::{ArgEnum, Clap};
+use reqwest::header::{self, HeaderMap};
+use reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode, Url};
+
+/// 重定向相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct RedirectArgs {
+    /// Follow 30x Location redirects (the default)
+    #[clap(long, global = true, overrides_with = "no-follow")]
+    follow: bool,
+    /// Do not follow redirects, print the 30x response instead
+    #[clap(long, global = true, overrides_with = "follow")]
+    no_follow: bool,
+    /// The maximum number of redirects to follow
+    #[clap(long, global = true, default_value = "10")]
+    pub max_redirects: usize,
+    /// Print every intermediate response of the redirect chain, not only the final one
+    #[clap(long, global = true)]
+    pub all: bool,
+    /// Keep sending Authorization, Cookie and --auth credentials when redirected to a
+    /// different origin
+    #[clap(long, global = true)]
+    pub unsafe_redirect_auth: bool,
+    /// How to change the method when following a redirect. By default 303 (and 301/302
+    /// for POST) switch to GET without a body while 307/308 keep the method and body;
+    /// "preserve" always keeps them, "get" always switches to GET
+    #[clap(long, global = true, arg_enum)]
+    pub redirect_method: Option<RedirectMethod>,
+}
+
+// --redirect-method，覆盖根据状态码决定的重定向方法
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq)]
+pub enum RedirectMethod {
+    Preserve,
+    Get,
+}
+
+impl RedirectArgs {
+    pub fn follow(&self) -> bool {
+        !self.no_follow
+    }
+}
+
+/// 30x 响应中 Location 指向的地址，相对地址基于当前请求的 URL
+pub fn location(resp: &Response) -> Option<Url> {
+    if !resp.status().is_redirection() {
+        return None;
+    }
+    let location = resp.headers().get(header::LOCATION)?.to_str().ok()?;
+    resp.url().join(location).ok()
+}
+
+/// 根据上一个请求生成重定向后的请求
+/// 301/302 的 POST 和 303 的所有请求（HEAD 除外）改为不带 body 的 GET，307/308 保持方法和 body，
+/// 指定了 --redirect-method 时按照它处理
+/// 需要重新发送 body 但 body 是流（例如上传的文件）时返回 None
+/// strip_credentials 为 true 时去掉 Authorization 和 Cookie，避免把凭证泄露给其它网站
+pub fn next_request(
+    client: &Client,
+    prev: &Request,
+    status: StatusCode,
+    url: Url,
+    strip_credentials: bool,
+    method: Option<RedirectMethod>,
+) -> Option<RequestBuilder> {
+    let to_get = match (method, status) {
+        (Some(RedirectMethod::Preserve), _) => false,
+        (Some(RedirectMethod::Get), _) => true,
+        (None, StatusCode::SEE_OTHER) => prev.method() != Method::HEAD,
+        (None, StatusCode::MOVED_PERMANENTLY) | (None, StatusCode::FOUND) => {
+            prev.method() == Method::POST
+        }
+        _ => false,
+    };
+    let mut headers = prev.headers().clone();
+    if strip_credentials {
+        headers.remove(header::AUTHORIZATION);
+        headers.remove(header::COOKIE);
+    }
+    if to_get {
+        remove_body_headers(&mut headers);
+        return Some(client.get(url).headers(headers));
+    }
+    let req = client.request(prev.method().clone(), url).headers(headers);
+    match prev.body() {
+        Some(body) => Some(req.body(body.as_bytes()?.to_vec())),
+        None => Some(req),
+    }
+}
+
+/// 两个地址的 scheme、host 和端口是否相同
+pub fn same_origin(a: &Url, b: &Url) -> bool {
+    a.origin() == b.origin()
+}
+
+/// 去掉描述 body 的 header
+fn remove_body_headers(headers: &mut HeaderMap) {
+    for name in [
+        header::CONTENT_TYPE,
+        header::CONTENT_LENGTH,
+        header::CONTENT_ENCODING,
+        header::TRANSFER_ENCODING,
+    ] {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clap, Debug)]
+    struct Args {
+        #[clap(flatten)]
+        redirect: RedirectArgs,
+    }
+
+    fn parse(args: &[&str]) -> RedirectArgs {
+        let mut argv = vec!["http"];
+        argv.extend_from_slice(args);
+        Args::try_parse_from(argv).unwrap().redirect
+    }
+
+    #[test]
+    fn follow_flags_work() {
+        assert!(parse(&[]).follow());
+        assert!(!parse(&["--no-follow"]).follow());
+        // 后出现的参数覆盖前面的
+        assert!(parse(&["--no-follow", "--follow"]).follow());
+        assert!(!parse(&["--follow", "--no-follow"]).follow());
+        assert_eq!(parse(&["--max-redirects", "3"]).max_redirects, 3);
+    }
+
+    #[test]
+    fn next_request_works() {
+        let client = Client::new();
+        let prev = client
+            .post("http://abc.xyz/a")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, "Bearer abc")
+            .body("{}")
+            .build()
+            .unwrap();
+        let url: Url = "http://abc.xyz/b".parse().unwrap();
+
+        let next = |status, strip, method| {
+            next_request(&client, &prev, status, url.clone(), strip, method)
+                .unwrap()
+                .build()
+                .unwrap()
+        };
+
+        let req = next(StatusCode::FOUND, false, None);
+        assert_eq!(req.method(), Method::GET);
+        assert!(req.body().is_none());
+        assert!(req.headers().get(header::CONTENT_TYPE).is_none());
+        let req = next(StatusCode::FOUND, false, Some(RedirectMethod::Preserve));
+        assert_eq!(req.method(), Method::POST);
+
+        let status = StatusCode::TEMPORARY_REDIRECT;
+        let req = next(status, false, None);
+        assert_eq!(req.headers()[header::AUTHORIZATION], "Bearer abc");
+        assert_eq!(next(status, false, Some(RedirectMethod::Get)).method(), Method::GET);
+        let req = next(status, true, None);
+        assert!(req.headers().get(header::AUTHORIZATION).is_none());
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.body().unwrap().as_bytes(), Some(&b"{}"[..]));
+        assert_eq!(req.headers()[header::CONTENT_TYPE], "application/json");
+    }
+
+    #[test]
+    fn same_origin_works() {
+        let url = |s: &str| -> Url { s.parse().unwrap() };
+        assert!(same_origin(&url("http://abc.xyz/a"), &url("http://abc.xyz:80/b?c")));
+        assert!(!same_origin(&url("http://abc.xyz/"), &url("https://abc.xyz/")));
+        assert!(!same_origin(&url("http://abc.xyz/"), &url("http://evil.xyz/")));
+        assert!(!same_origin(&url("http://abc.xyz/"), &url("http://abc.xyz:8080/")));
+    }
+}
@@ -0,0 +1,230 @@
+use std::str::FromStr;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use clap::Clap;
+use rand::Rng;
+use reqwest::{header, Method, Response, StatusCode};
+
+use crate::client::{parse_duration, ReadTimeout};
+
+/// 重试相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct RetryArgs {
+    /// Retry up to N times on the failures given by --retry-on, -v prints each retry
+    #[clap(long, global = true, default_value = "0")]
+    pub retry: u32,
+    /// Comma separated failures to retry on: status codes (503), status classes (5xx),
+    /// connect-error and timeout
+    #[clap(long, global = true, default_value = "5xx,429,connect-error,timeout")]
+    retry_on: RetryOn,
+    /// Comma separated methods that may be retried, only idempotent methods by default
+    /// so that a POST is never sent twice
+    #[clap(long, global = true, default_value = "GET,HEAD,PUT,DELETE,OPTIONS,TRACE")]
+    retry_methods: RetryMethods,
+    /// The delay before the first retry, doubled (with jitter) for each further retry
+    #[clap(long, global = true, default_value = "1s", parse(try_from_str = parse_duration))]
+    retry_delay: Duration,
+    /// The maximum delay between two retries
+    #[clap(long, global = true, default_value = "30s", parse(try_from_str = parse_duration))]
+    retry_max_delay: Duration,
+    /// The longest delay accepted from a Retry-After header of a 429 / 503 response,
+    /// longer delays are cut to this
+    #[clap(long, global = true, default_value = "60s", parse(try_from_str = parse_duration))]
+    retry_after_limit: Duration,
+}
+
+impl RetryArgs {
+    /// 重试前等待的时间，429 / 503 响应带有 Retry-After 时使用它，否则使用退避时间
+    pub fn delay(&self, attempt: u32, result: &Result<Response>) -> Duration {
+        let retry_after = match result {
+            Ok(resp) => retry_after(resp, Utc::now()),
+            Err(_) => None,
+        };
+        match retry_after {
+            Some(delay) => delay.min(self.retry_after_limit),
+            None => self.backoff(attempt),
+        }
+    }
+
+    /// 第 attempt 次（从 0 开始）重试前等待的时间：retry_delay * 2^attempt，不超过
+    /// retry_max_delay，再乘以 [0.5, 1) 之间的随机数，避免很多客户端同时重试
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.retry_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let delay = delay.min(self.retry_max_delay);
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..1.0))
+    }
+
+    /// 是否需要重试：请求的方法在 --retry-methods 中，并且失败的原因在 --retry-on 中
+    pub fn should_retry(&self, method: &Method, result: &Result<Response>) -> bool {
+        if !self.retry_methods.0.contains(method) {
+            return false;
+        }
+        let failure = match result {
+            Ok(resp) => Failure::Status(resp.status()),
+            Err(e) => match e.downcast_ref::<reqwest::Error>() {
+                // 连接超时也属于 connect-error
+                Some(e) if e.is_connect() => Failure::ConnectError,
+                Some(e) if e.is_timeout() => Failure::Timeout,
+                None if e.downcast_ref::<ReadTimeout>().is_some() => Failure::Timeout,
+                _ => return false,
+            },
+        };
+        self.retry_on.0.iter().any(|c| c.matches(&failure))
+    }
+}
+
+/// 请求失败的原因
+enum Failure {
+    Status(StatusCode),
+    ConnectError,
+    Timeout,
+}
+
+/// --retry-on 中的一项
+#[derive(Debug, PartialEq)]
+enum RetryCondition {
+    Status(u16),
+    // 状态码的类别，例如 5xx 为 5
+    Class(u16),
+    ConnectError,
+    Timeout,
+}
+
+impl RetryCondition {
+    fn matches(&self, failure: &Failure) -> bool {
+        match (self, failure) {
+            (Self::Status(code), Failure::Status(status)) => status.as_u16() == *code,
+            (Self::Class(class), Failure::Status(status)) => status.as_u16() / 100 == *class,
+            (Self::ConnectError, Failure::ConnectError) => true,
+            (Self::Timeout, Failure::Timeout) => true,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for RetryCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "connect-error" => return Ok(Self::ConnectError),
+            "timeout" => return Ok(Self::Timeout),
+            _ => {}
+        }
+        let condition = match s.strip_suffix("xx") {
+            Some(class) => class.parse().ok().map(Self::Class),
+            None => s.parse().ok().map(Self::Status),
+        };
+        condition.ok_or_else(|| {
+            anyhow!("Failed to parse {}, expect a status code, 5xx, connect-error or timeout", s)
+        })
+    }
+}
+
+/// --retry-on 的值，逗号分隔
+#[derive(Debug, PartialEq)]
+struct RetryOn(Vec<RetryCondition>);
+
+impl FromStr for RetryOn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.split(',').map(|c| c.parse()).collect::<Result<_>>()?))
+    }
+}
+
+/// --retry-methods 的值，逗号分隔
+#[derive(Debug, PartialEq)]
+struct RetryMethods(Vec<Method>);
+
+impl FromStr for RetryMethods {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let methods = s.split(',').map(|m| m.trim().to_ascii_uppercase().parse());
+        Ok(Self(methods.collect::<Result<_, _>>()?))
+    }
+}
+
+/// 解析 429 / 503 响应的 Retry-After，可以是秒数或者 HTTP 日期
+fn retry_after(resp: &Response, now: DateTime<Utc>) -> Option<Duration> {
+    let status = resp.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    parse_retry_after(resp.headers().get(header::RETRY_AFTER)?.to_str().ok()?, now)
+}
+
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    // HTTP 日期形如 Wed, 21 Oct 2015 07:28:00 GMT，已经过去的时间不需要等待
+    let time = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((time.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+/// 重试时输出的失败原因
+pub fn describe(result: &Result<Response>) -> String {
+    match result {
+        Ok(resp) => resp.status().to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_works() {
+        let args = RetryArgs {
+            retry: 5,
+            retry_on: "5xx".parse().unwrap(),
+            retry_methods: "GET".parse().unwrap(),
+            retry_delay: Duration::from_secs(1),
+            retry_max_delay: Duration::from_secs(5),
+            retry_after_limit: Duration::from_secs(60),
+        };
+        for _ in 0..10 {
+            let delay = args.backoff(0);
+            assert!(delay >= Duration::from_millis(500) && delay < Duration::from_secs(1));
+            let delay = args.backoff(2);
+            assert!(delay >= Duration::from_secs(2) && delay < Duration::from_secs(4));
+            // 不超过 --retry-max-delay
+            assert!(args.backoff(10) < Duration::from_secs(5));
+            assert!(args.backoff(100) < Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn parse_retry_after_works() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        let now = now.with_timezone(&Utc);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn retry_on_works() {
+        let retry_on: RetryOn = "5xx, 429,connect-error".parse().unwrap();
+        let matches = |failure| retry_on.0.iter().any(|c| c.matches(&failure));
+        assert!(matches(Failure::Status(StatusCode::BAD_GATEWAY)));
+        assert!(matches(Failure::Status(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(!matches(Failure::Status(StatusCode::NOT_FOUND)));
+        assert!(matches(Failure::ConnectError));
+        assert!(!matches(Failure::Timeout));
+        assert!("5xx,oops".parse::<RetryOn>().is_err());
+
+        let methods: RetryMethods = "get,PUT".parse().unwrap();
+        assert_eq!(methods.0, vec![Method::GET, Method::PUT]);
+    }
+}
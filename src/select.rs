@@ -0,0 +1,63 @@
+use std::str::FromStr;
+use anyhow::{anyhow, Result};
+use scraper::{Html, Selector};
+
+/// --select 指定的 CSS 选择器，末尾的 ::attr(name) 表示提取属性而不是文本，
+/// 例如 'table.prices td.amount' 或者 'a.next::attr(href)'
+#[derive(Debug, Clone)]
+pub struct Select {
+    selector: Selector,
+    attr: Option<String>,
+}
+
+impl FromStr for Select {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (css, attr) = match s.trim_end().strip_suffix(')').and_then(|s| s.rsplit_once("::attr(")) {
+            Some((css, attr)) if !attr.trim().is_empty() => (css, Some(attr.trim().to_string())),
+            Some(_) => return Err(anyhow!("Invalid --select {}, missing the attribute name in ::attr()", s)),
+            None => (s, None),
+        };
+        let selector = Selector::parse(css).map_err(|e| anyhow!("Invalid --select {}: {}", s, e))?;
+        Ok(Self { selector, attr })
+    }
+}
+
+impl Select {
+    /// 按文档顺序返回所有匹配元素的文本，连续的空白合并成一个空格
+    /// 提取属性时跳过没有这个属性的元素
+    pub fn extract(&self, html: &str) -> Vec<String> {
+        let doc = Html::parse_document(html);
+        doc.select(&self.selector)
+            .filter_map(|element| match self.attr {
+                Some(ref name) => element.value().attr(name).map(|v| v.to_string()),
+                None => {
+                    let text: String = element.text().collect();
+                    Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_works() {
+        let html = r#"<table class="prices">
+            <tr><td class="name">Tea</td><td class="amount">
+                1.50 <b>EUR</b></td></tr>
+            <tr><td class="name">Coffee</td><td class="amount">2.00 EUR</td></tr>
+        </table>
+        <a class="next" href="/page/2">next</a><a class="next">no href</a>"#;
+        let select: Select = "table.prices td.amount".parse().unwrap();
+        assert_eq!(select.extract(html), vec!["1.50 EUR", "2.00 EUR"]);
+        let select: Select = "a.next::attr(href)".parse().unwrap();
+        assert_eq!(select.extract(html), vec!["/page/2"]);
+        assert!("td::attr()".parse::<Select>().is_err());
+        assert!("td[".parse::<Select>().is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::Duration;
+use colored::*;
+use mime::Mime;
+
+use crate::body;
+
+/// 服务器没有用 retry: 指定间隔时，重新连接之前等待的时间
+pub const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+/// 是否是 Server-Sent Events 的响应
+pub fn is_event_stream(mime: Option<&Mime>) -> bool {
+    mime.is_some_and(|m| m.type_() == mime::TEXT && m.subtype() == "event-stream")
+}
+
+/// 一个完整的事件，没有 event: 时类型是 message
+#[derive(Debug, PartialEq)]
+pub struct Event {
+    pub id: Option<String>,
+    pub event: String,
+    pub data: String,
+}
+
+/// 按照 HTML 标准中的规则逐行解析 text/event-stream，空行结束一个事件
+/// 最后一个 id: 在重新连接时作为 Last-Event-ID 发送
+#[derive(Debug, Default)]
+pub struct Parser {
+    event: Option<String>,
+    data: Option<String>,
+    last_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Parser {
+    /// 处理一行（不含换行符），结束一个事件时返回这个事件
+    pub fn feed(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            let event = self.event.take();
+            // 没有 data: 的事件不会分发
+            let data = self.data.take()?;
+            return Some(Event {
+                id: self.last_id.clone().filter(|id| !id.is_empty()),
+                event: event.unwrap_or_else(|| "message".into()),
+                data,
+            });
+        }
+        // : 开头的是注释，服务器常用来保持连接
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.find(':') {
+            Some(i) => (&line[..i], line[i + 1..].strip_prefix(' ').unwrap_or(&line[i + 1..])),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.into()),
+            "data" => match self.data {
+                Some(ref mut data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.into()),
+            },
+            // 空的 id: 会清除 Last-Event-ID
+            "id" if !value.contains('\0') => self.last_id = Some(value.into()),
+            "retry" => {
+                if let Ok(ms) = value.parse() {
+                    self.retry = Some(Duration::from_millis(ms));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// 重新连接时发送的 Last-Event-ID
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_id.as_deref().filter(|id| !id.is_empty())
+    }
+
+    /// 服务器用 retry: 指定的重新连接间隔
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+}
+
+/// 打印一个事件，data 是 JSON 时格式化打印
+pub fn print(event: &Event) {
+    print!("{}: {}", "event".blue(), event.event.green());
+    match event.id {
+        Some(ref id) => println!("  {}: {}", "id".blue(), id),
+        None => println!(),
+    }
+    match body::format_json(&event.data) {
+        Some(pretty) => println!("{}", pretty.cyan()),
+        None => println!("{}", event.data),
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(parser: &mut Parser, text: &str) -> Vec<Event> {
+        text.lines().filter_map(|line| parser.feed(line)).collect()
+    }
+
+    #[test]
+    fn parse_events_works() {
+        let mut parser = Parser::default();
+        let text = ": keep-alive\n\nretry: 1500\ndata: {\"a\":1}\n\nevent: update\nid: 7\ndata:first\ndata: second\n\nid\n\n";
+        let events = feed_all(&mut parser, text);
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    id: None,
+                    event: "message".into(),
+                    data: "{\"a\":1}".into()
+                },
+                Event {
+                    id: Some("7".into()),
+                    event: "update".into(),
+                    data: "first\nsecond".into()
+                },
+            ]
+        );
+        assert_eq!(parser.retry(), Some(Duration::from_millis(1500)));
+        // 最后的 id 没有值，清除了 Last-Event-ID
+        assert_eq!(parser.last_event_id(), None);
+        feed_all(&mut parser, "id: 8\n");
+        assert_eq!(parser.last_event_id(), Some("8"));
+    }
+
+    #[test]
+    fn is_event_stream_works() {
+        let mime: Mime = "text/event-stream; charset=utf-8".parse().unwrap();
+        assert!(is_event_stream(Some(&mime)));
+        assert!(!is_event_stream(Some(&mime::TEXT_PLAIN)));
+        assert!(!is_event_stream(None));
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+
+/// --limit-rate 的令牌桶，每秒补充 rate 个字节的令牌，最多存一秒的令牌
+/// 令牌不够时先欠着，等到补足为止，这样一次读取很大的块时平均速度也不会超过 rate
+#[derive(Debug)]
+pub struct RateLimit {
+    rate: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimit {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// 同样速度的另一个桶，上传和下载分别限速
+    pub fn fresh(&self) -> Self {
+        Self::new(self.rate)
+    }
+
+    /// 传输了 n 个字节，超过速度时等待
+    pub async fn take(&self, n: usize) {
+        let wait = self.debit(n, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// 补充令牌之后扣除 n 个字节，返回需要等待的时间
+    fn debit(&self, n: usize, now: Instant) -> Duration {
+        let rate = self.rate as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate) - n as f64;
+        bucket.last = now;
+        if bucket.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(-bucket.tokens / rate)
+    }
+}
+
+/// 解析每秒的字节数，支持 k、m、g 后缀（1024 进制）和小数，例如 500k、1.5m
+pub fn parse_rate(s: &str) -> Result<RateLimit> {
+    let s = s.trim();
+    let err = || anyhow!("Invalid rate {}, expected bytes per second like 500k or 2m", s);
+    let (num, scale) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let scale = match c.to_ascii_lowercase() {
+                'k' => 1024.0,
+                'm' => 1024.0 * 1024.0,
+                'g' => 1024.0 * 1024.0 * 1024.0,
+                _ => return Err(err()),
+            };
+            (&s[..i], scale)
+        }
+        _ => (s, 1.0),
+    };
+    let rate = num.parse::<f64>().map_err(|_| err())? * scale;
+    if !rate.is_finite() || rate < 1.0 {
+        return Err(err());
+    }
+    Ok(RateLimit::new(rate as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rate_works() {
+        assert_eq!(parse_rate("500k").unwrap().rate, 512_000);
+        assert_eq!(parse_rate("1.5M").unwrap().rate, 1_572_864);
+        assert_eq!(parse_rate("100").unwrap().rate, 100);
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("10x").is_err());
+        assert!(parse_rate("k").is_err());
+    }
+
+    #[test]
+    fn debit_works() {
+        let limit = RateLimit::new(1000);
+        let start = limit.bucket.lock().unwrap().last;
+        // 桶一开始是空的，500 个字节需要等 0.5 秒
+        assert_eq!(limit.debit(500, start), Duration::from_millis(500));
+        // 1.5 秒之后补充了 1 秒的令牌，还清欠下的 500 个字节之后剩下 500 个
+        let later = start + Duration::from_millis(1500);
+        assert_eq!(limit.debit(500, later), Duration::ZERO);
+        assert_eq!(limit.debit(1000, later), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,574 @@
+mod cert;
+mod keylog;
+mod ocsp;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::SystemTime;
+use anyhow::{anyhow, Context, Result};
+use clap::Clap;
+use colored::*;
+use p12_keystore::Pkcs12Archive;
+use reqwest::{Client, ClientBuilder, Url};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::version::{TLS12, TLS13};
+use rustls::{
+    Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName, SupportedCipherSuite,
+    SupportedProtocolVersion,
+};
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use cert::{CertRecorder, PeerCertificates};
+use keylog::KeyLogFile;
+use ocsp::{CertStatus, Revocation};
+use crate::quiet;
+
+/// TLS 相关的命令行参数
+#[derive(Clap, Debug)]
+pub struct TlsArgs {
+    /// Verify the server certificate: "yes" (the default), "no" to accept any
+    /// certificate, e.g. of a self-signed dev server (never use it for real traffic), or
+    /// the path of a PEM / DER CA bundle trusted in addition to the system roots
+    #[clap(long, global = true, default_value = "yes")]
+    verify: Verify,
+    /// Client certificate for mutual TLS: a PEM file (which may also contain the private
+    /// key) or a PKCS#12 (.p12 / .pfx) file
+    #[clap(long, global = true)]
+    cert: Option<PathBuf>,
+    /// The PEM private key of --cert if it is not in the same file
+    #[clap(long, global = true, requires = "cert")]
+    cert_key: Option<PathBuf>,
+    /// The password of a PKCS#12 --cert file
+    #[clap(long, global = true, requires = "cert")]
+    cert_key_pass: Option<String>,
+    /// The lowest TLS version to offer: 1.2 or 1.3
+    #[clap(long, global = true)]
+    tls_min: Option<TlsVersion>,
+    /// The highest TLS version to offer: 1.2 or 1.3
+    #[clap(long, global = true)]
+    tls_max: Option<TlsVersion>,
+    /// Comma separated cipher suites to offer, e.g.
+    /// TLS13_AES_256_GCM_SHA384,TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+    #[clap(long, global = true)]
+    ciphers: Option<CipherSuites>,
+    /// Fail unless the server's public key matches one of these pins, given as
+    /// sha256//BASE64 of the SubjectPublicKeyInfo and separated by ";"
+    #[clap(long, global = true, multiple_occurrences = true, number_of_values = 1)]
+    pin: Vec<Pins>,
+    /// Print the subject, issuer, SANs, validity and fingerprints of the certificate
+    /// chain presented by the server before the response
+    #[clap(long, global = true)]
+    pub show_cert: bool,
+    /// Check whether the server certificate is revoked with OCSP, using the response
+    /// stapled by the server or else querying the responder named in the certificate,
+    /// and print Good / Revoked / Unknown before the response
+    #[clap(long, global = true)]
+    check_revocation: bool,
+    /// Append the TLS session keys in NSS key log format to this file so that captured
+    /// traffic can be decrypted, e.g. by Wireshark. Defaults to $SSLKEYLOGFILE
+    #[clap(long, global = true)]
+    ssl_keylog: Option<PathBuf>,
+    /// Comma separated ALPN protocols to offer in the TLS handshake, "h2,http/1.1" by
+    /// default ("http/1.1" with --http1.0 / --http1.1, "h2" with --http2). Use "none" to
+    /// send no ALPN extension at all
+    #[clap(long, global = true)]
+    alpn: Option<Alpn>,
+    // TLS 握手时记录的证书链和 OCSP 响应
+    #[clap(skip)]
+    peer_certs: Arc<PeerCertificates>,
+    // 创建的 rustls 配置，TCP 和 QUIC 的客户端共用，警告只输出一次
+    #[clap(skip)]
+    config: OnceLock<ClientConfig>,
+}
+
+impl TlsArgs {
+    /// 根据命令行参数创建 rustls 的配置，交给 reqwest 使用，alpn 是没有 --alpn 时提供的协议
+    pub fn apply(&self, builder: ClientBuilder, alpn: Vec<Vec<u8>>) -> Result<ClientBuilder> {
+        Ok(builder.use_preconfigured_tls(self.config(alpn)?))
+    }
+
+    /// ws 子命令自己建立 TLS 连接时使用的配置，WebSocket 只能在 HTTP/1.1 上升级
+    pub fn ws_config(&self) -> Result<Arc<ClientConfig>> {
+        self.connect_config(vec![b"http/1.1".to_vec()])
+    }
+
+    /// 自己建立 TLS 连接（ws 子命令和 --trailers）时使用的配置，alpn 和 apply 中的一样
+    pub fn connect_config(&self, alpn: Vec<Vec<u8>>) -> Result<Arc<ClientConfig>> {
+        Ok(Arc::new(self.config(alpn)?))
+    }
+
+    fn config(&self, alpn: Vec<Vec<u8>>) -> Result<ClientConfig> {
+        let mut config = match self.config.get() {
+            Some(config) => config.clone(),
+            None => {
+                let config = self.build_config()?;
+                self.config.get_or_init(|| config).clone()
+            }
+        };
+        config.alpn_protocols = match &self.alpn {
+            Some(alpn) => alpn.0.clone(),
+            None => alpn,
+        };
+        Ok(config)
+    }
+
+    fn build_config(&self) -> Result<ClientConfig> {
+        let suites = match &self.ciphers {
+            Some(ciphers) => ciphers.0.clone(),
+            None => rustls::DEFAULT_CIPHER_SUITES.to_vec(),
+        };
+        let builder = ClientConfig::builder()
+            .with_cipher_suites(&suites)
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&self.versions()?)
+            .map_err(|e| anyhow!("Invalid --ciphers / --tls-min / --tls-max: {}", e))?
+            .with_custom_certificate_verifier(self.verifier()?);
+        let mut config = match self.identity()? {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs, key)
+                .context("Failed to use the client certificate")?,
+            None => builder.with_no_client_auth(),
+        };
+        let keylog = match &self.ssl_keylog {
+            Some(path) => Some(path.clone()),
+            None => env::var_os("SSLKEYLOGFILE").filter(|p| !p.is_empty()).map(PathBuf::from),
+        };
+        if let Some(path) = keylog {
+            quiet::warn(
+                format!("http: warning: writing TLS session keys to {}", path.display()).yellow(),
+            );
+            config.key_log = Arc::new(KeyLogFile::open(&path)?);
+        }
+        Ok(config)
+    }
+
+    /// 辅助请求（例如 DoH 查询）使用的客户端，和请求一样信任系统和 --verify 指定的 CA，
+    /// 不使用 --pin、客户端证书等针对请求的服务器的参数
+    pub fn plain_client(&self) -> Result<Client> {
+        let verifier: Arc<dyn ServerCertVerifier> = if self.verify == Verify::No {
+            Arc::new(NoVerifier)
+        } else {
+            Arc::new(WebPkiVerifier::new(self.root_store()?, None))
+        };
+        let mut config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Client::builder().use_preconfigured_tls(config).build()?)
+    }
+
+    /// 验证服务器证书的方式，指定了 --pin 时在验证证书之后再检查公钥
+    fn verifier(&self) -> Result<Arc<dyn ServerCertVerifier>> {
+        let verifier: Arc<dyn ServerCertVerifier> = if self.verify == Verify::No {
+            quiet::warn(
+                "http: warning: TLS certificate verification is disabled (--verify=no), \
+                 the connection can be intercepted"
+                    .yellow()
+                    .bold(),
+            );
+            Arc::new(NoVerifier)
+        } else {
+            Arc::new(WebPkiVerifier::new(self.root_store()?, None))
+        };
+        let verifier: Arc<dyn ServerCertVerifier> = if self.pin.is_empty() {
+            verifier
+        } else {
+            let pins = self.pin.iter().flat_map(|pins| pins.0.iter().cloned()).collect();
+            Arc::new(PinVerifier { verifier, pins })
+        };
+        if !self.show_cert && !self.check_revocation {
+            return Ok(verifier);
+        }
+        let certs = self.peer_certs.clone();
+        Ok(Arc::new(CertRecorder { verifier, certs }))
+    }
+
+    /// 打印响应所在主机的证书链（--show-cert）和证书的吊销状态（--check-revocation），
+    /// 只在建立新的连接后打印
+    pub async fn print_handshake(&self, client: &Client, url: &Url) {
+        let handshake = match self.peer_certs.take(url) {
+            Some(handshake) => handshake,
+            None => return,
+        };
+        if self.show_cert {
+            cert::print(&handshake.chain);
+        }
+        if self.check_revocation {
+            let trusted = self.trusted_certificates();
+            match ocsp::check(client, &handshake.chain, &handshake.ocsp, &trusted).await {
+                Ok(revocation) => print_revocation(&revocation),
+                Err(e) => {
+                    let message = format!("Revocation status: could not be checked: {:#}", e);
+                    println!("{}\n", message.yellow());
+                }
+            }
+        }
+    }
+
+    /// 系统的根证书和 --verify 指定的 CA 证书，用于查找服务器证书的签发者
+    fn trusted_certificates(&self) -> Vec<Certificate> {
+        let native = rustls_native_certs::load_native_certs().unwrap_or_default();
+        let mut certs: Vec<_> = native.into_iter().map(|c| Certificate(c.0)).collect();
+        if let Verify::CaBundle(path) = &self.verify {
+            certs.extend(load_certificates(path).unwrap_or_default());
+        }
+        certs
+    }
+
+    /// --tls-min 和 --tls-max 之间的 TLS 版本
+    fn versions(&self) -> Result<Vec<&'static SupportedProtocolVersion>> {
+        let min = self.tls_min.unwrap_or(TlsVersion::Tls12);
+        let max = self.tls_max.unwrap_or(TlsVersion::Tls13);
+        if min > max {
+            return Err(anyhow!("--tls-min is higher than --tls-max"));
+        }
+        let versions = [TlsVersion::Tls12, TlsVersion::Tls13];
+        Ok(versions
+            .iter()
+            .filter(|v| **v >= min && **v <= max)
+            .map(|v| v.rustls())
+            .collect())
+    }
+
+    /// 系统的根证书和 --verify 指定的 CA 证书
+    fn root_store(&self) -> Result<RootCertStore> {
+        let mut store = RootCertStore::empty();
+        // 系统的证书库中可能有 rustls 不能解析的旧证书，忽略它们
+        let native = rustls_native_certs::load_native_certs().unwrap_or_default();
+        store.add_parsable_certificates(&native.into_iter().map(|c| c.0).collect::<Vec<_>>());
+        if let Verify::CaBundle(path) = &self.verify {
+            for cert in load_certificates(path)? {
+                store
+                    .add(&cert)
+                    .with_context(|| format!("Invalid CA certificate in {}", path.display()))?;
+            }
+        }
+        Ok(store)
+    }
+
+    /// 读取 --cert / --cert-key 指定的客户端证书，不是 PEM 格式的文件当作 PKCS#12
+    fn identity(&self) -> Result<Option<(Vec<Certificate>, PrivateKey)>> {
+        let path = match &self.cert {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let cert = read(path, "client certificate")?;
+        let identity = if cert.starts_with(b"-----BEGIN") {
+            let mut pem = cert;
+            if let Some(key) = &self.cert_key {
+                pem.push(b'\n');
+                pem.extend(read(key, "client key")?);
+            }
+            parse_pem_identity(&pem)
+        } else {
+            parse_pkcs12(&cert, self.cert_key_pass.as_deref().unwrap_or_default())
+        };
+        let identity = identity
+            .with_context(|| format!("Failed to load the client certificate {}", path.display()))?;
+        Ok(Some(identity))
+    }
+}
+
+/// 打印 OCSP 查询到的吊销状态
+fn print_revocation(revocation: &Revocation) {
+    let status = match revocation.status {
+        CertStatus::Good => "good".green(),
+        CertStatus::Revoked(time, reason) => {
+            let reason = reason.map(|r| format!(", reason: {}", r)).unwrap_or_default();
+            format!("revoked at {}{}", cert::format_time(time), reason).red()
+        }
+        CertStatus::Unknown => "unknown to the OCSP responder".yellow(),
+    };
+    println!("{} {}", "Revocation status:".magenta(), status.bold());
+    println!("  {}: {}", "source".green(), revocation.source);
+    println!("  {}: {}", "this update".green(), cert::format_time(revocation.this_update));
+    if let Some(next_update) = revocation.next_update {
+        println!("  {}: {}", "next update".green(), cert::format_time(next_update));
+    }
+    println!();
+}
+
+/// 从 PEM 中读取证书链和私钥，私钥可以是 PKCS#8、PKCS#1 (RSA) 或 SEC1 (EC) 格式
+fn parse_pem_identity(pem: &[u8]) -> Result<(Vec<Certificate>, PrivateKey)> {
+    let (mut certs, mut key) = (Vec::new(), None);
+    for item in rustls_pemfile::read_all(&mut &pem[..])? {
+        match item {
+            rustls_pemfile::Item::X509Certificate(der) => certs.push(Certificate(der)),
+            rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::ECKey(der) => key = Some(PrivateKey(der)),
+            _ => {}
+        }
+    }
+    let key = key.ok_or_else(|| anyhow!("No private key found, pass it with --cert-key"))?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificate found"));
+    }
+    Ok((certs, key))
+}
+
+/// 读取 PKCS#12 文件中的证书链和私钥
+fn parse_pkcs12(data: &[u8], password: &str) -> Result<(Vec<Certificate>, PrivateKey)> {
+    let archive = Pkcs12Archive::from_pkcs12(data, password)
+        .map_err(|e| anyhow!("Failed to decode the PKCS#12 file (wrong --cert-key-pass?): {}", e))?;
+    let key = archive.keys.first().ok_or_else(|| anyhow!("No private key in the PKCS#12 file"))?;
+    let certs = archive.certs.iter().map(|bag| Certificate(bag.cert.as_der().to_vec()));
+    Ok((certs.collect(), PrivateKey(key.key.as_der().to_vec())))
+}
+
+fn read(path: &PathBuf, what: &str) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("Failed to read the {} {}", what, path.display()))
+}
+
+/// 读取 CA 证书文件，PEM 文件中可以有多个证书
+fn load_certificates(path: &PathBuf) -> Result<Vec<Certificate>> {
+    let data = read(path, "CA bundle")?;
+    let certs = if data.starts_with(b"-----BEGIN") {
+        rustls_pemfile::certs(&mut &data[..])
+            .with_context(|| format!("Failed to parse the CA bundle {}", path.display()))?
+    } else {
+        vec![data]
+    };
+    if certs.is_empty() {
+        return Err(anyhow!("No certificate found in the CA bundle {}", path.display()));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// --verify=no 时接受任何服务器证书
+struct NoVerifier;
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// 先用 verifier 验证证书，再检查证书的公钥是否和 --pin 中的一个相同
+struct PinVerifier {
+    verifier: Arc<dyn ServerCertVerifier>,
+    // SubjectPublicKeyInfo 的 SHA-256
+    pins: Vec<Vec<u8>>,
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        let pin = spki_sha256(&end_entity.0).map_err(|e| rustls::Error::General(e.to_string()))?;
+        if !self.pins.contains(&pin) {
+            return Err(rustls::Error::General(format!(
+                "the server public key sha256//{} does not match --pin",
+                base64::encode(&pin)
+            )));
+        }
+        Ok(verified)
+    }
+}
+
+/// 证书中 SubjectPublicKeyInfo 的 SHA-256，和 curl 的 --pinnedpubkey 相同
+fn spki_sha256(der: &[u8]) -> Result<Vec<u8>> {
+    let (_, cert) = X509Certificate::from_der(der)
+        .map_err(|e| anyhow!("Failed to parse the server certificate: {}", e))?;
+    Ok(Sha256::digest(cert.public_key().raw).to_vec())
+}
+
+/// --pin 的值，多个 pin 用 ; 分隔
+#[derive(Debug)]
+struct Pins(Vec<Vec<u8>>);
+
+impl FromStr for Pins {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pins = Vec::new();
+        for pin in s.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let hash = pin
+                .strip_prefix("sha256//")
+                .and_then(|hash| base64::decode(hash).ok())
+                .filter(|hash| hash.len() == 32)
+                .ok_or_else(|| anyhow!("Failed to parse pin {}, expect sha256//BASE64", pin))?;
+            pins.push(hash);
+        }
+        if pins.is_empty() {
+            return Err(anyhow!("No pin given in --pin"));
+        }
+        Ok(Self(pins))
+    }
+}
+
+/// TLS 版本，rustls 只支持 TLS 1.2 和 1.3
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    fn rustls(self) -> &'static SupportedProtocolVersion {
+        match self {
+            Self::Tls12 => &TLS12,
+            Self::Tls13 => &TLS13,
+        }
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = s.trim().to_ascii_lowercase();
+        match version.trim_start_matches("tlsv") {
+            "1.2" => Ok(Self::Tls12),
+            "1.3" => Ok(Self::Tls13),
+            "1.0" | "1.1" => Err(anyhow!("TLS {} is insecure and not supported", s)),
+            _ => Err(anyhow!("Failed to parse TLS version {}, expect 1.2 or 1.3", s)),
+        }
+    }
+}
+
+/// --ciphers 的值，使用 IANA 的名字，逗号分隔
+#[derive(Debug)]
+struct CipherSuites(Vec<SupportedCipherSuite>);
+
+impl FromStr for CipherSuites {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = |suite: &SupportedCipherSuite| format!("{:?}", suite.suite());
+        let mut suites = Vec::new();
+        for cipher in s.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+            let suite = rustls::ALL_CIPHER_SUITES
+                .iter()
+                .find(|suite| name(suite).eq_ignore_ascii_case(cipher))
+                .ok_or_else(|| {
+                    let names: Vec<_> = rustls::ALL_CIPHER_SUITES.iter().map(name).collect();
+                    anyhow!("Unknown cipher suite {}, supported: {}", cipher, names.join(", "))
+                })?;
+            suites.push(*suite);
+        }
+        if suites.is_empty() {
+            return Err(anyhow!("No cipher suite given in --ciphers"));
+        }
+        Ok(Self(suites))
+    }
+}
+
+/// --alpn 的值，逗号分隔，none 表示不发送 ALPN
+#[derive(Debug)]
+struct Alpn(Vec<Vec<u8>>);
+
+impl FromStr for Alpn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("none") {
+            return Ok(Self(Vec::new()));
+        }
+        let protocols: Vec<_> = s.split(',').map(str::trim).collect();
+        if protocols.iter().any(|p| p.is_empty() || p.len() > 255) {
+            return Err(anyhow!("Failed to parse --alpn {}, expect e.g. h2,http/1.1", s));
+        }
+        Ok(Self(protocols.iter().map(|p| p.as_bytes().to_vec()).collect()))
+    }
+}
+
+/// --verify 的值
+#[derive(Debug, PartialEq)]
+enum Verify {
+    Yes,
+    No,
+    // 额外信任的 CA 证书文件
+    CaBundle(PathBuf),
+}
+
+impl FromStr for Verify {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "yes" | "true" => Ok(Self::Yes),
+            "no" | "false" => Ok(Self::No),
+            "" => Err(anyhow!("Failed to parse --verify, expect yes, no or a CA bundle path")),
+            _ => Ok(Self::CaBundle(s.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tls_version_works() {
+        assert_eq!("1.2".parse::<TlsVersion>().unwrap(), TlsVersion::Tls12);
+        assert_eq!("TLSv1.3".parse::<TlsVersion>().unwrap(), TlsVersion::Tls13);
+        assert!("1.1".parse::<TlsVersion>().is_err());
+    }
+
+    #[test]
+    fn parse_ciphers_works() {
+        let ciphers: CipherSuites =
+            "tls13_aes_256_gcm_sha384, TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".parse().unwrap();
+        assert_eq!(ciphers.0.len(), 2);
+        assert_eq!(format!("{:?}", ciphers.0[0].suite()), "TLS13_AES_256_GCM_SHA384");
+        assert!("TLS_RSA_WITH_RC4_128_MD5".parse::<CipherSuites>().is_err());
+        assert!("".parse::<CipherSuites>().is_err());
+    }
+
+    #[test]
+    fn parse_pins_works() {
+        let hash = base64::encode([7u8; 32]);
+        let pins: Pins = format!("sha256//{}; sha256//{}", hash, hash).parse().unwrap();
+        assert_eq!(pins.0, vec![vec![7u8; 32], vec![7u8; 32]]);
+        assert!("sha1//abc".parse::<Pins>().is_err());
+        assert!("sha256//abc".parse::<Pins>().is_err());
+    }
+
+    #[test]
+    fn parse_alpn_works() {
+        let alpn: Alpn = "h2, http/1.1".parse().unwrap();
+        assert_eq!(alpn.0, vec![b"h2".to_vec(), b"http/1.1".to_vec()]);
+        assert!("None".parse::<Alpn>().unwrap().0.is_empty());
+        assert!("h2,,http/1.1".parse::<Alpn>().is_err());
+    }
+
+    #[test]
+    fn parse_verify_works() {
+        assert_eq!("yes".parse::<Verify>().unwrap(), Verify::Yes);
+        assert_eq!("NO".parse::<Verify>().unwrap(), Verify::No);
+        assert_eq!("false".parse::<Verify>().unwrap(), Verify::No);
+        assert_eq!(
+            "/etc/ssl/ca.pem".parse::<Verify>().unwrap(),
+            Verify::CaBundle("/etc/ssl/ca.pem".into())
+        );
+        assert!("".parse::<Verify>().is_err());
+    }
+}
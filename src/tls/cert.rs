@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use chrono::{TimeZone, Utc};
+use colored::*;
+use reqwest::Url;
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ServerName};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+/// TLS 握手时服务器发送的证书链和 OCSP 响应
+#[derive(Debug)]
+pub struct Handshake {
+    pub chain: Vec<Certificate>,
+    // 服务器附带（stapled）的 OCSP 响应，没有时为空
+    pub ocsp: Vec<u8>,
+}
+
+/// 按照主机保存的握手信息，用于 --show-cert 和 --check-revocation
+#[derive(Debug, Default)]
+pub struct PeerCertificates(Mutex<HashMap<String, Handshake>>);
+
+impl PeerCertificates {
+    /// 取出 url 所在主机的握手信息，每次握手只取出一次，复用的连接没有握手信息
+    pub fn take(&self, url: &Url) -> Option<Handshake> {
+        self.0.lock().unwrap().remove(&host(url)?)
+    }
+}
+
+/// 打印证书链
+pub fn print(chain: &[Certificate]) {
+    let now = Utc::now().timestamp();
+    for (i, cert) in chain.iter().enumerate() {
+        println!("{}", format!("Certificate #{}:", i).magenta());
+        match describe(&cert.0, now) {
+            Some(lines) => {
+                for (name, value) in lines {
+                    println!("  {}: {}", name.green(), value);
+                }
+            }
+            None => println!("  {}", "(failed to parse the certificate)".red()),
+        }
+    }
+    println!();
+}
+
+/// 记录服务器证书链和 OCSP 响应的 verifier，验证由 verifier 完成
+pub struct CertRecorder {
+    pub verifier: Arc<dyn ServerCertVerifier>,
+    pub certs: Arc<PeerCertificates>,
+}
+
+impl ServerCertVerifier for CertRecorder {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_string(),
+            ServerName::IpAddress(ip) => ip.to_string(),
+            _ => return Ok(verified),
+        };
+        let mut chain = vec![end_entity.clone()];
+        chain.extend_from_slice(intermediates);
+        let ocsp = ocsp_response.to_vec();
+        self.certs.0.lock().unwrap().insert(host, Handshake { chain, ocsp });
+        Ok(verified)
+    }
+}
+
+/// 和 rustls 的 ServerName 一致的主机名，IPv6 地址不带方括号
+fn host(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(host.trim_start_matches('[').trim_end_matches(']').to_string())
+}
+
+/// 证书的主题、签发者、SAN、有效期和指纹
+fn describe(der: &[u8], now: i64) -> Option<Vec<(&'static str, ColoredString)>> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let mut lines = vec![
+        ("subject", cert.subject().to_string().normal()),
+        ("issuer", cert.issuer().to_string().normal()),
+    ];
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        let names: Vec<_> = san.value.general_names.iter().map(general_name).collect();
+        lines.push(("SANs", names.join(", ").normal()));
+    }
+    let validity = cert.validity();
+    let (not_before, not_after) = (validity.not_before.timestamp(), validity.not_after.timestamp());
+    let valid = format!("{} - {}", format_time(not_before), format_time(not_after));
+    let valid = if now < not_before {
+        format!("{} (not valid yet)", valid).red()
+    } else if now > not_after {
+        format!("{} (expired)", valid).red()
+    } else {
+        format!("{} (expires in {} days)", valid, (not_after - now) / 86400).green()
+    };
+    lines.push(("valid", valid));
+    lines.push(("SHA-256", fingerprint(&Sha256::digest(der)).normal()));
+    lines.push(("SHA-1", fingerprint(&Sha1::digest(der)).normal()));
+    Some(lines)
+}
+
+fn general_name(name: &GeneralName) -> String {
+    match name {
+        GeneralName::DNSName(name) => format!("DNS:{}", name),
+        GeneralName::IPAddress(ip) => {
+            let v4 = <[u8; 4]>::try_from(*ip).ok().map(IpAddr::from);
+            let v6 = <[u8; 16]>::try_from(*ip).ok().map(IpAddr::from);
+            match v4.or(v6) {
+                Some(ip) => format!("IP:{}", ip),
+                None => name.to_string(),
+            }
+        }
+        GeneralName::RFC822Name(email) => format!("email:{}", email),
+        GeneralName::URI(uri) => format!("URI:{}", uri),
+        _ => name.to_string(),
+    }
+}
+
+pub fn format_time(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// 冒号分隔的十六进制指纹，和 openssl x509 -fingerprint 相同
+fn fingerprint(hash: &[u8]) -> String {
+    hash.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIBozCCAUigAwIBAgICEjQwCgYIKoZIzj0EAwIwFzEVMBMGA1UEAwwMdGVzdC5l
+eGFtcGxlMB4XDTI2MTAxNDAzMTUzN1oXDTM2MTAxMTAzMTUzN1owFzEVMBMGA1UE
+AwwMdGVzdC5leGFtcGxlMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAExCO2X7lb
+Pc0/YM6AG6jKezhUG34ADJirO/Ms5hnFYzU7YM2pVKySa/h5O3KoidrS/oD6La76
+zjyWgJICYvbnPaOBgzCBgDAdBgNVHQ4EFgQU308kDUVZ5hJbi21K+E2qEfcMIGEw
+HwYDVR0jBBgwFoAU308kDUVZ5hJbi21K+E2qEfcMIGEwDwYDVR0TAQH/BAUwAwEB
+/zAtBgNVHREEJjAkggx0ZXN0LmV4YW1wbGWCDioudGVzdC5leGFtcGxlhwQKAAAB
+MAoGCCqGSM49BAMCA0kAMEYCIQDZNMwMlrHwYIebEEvRI445lKjsX7uqPKuoADig
+eIgbbAIhAJ4hqKX0gF9uVmwUTmy7DO7GmexvP3kBSMy0BSNeLMan
+-----END CERTIFICATE-----";
+
+    #[test]
+    fn describe_works() {
+        colored::control::set_override(false);
+        let der = rustls_pemfile::certs(&mut CERT.as_bytes()).unwrap().remove(0);
+        // 2026-10-15 00:00:00 UTC
+        let lines = describe(&der, 1792022400).unwrap();
+        let value = |name| {
+            let line = lines.iter().find(|(n, _)| *n == name).unwrap();
+            line.1.to_string()
+        };
+        assert_eq!(value("subject"), "CN=test.example");
+        assert_eq!(value("SANs"), "DNS:test.example, DNS:*.test.example, IP:10.0.0.1");
+        assert_eq!(
+            value("valid"),
+            "2026-10-14 03:15:37 UTC - 2036-10-11 03:15:37 UTC (expires in 3649 days)"
+        );
+        assert_eq!(
+            value("SHA-256"),
+            "28:9B:E2:A6:C6:72:79:61:79:EC:0F:5D:96:7C:F9:65:\
+             9E:05:25:D3:3A:F5:AE:41:F8:81:4F:87:A1:77:1E:A3"
+        );
+        assert_eq!(value("SHA-1"), "9D:B4:03:A7:B7:BE:4A:80:41:9F:74:5A:23:03:26:AF:0F:24:C9:8B");
+        assert!(describe(&der, 2136000000).unwrap()[3].1.to_string().ends_with("(expired)"));
+    }
+
+    #[test]
+    fn host_works() {
+        let host = |url: &str| host(&url.parse().unwrap());
+        assert_eq!(host("https://Example.com:8443/a").as_deref(), Some("example.com"));
+        assert_eq!(host("https://[::1]:8443/").as_deref(), Some("::1"));
+        assert_eq!(host("https://127.0.0.1/").as_deref(), Some("127.0.0.1"));
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use colored::*;
+use rustls::KeyLog;
+
+use crate::quiet;
+
+/// 按照 NSS key log 格式把 TLS 会话密钥追加到文件中，Wireshark 可以用它解密抓到的包
+pub struct KeyLogFile(Mutex<File>);
+
+impl KeyLogFile {
+    /// 文件不存在时创建，拿到密钥就能解密流量，所以只允许当前用户读写；已经存在的文件
+    /// （也可能是 /dev/null 或者 FIFO）不改权限，其他用户可以读取时只警告
+    pub fn open(path: &Path) -> Result<Self> {
+        let context = || format!("Failed to open the TLS key log file {}", path.display());
+        let mut options = OpenOptions::new();
+        options.append(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = match options.open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let file = OpenOptions::new().append(true).open(path).with_context(context)?;
+                warn_readable(path, &file);
+                file
+            }
+            Err(e) => return Err(e).with_context(context),
+        };
+        Ok(Self(Mutex::new(file)))
+    }
+}
+
+/// 已经存在的普通文件可以被同组或者其他用户读取时警告
+#[cfg(unix)]
+fn warn_readable(path: &Path, file: &File) {
+    use std::os::unix::fs::PermissionsExt;
+    let readable = file
+        .metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o044 != 0);
+    if readable {
+        let msg = format!(
+            "http: warning: the TLS key log file {} is readable by other users",
+            path.display()
+        );
+        quiet::warn(msg.yellow());
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_readable(_path: &Path, _file: &File) {}
+
+impl KeyLog for KeyLogFile {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let line = format_line(label, client_random, secret);
+        // 写入失败不影响请求
+        let _ = self.0.lock().unwrap().write_all(line.as_bytes());
+    }
+}
+
+/// NSS key log 的一行：<label> <client random 的十六进制> <secret 的十六进制>
+fn format_line(label: &str, client_random: &[u8], secret: &[u8]) -> String {
+    format!("{} {} {}\n", label, hex(client_random), hex(secret))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_line_works() {
+        assert_eq!(
+            format_line("CLIENT_RANDOM", &[0x01, 0xab], &[0xff, 0x00, 0x10]),
+            "CLIENT_RANDOM 01ab ff0010\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_works() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("httpie-keylog-{}.txt", std::process::id()));
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        let _ = std::fs::remove_file(&path);
+        // 新建的文件只允许当前用户读写
+        KeyLogFile::open(&path).unwrap().log("CLIENT_RANDOM", &[1], &[2]);
+        assert_eq!(mode(&path), 0o600);
+        // 已经存在的文件不改权限，继续追加
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        KeyLogFile::open(&path).unwrap().log("CLIENT_RANDOM", &[3], &[4]);
+        assert_eq!(mode(&path), 0o644);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content, "CLIENT_RANDOM 01 02\nCLIENT_RANDOM 03 04\n");
+        assert!(KeyLogFile::open(Path::new("/dev/null")).is_ok());
+    }
+}
@@ -0,0 +1,572 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use reqwest::{header, Client};
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use rustls::Certificate;
+use sha1::{Digest, Sha1};
+use x509_parser::oid_registry::OID_PKIX_ACCESS_DESCRIPTOR_OCSP;
+use x509_parser::prelude::{FromDer, GeneralName, ParsedExtension, X509Certificate};
+use x509_parser::x509::SubjectPublicKeyInfo;
+
+// 用到的 OID，DER 编码后的内容
+const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const OID_OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+const OID_SHA1_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05];
+const OID_SHA256_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const OID_SHA384_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const OID_SHA512_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+
+// DER 的 tag
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OID: u8 = 0x06;
+const ENUMERATED: u8 = 0x0a;
+const GENERALIZED_TIME: u8 = 0x18;
+const SEQUENCE: u8 = 0x30;
+
+// 允许的时钟误差
+const CLOCK_SKEW: i64 = 300;
+
+/// 证书的吊销状态
+#[derive(Debug, PartialEq)]
+pub enum CertStatus {
+    Good,
+    // 吊销的时间和原因
+    Revoked(i64, Option<&'static str>),
+    Unknown,
+}
+
+/// OCSP 响应中服务器证书的状态
+#[derive(Debug, PartialEq)]
+pub struct Revocation {
+    pub status: CertStatus,
+    pub this_update: i64,
+    pub next_update: Option<i64>,
+    // stapled 或者 OCSP 服务器的地址
+    pub source: String,
+}
+
+/// 查询证书链中第一个证书（服务器证书）的吊销状态
+/// 优先使用握手时服务器附带（stapled）的 OCSP 响应，没有或者无效时向证书中的 OCSP 服务器查询，
+/// trusted 是在证书链中找不到签发者时使用的 CA 证书
+pub async fn check(
+    client: &Client,
+    chain: &[Certificate],
+    stapled: &[u8],
+    trusted: &[Certificate],
+) -> Result<Revocation> {
+    let (_, leaf) = X509Certificate::from_der(&chain[0].0)
+        .map_err(|e| anyhow!("Failed to parse the server certificate: {}", e))?;
+    let issuer = chain[1..]
+        .iter()
+        .chain(trusted.iter())
+        .filter_map(|cert| {
+            X509Certificate::from_der(&cert.0)
+                .ok()
+                .map(|(_, cert)| cert)
+        })
+        .find(|cert| cert.subject().as_raw() == leaf.issuer().as_raw())
+        .ok_or_else(|| anyhow!("The issuer of the server certificate is unknown"))?;
+    let id = CertId::new(&leaf, &issuer);
+    let now = Utc::now().timestamp();
+    let stapled = match stapled {
+        [] => None,
+        stapled => Some(parse_response(stapled, &id, &issuer, now)),
+    };
+    if let Some(Ok(status)) = stapled {
+        return Ok(Revocation {
+            source: "stapled".into(),
+            ..status
+        });
+    }
+    let url = match responder_url(&leaf) {
+        Some(url) => url,
+        None => {
+            return match stapled {
+                Some(Err(e)) => Err(e.context("Invalid stapled OCSP response")),
+                _ => Err(anyhow!("The server certificate has no OCSP responder")),
+            }
+        }
+    };
+    let resp = client
+        .post(&url)
+        .header(header::CONTENT_TYPE, "application/ocsp-request")
+        .body(id.request())
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .with_context(|| format!("Failed to query the OCSP responder {}", url))?;
+    let body = resp.bytes().await?;
+    let status = parse_response(&body, &id, &issuer, now)
+        .with_context(|| format!("Invalid OCSP response from {}", url))?;
+    Ok(Revocation {
+        source: url,
+        ..status
+    })
+}
+
+/// 证书中 Authority Information Access 扩展给出的 OCSP 服务器
+fn responder_url(cert: &X509Certificate) -> Option<String> {
+    cert.extensions()
+        .iter()
+        .find_map(|ext| match ext.parsed_extension() {
+            ParsedExtension::AuthorityInfoAccess(aia) => {
+                aia.iter()
+                    .find_map(|desc| match (&desc.access_method, &desc.access_location) {
+                        (method, GeneralName::URI(uri))
+                            if *method == OID_PKIX_ACCESS_DESCRIPTOR_OCSP =>
+                        {
+                            Some(uri.to_string())
+                        }
+                        _ => None,
+                    })
+            }
+            _ => None,
+        })
+}
+
+/// OCSP 中标识一个证书的 CertID，使用 SHA-1
+#[derive(Debug)]
+struct CertId {
+    // 签发者 Name 的 SHA-1
+    name_hash: Vec<u8>,
+    // 签发者公钥（不包含算法）的 SHA-1
+    key_hash: Vec<u8>,
+    serial: Vec<u8>,
+}
+
+impl CertId {
+    fn new(cert: &X509Certificate, issuer: &X509Certificate) -> Self {
+        Self {
+            name_hash: Sha1::digest(cert.issuer().as_raw()).to_vec(),
+            key_hash: Sha1::digest(&issuer.public_key().subject_public_key.data).to_vec(),
+            serial: cert.raw_serial().to_vec(),
+        }
+    }
+
+    /// 只查询这一个证书、不带 nonce 的 OCSPRequest
+    fn request(&self) -> Vec<u8> {
+        let algorithm = tlv(SEQUENCE, &[tlv(OID, OID_SHA1), tlv(NULL, &[])].concat());
+        let cert_id = [
+            algorithm,
+            tlv(OCTET_STRING, &self.name_hash),
+            tlv(OCTET_STRING, &self.key_hash),
+            tlv(INTEGER, &self.serial),
+        ];
+        // OCSPRequest / TBSRequest / requestList / Request / CertID
+        let mut der = tlv(SEQUENCE, &cert_id.concat());
+        for _ in 0..4 {
+            der = tlv(SEQUENCE, &der);
+        }
+        der
+    }
+
+    /// 响应中的 CertID 是否是这个证书，响应可以使用其它的哈希算法，这时只比较序列号
+    fn matches(&self, der: &[u8]) -> Result<bool> {
+        let mut reader = Reader(der);
+        let mut algorithm = Reader(reader.expect(SEQUENCE)?);
+        let sha1 = algorithm.expect(OID)? == OID_SHA1;
+        let name_hash = reader.expect(OCTET_STRING)?;
+        let key_hash = reader.expect(OCTET_STRING)?;
+        let serial = reader.expect(INTEGER)?;
+        Ok(serial == self.serial
+            && (!sha1 || (name_hash == self.name_hash && key_hash == self.key_hash)))
+    }
+}
+
+/// 解析 OCSPResponse，验证签名并找到 id 对应的证书状态
+fn parse_response(
+    der: &[u8],
+    id: &CertId,
+    issuer: &X509Certificate,
+    now: i64,
+) -> Result<Revocation> {
+    let mut reader = Reader(Reader(der).expect(SEQUENCE)?);
+    match reader.expect(ENUMERATED)? {
+        [0] => {}
+        [status] => {
+            return Err(anyhow!(
+                "The OCSP responder returned {}",
+                response_status(*status)
+            ))
+        }
+        _ => return Err(anyhow!("Malformed OCSP response")),
+    }
+    // responseBytes 是 [0] EXPLICIT
+    let mut bytes = Reader(Reader(reader.expect(0xa0)?).expect(SEQUENCE)?);
+    if bytes.expect(OID)? != OID_OCSP_BASIC {
+        return Err(anyhow!("Unsupported OCSP response type"));
+    }
+    let mut basic = Reader(Reader(bytes.expect(OCTET_STRING)?).expect(SEQUENCE)?);
+    let tbs = basic.next()?;
+    let algorithm = Reader(basic.expect(SEQUENCE)?).expect(OID)?;
+    let signature = bit_string(basic.expect(BIT_STRING)?)?;
+    let certs = match basic.optional(0xa0)? {
+        Some(certs) => Reader(Reader(certs).expect(SEQUENCE)?).all()?,
+        None => Vec::new(),
+    };
+    verify_response(tbs.raw, algorithm, signature, issuer, &certs)?;
+
+    let mut data = Reader(tbs.value);
+    data.optional(0xa0)?;
+    // responderID 是 [1] Name 或者 [2] KeyHash
+    data.next()?;
+    data.expect(GENERALIZED_TIME)?;
+    for single in Reader(data.expect(SEQUENCE)?).all()? {
+        let mut single = Reader(Reader(single).expect(SEQUENCE)?);
+        if !id.matches(single.expect(SEQUENCE)?)? {
+            continue;
+        }
+        let status = single.next()?;
+        let status = match status.tag {
+            0x80 => CertStatus::Good,
+            0xa1 => {
+                let mut info = Reader(status.value);
+                let time = generalized_time(info.expect(GENERALIZED_TIME)?)?;
+                let reason = match info.optional(0xa0)? {
+                    Some(reason) => match Reader(reason).expect(ENUMERATED)? {
+                        [reason] => crl_reason(*reason),
+                        _ => None,
+                    },
+                    None => None,
+                };
+                CertStatus::Revoked(time, reason)
+            }
+            0x82 => CertStatus::Unknown,
+            _ => return Err(anyhow!("Malformed OCSP response")),
+        };
+        let this_update = generalized_time(single.expect(GENERALIZED_TIME)?)?;
+        let next_update = match single.optional(0xa0)? {
+            Some(time) => Some(generalized_time(Reader(time).expect(GENERALIZED_TIME)?)?),
+            None => None,
+        };
+        // 过期的响应可能是攻击者重放的
+        if this_update > now + CLOCK_SKEW || next_update.is_some_and(|t| t < now - CLOCK_SKEW) {
+            return Err(anyhow!(
+                "The OCSP response is not valid at the current time"
+            ));
+        }
+        return Ok(Revocation {
+            status,
+            this_update,
+            next_update,
+            source: String::new(),
+        });
+    }
+    Err(anyhow!(
+        "The OCSP response does not contain the server certificate"
+    ))
+}
+
+/// 响应由签发者签名，或者由签发者授权（有 OCSP Signing 用途）的证书签名
+fn verify_response(
+    tbs: &[u8],
+    algorithm: &[u8],
+    signature: &[u8],
+    issuer: &X509Certificate,
+    certs: &[&[u8]],
+) -> Result<()> {
+    if verify_signature(issuer.public_key(), algorithm, tbs, signature).is_ok() {
+        return Ok(());
+    }
+    for der in certs.iter() {
+        let responder = match X509Certificate::from_der(der) {
+            Ok((_, cert)) => cert,
+            Err(_) => continue,
+        };
+        let ocsp_signing =
+            matches!(responder.extended_key_usage(), Ok(Some(eku)) if eku.value.ocsp_signing);
+        let authorized = ocsp_signing
+            && responder.issuer().as_raw() == issuer.subject().as_raw()
+            && verify_signature(
+                issuer.public_key(),
+                responder.signature_algorithm.algorithm.as_bytes(),
+                responder.tbs_certificate.as_ref(),
+                &responder.signature_value.data,
+            )
+            .is_ok();
+        if authorized && verify_signature(responder.public_key(), algorithm, tbs, signature).is_ok()
+        {
+            return Ok(());
+        }
+    }
+    Err(anyhow!("The signature of the OCSP response is invalid"))
+}
+
+/// 用 ring 验证 RSA PKCS#1 或者 ECDSA 签名
+fn verify_signature(
+    key: &SubjectPublicKeyInfo,
+    algorithm: &[u8],
+    message: &[u8],
+    sig: &[u8],
+) -> Result<()> {
+    let curve = key.algorithm.parameters.as_ref().map(|p| p.data);
+    let algorithm: &dyn VerificationAlgorithm = match (algorithm, curve) {
+        (OID_SHA1_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+        (OID_SHA256_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (OID_SHA384_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA384,
+        (OID_SHA512_RSA, _) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        (OID_ECDSA_SHA256, Some(OID_P256)) => &signature::ECDSA_P256_SHA256_ASN1,
+        (OID_ECDSA_SHA256, Some(OID_P384)) => &signature::ECDSA_P384_SHA256_ASN1,
+        (OID_ECDSA_SHA384, Some(OID_P256)) => &signature::ECDSA_P256_SHA384_ASN1,
+        (OID_ECDSA_SHA384, Some(OID_P384)) => &signature::ECDSA_P384_SHA384_ASN1,
+        _ => return Err(anyhow!("Unsupported signature algorithm")),
+    };
+    UnparsedPublicKey::new(algorithm, &key.subject_public_key.data)
+        .verify(message, sig)
+        .map_err(|_| anyhow!("Invalid signature"))
+}
+
+fn response_status(status: u8) -> &'static str {
+    match status {
+        1 => "malformedRequest",
+        2 => "internalError",
+        3 => "tryLater",
+        5 => "sigRequired",
+        6 => "unauthorized",
+        _ => "an unknown status",
+    }
+}
+
+fn crl_reason(reason: u8) -> Option<&'static str> {
+    let reason = match reason {
+        0 => "unspecified",
+        1 => "keyCompromise",
+        2 => "cACompromise",
+        3 => "affiliationChanged",
+        4 => "superseded",
+        5 => "cessationOfOperation",
+        6 => "certificateHold",
+        8 => "removeFromCRL",
+        9 => "privilegeWithdrawn",
+        10 => "aACompromise",
+        _ => return None,
+    };
+    Some(reason)
+}
+
+/// GeneralizedTime，形如 20261014031846Z，忽略秒的小数部分
+fn generalized_time(value: &[u8]) -> Result<i64> {
+    let time = std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.get(..14))
+        .and_then(|s| NaiveDateTime::parse_from_str(s, "%Y%m%d%H%M%S").ok())
+        .ok_or_else(|| anyhow!("Malformed time in the OCSP response"))?;
+    Ok(Utc.from_utc_datetime(&time).timestamp())
+}
+
+/// BIT STRING 的内容，第一个字节是未使用的位数
+fn bit_string(value: &[u8]) -> Result<&[u8]> {
+    match value {
+        [0, bits @ ..] => Ok(bits),
+        _ => Err(anyhow!("Malformed BIT STRING in the OCSP response")),
+    }
+}
+
+/// DER 编码的 tag、length、value
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    let len = value.len();
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .iter()
+            .copied()
+            .skip_while(|b| *b == 0)
+            .collect();
+        der.push(0x80 | bytes.len() as u8);
+        der.extend(bytes);
+    }
+    der.extend_from_slice(value);
+    der
+}
+
+/// 一个 DER 编码的值
+struct Tlv<'a> {
+    tag: u8,
+    value: &'a [u8],
+    // 包括 tag 和 length，用于验证签名
+    raw: &'a [u8],
+}
+
+/// 依次读取 DER 编码的值，只支持一个字节的 tag
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn next(&mut self) -> Result<Tlv<'a>> {
+        let malformed = || anyhow!("Malformed DER in the OCSP response");
+        let data = self.0;
+        let (&tag, rest) = data.split_first().ok_or_else(malformed)?;
+        let (&first, mut rest) = rest.split_first().ok_or_else(malformed)?;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 4 || rest.len() < n {
+                return Err(malformed());
+            }
+            let len = rest[..n].iter().fold(0, |len, b| len << 8 | *b as usize);
+            rest = &rest[n..];
+            len
+        };
+        if rest.len() < len {
+            return Err(malformed());
+        }
+        let header = data.len() - rest.len();
+        self.0 = &rest[len..];
+        Ok(Tlv {
+            tag,
+            value: &rest[..len],
+            raw: &data[..header + len],
+        })
+    }
+
+    /// 读取下一个值，tag 不同时出错
+    fn expect(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let tlv = self.next()?;
+        if tlv.tag != tag {
+            return Err(anyhow!(
+                "Unexpected DER tag {:#04x} in the OCSP response",
+                tlv.tag
+            ));
+        }
+        Ok(tlv.value)
+    }
+
+    /// 下一个值的 tag 相同时读取它
+    fn optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>> {
+        match self.0.first() {
+            Some(t) if *t == tag => self.expect(tag).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// SEQUENCE OF 中所有的值，包括 tag 和 length
+    fn all(&mut self) -> Result<Vec<&'a [u8]>> {
+        let mut values = Vec::new();
+        while !self.0.is_empty() {
+            values.push(self.next()?.raw);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBjzCCATagAwIBAgIUPhaRQpOjr0+3KK9snUmU13DENEowCgYIKoZIzj0EAwIw
+FzEVMBMGA1UEAwwMT0NTUCBUZXN0IENBMB4XDTI2MTAxNDAzMTgwNloXDTM2MTAx
+MTAzMTgwNlowFzEVMBMGA1UEAwwMT0NTUCBUZXN0IENBMFkwEwYHKoZIzj0CAQYI
+KoZIzj0DAQcDQgAEfhW0l+VpWWs6g5yQU5Iy8FxDkvitQWh26Yl+BsjzgHsm1AhN
+moA9gZb8pFY3fPQRSDWTKDbIxRpPoUm9rb/dfqNgMF4wHQYDVR0OBBYEFGBcDAGx
+K6JH2hKeqRb6f8LDd4JjMB8GA1UdIwQYMBaAFGBcDAGxK6JH2hKeqRb6f8LDd4Jj
+MA8GA1UdEwEB/wQFMAMBAf8wCwYDVR0PBAQDAgEGMAoGCCqGSM49BAMCA0cAMEQC
+IAFxyM8pe06ClcLYLg5WE2NnTskQtIaTW+OwexKApmQ5AiAaqLfMxJvmV+e75gZ6
+ZqDbvz1E5CbM8wUCip4lTPTysw==
+-----END CERTIFICATE-----";
+
+    // 序列号 0x1001，已经因为 keyCompromise 吊销
+    const CERT: &str = "-----BEGIN CERTIFICATE-----
+MIIByzCCAXGgAwIBAgICEAEwCgYIKoZIzj0EAwIwFzEVMBMGA1UEAwwMT0NTUCBU
+ZXN0IENBMB4XDTI2MTAxNDAzMTgwN1oXDTM2MTAxMTAzMTgwN1owHDEaMBgGA1UE
+AwwRcmV2b2tlZC5sb2NhbGhvc3QwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASj
+thqeHpBS6wfy2ATF5UazYy9Sr5d3racqfgrx59Kmck+JB9NgL6OT/aRiga2aScL5
+nO/b41jBnO4CzSd2VyxSo4GnMIGkMBoGA1UdEQQTMBGCCWxvY2FsaG9zdIcEfwAA
+ATAxBggrBgEFBQcBAQQlMCMwIQYIKwYBBQUHMAGGFWh0dHA6Ly8xMjcuMC4wLjE6
+ODg5MDATBgNVHSUEDDAKBggrBgEFBQcDATAdBgNVHQ4EFgQUCCy5za9hZ1JBhD2c
+FAeUY+SzecQwHwYDVR0jBBgwFoAUYFwMAbErokfaEp6pFvp/wsN3gmMwCgYIKoZI
+zj0EAwIDSAAwRQIgVB8S6Vv4Mw1gYTKrRClzYsehNaG90jTTfiFceHMK6sYCIQDf
+Ch650jRzeDxqVhosHyGgqsZ4+gufyygsVqldrdgIGg==
+-----END CERTIFICATE-----";
+
+    // openssl ocsp 对 CERT 的响应
+    const RESPONSE: &str = "\
+MIICugoBAKCCArMwggKvBgkrBgEFBQcwAQEEggKgMIICnDCBqaEZMBcxFTATBgNV
+BAMMDE9DU1AgVGVzdCBDQRgPMjAyNjEwMTQwMzE4NDZaMHsweTA7MAkGBSsOAwIa
+BQAEFKp+ks0HqcWPpQAzDat9ugquQINfBBRgXAwBsSuiR9oSnqkW+n/Cw3eCYwIC
+EAGhFhgPMjAyNjEwMTQwMzE4MDdaoAMKAQEYDzIwMjYxMDE0MDMxODQ2WqARGA8y
+MDI2MTAyMTAzMTg0NlowCgYIKoZIzj0EAwIDRwAwRAIgZAMbl/FU2WAVu2czp+fn
+Qs2aIDvsnusWffyv6qalrHkCIHx7czP8DvsV1GSiPfXjXK1xpJmI3tFqdnXIcLxF
+8RNXoIIBlzCCAZMwggGPMIIBNqADAgECAhQ+FpFCk6OvT7cor2ydSZTXcMQ0SjAK
+BggqhkjOPQQDAjAXMRUwEwYDVQQDDAxPQ1NQIFRlc3QgQ0EwHhcNMjYxMDE0MDMx
+ODA2WhcNMzYxMDExMDMxODA2WjAXMRUwEwYDVQQDDAxPQ1NQIFRlc3QgQ0EwWTAT
+BgcqhkjOPQIBBggqhkjOPQMBBwNCAAR+FbSX5WlZazqDnJBTkjLwXEOS+K1BaHbp
+iX4GyPOAeybUCE2agD2BlvykVjd89BFINZMoNsjFGk+hSb2tv91+o2AwXjAdBgNV
+HQ4EFgQUYFwMAbErokfaEp6pFvp/wsN3gmMwHwYDVR0jBBgwFoAUYFwMAbErokfa
+Ep6pFvp/wsN3gmMwDwYDVR0TAQH/BAUwAwEB/zALBgNVHQ8EBAMCAQYwCgYIKoZI
+zj0EAwIDRwAwRAIgAXHIzyl7ToKVwtguDlYTY2dOyRC0hpNb47B7EoCmZDkCIBqo
+t8zEm+ZX57vmBnpmoNu/PUTkJszzBQKKniVM9PKz";
+
+    fn der(pem: &str) -> Vec<u8> {
+        rustls_pemfile::certs(&mut pem.as_bytes())
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn request_works() {
+        let (ca, cert) = (der(CA), der(CERT));
+        let (_, ca) = X509Certificate::from_der(&ca).unwrap();
+        let (_, cert) = X509Certificate::from_der(&cert).unwrap();
+        assert_eq!(
+            responder_url(&cert).as_deref(),
+            Some("http://127.0.0.1:8890")
+        );
+        // 和 openssl ocsp -no_nonce -reqout 生成的请求相同
+        let hex: String = CertId::new(&cert, &ca)
+            .request()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(
+            hex,
+            "30433041303f303d303b300906052b0e03021a05000414aa7e92cd07a9c58fa500330dab7dba0aae\
+             40835f0414605c0c01b12ba247da129ea916fa7fc2c377826302021001"
+        );
+        assert_eq!(tlv(OCTET_STRING, &[0; 200])[..3], [OCTET_STRING, 0x81, 200]);
+    }
+
+    #[test]
+    fn parse_response_works() {
+        let (ca, cert) = (der(CA), der(CERT));
+        let (_, ca) = X509Certificate::from_der(&ca).unwrap();
+        let (_, cert) = X509Certificate::from_der(&cert).unwrap();
+        let id = CertId::new(&cert, &ca);
+        let response = base64::decode(RESPONSE.replace('\n', "")).unwrap();
+        // 2026-10-15 00:00:00 UTC
+        let now = 1792022400;
+        let revocation = parse_response(&response, &id, &ca, now).unwrap();
+        assert_eq!(
+            revocation.status,
+            CertStatus::Revoked(1791947887, Some("keyCompromise"))
+        );
+        assert_eq!(revocation.this_update, 1791947926);
+        assert_eq!(revocation.next_update, Some(1791947926 + 7 * 86400));
+        // 过期的响应
+        assert!(parse_response(&response, &id, &ca, now + 30 * 86400).is_err());
+        // 其它证书
+        let other = CertId {
+            serial: vec![0x10, 0x00],
+            ..CertId::new(&cert, &ca)
+        };
+        assert!(parse_response(&response, &other, &ca, now).is_err());
+        // 篡改了 thisUpdate 的响应
+        let mut tampered = response.clone();
+        let pos = tampered.windows(4).position(|w| w == b"2026").unwrap();
+        tampered[pos + 3] = b'5';
+        assert!(parse_response(&tampered, &id, &ca, now).is_err());
+        // 不成功的响应
+        let err = parse_response(&[0x30, 0x03, 0x0a, 0x01, 0x03], &id, &ca, now).unwrap_err();
+        assert_eq!(err.to_string(), "The OCSP responder returned tryLater");
+    }
+}
@@ -0,0 +1,479 @@
+use std::convert::TryFrom;
+use std::io;
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Context as _, Result};
+use hyper::body::{Bytes, HttpBody};
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, Request, Response, ResponseBuilderExt, StatusCode, Version};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_rustls::TlsConnector;
+
+use crate::dns::DnsArgs;
+use crate::tls::TlsArgs;
+use crate::ws::Io;
+
+/// 响应 head 和一行 chunk 大小、trailer 的最大长度
+const MAX_LINE: usize = 64 * 1024;
+/// 每次交给 body 的最大字节数
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// --trailers 发送的请求的响应中收到的 trailer，读完 body 之后才有值
+#[derive(Clone, Default)]
+pub struct Trailers(Arc<Mutex<Option<HeaderMap>>>);
+
+impl Trailers {
+    /// 响应中的 trailer，不是通过 --trailers 发送的请求时返回 None
+    pub fn of(resp: &Response) -> Option<Self> {
+        resp.extensions().get::<Self>().cloned()
+    }
+
+    /// 读完 body 之后取出 trailer，没有 trailer 时为空
+    pub fn take(&self) -> HeaderMap {
+        self.0.lock().unwrap().take().unwrap_or_default()
+    }
+
+    fn set(&self, trailers: HeaderMap) {
+        *self.0.lock().unwrap() = Some(trailers);
+    }
+}
+
+/// 自己建立连接发送请求并保留响应的 trailer：reqwest 读取 body 时会丢弃 trailer，hyper 的
+/// HTTP/1.1 连接解码 chunked body 时也会跳过它们。TLS 协商出 h2 或者 --http2-prior-knowledge
+/// 时通过 hyper 的 HTTP/2 连接发送，body 结束之后读取 trailer，其它情况自己发送 HTTP/1.1
+/// 请求并解码 chunked body。和 ws 子命令一样连接 dns 解析出的地址，请求的 body 需要在内存中
+pub async fn send(
+    req: Request,
+    tls: &TlsArgs,
+    dns: &DnsArgs,
+    alpn: Vec<Vec<u8>>,
+    prior_knowledge: bool,
+) -> Result<Response> {
+    let url = req.url().clone();
+    let secure = match url.scheme() {
+        "http" => false,
+        "https" => true,
+        scheme => return Err(anyhow!("Unsupported scheme {} for --trailers", scheme)),
+    };
+    let host = url.host_str().ok_or_else(|| anyhow!("Missing host in {}", url))?;
+    // IPv6 的地址带着方括号
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = url.port_or_known_default().unwrap_or(if secure { 443 } else { 80 });
+    let addrs = dns.lookup(&host, port).await?;
+    let tcp = TcpStream::connect(&addrs[..])
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+    tcp.set_nodelay(true)?;
+    let (stream, h2): (Box<dyn Io>, bool) = if secure {
+        let name = rustls::ServerName::try_from(host.as_str())
+            .map_err(|_| anyhow!("Invalid TLS server name {}", host))?;
+        let connector = TlsConnector::from(tls.connect_config(alpn)?);
+        let tls = connector
+            .connect(name, tcp)
+            .await
+            .with_context(|| format!("TLS handshake with {} failed", host))?;
+        let h2 = tls.get_ref().1.alpn_protocol() == Some(&b"h2"[..]);
+        (Box::new(tls), h2)
+    } else {
+        (Box::new(tcp), prior_knowledge)
+    };
+    let trailers = Trailers::default();
+    let resp = match h2 {
+        true => send_h2(stream, req, trailers.clone()).await?,
+        false => send_h1(stream, req, trailers.clone()).await?,
+    };
+    // resp.url() 用于重定向、下载的文件名和打印 TLS 握手
+    let (parts, body) = resp.into_parts();
+    let mut resp = hyper::Response::builder().url(url).extension(trailers).body(body)?;
+    *resp.status_mut() = parts.status;
+    *resp.version_mut() = parts.version;
+    *resp.headers_mut() = parts.headers;
+    Ok(Response::from(resp))
+}
+
+/// 请求在内存中的 body
+fn request_body(req: &Request) -> Result<Bytes> {
+    match req.body() {
+        Some(body) => body
+            .as_bytes()
+            .map(Bytes::copy_from_slice)
+            .ok_or_else(|| anyhow!("--trailers cannot send a streamed body, e.g. @file or stdin")),
+        None => Ok(Bytes::new()),
+    }
+}
+
+/// 一边接收一边交给 reqwest 的 body，读取出错时 body 也返回这个错误
+fn channel_body() -> (mpsc::Sender<io::Result<Bytes>>, reqwest::Body) {
+    let (tx, rx) = mpsc::channel(16);
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    (tx, reqwest::Body::wrap_stream(stream))
+}
+
+/// 通过 hyper 的 HTTP/2 连接发送请求，body 结束之后读取 trailer
+async fn send_h2(
+    stream: Box<dyn Io>,
+    req: Request,
+    trailers: Trailers,
+) -> Result<hyper::Response<reqwest::Body>> {
+    let (mut sender, conn) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake::<_, hyper::Body>(stream)
+        .await
+        .context("HTTP/2 handshake failed")?;
+    tokio::spawn(conn);
+    let mut builder = hyper::Request::builder()
+        .method(req.method().clone())
+        .uri(req.url().as_str())
+        .version(Version::HTTP_2);
+    // HTTP/2 中不能出现连接相关的 header，主机放在 :authority 中
+    for (name, value) in req.headers() {
+        if !is_connection_header(name) {
+            builder = builder.header(name, value);
+        }
+    }
+    let request = builder.body(hyper::Body::from(request_body(&req)?))?;
+    let (parts, mut body) = sender.send_request(request).await?.into_parts();
+    let (tx, reqwest_body) = channel_body();
+    tokio::spawn(async move {
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(io::Error::other);
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+        match body.trailers().await {
+            Ok(headers) => trailers.set(headers.unwrap_or_default()),
+            Err(e) => {
+                let _ = tx.send(Err(io::Error::other(e))).await;
+            }
+        }
+    });
+    Ok(hyper::Response::from_parts(parts, reqwest_body))
+}
+
+/// HTTP/2 不允许的连接相关的 header，TE 只能是 trailers
+fn is_connection_header(name: &HeaderName) -> bool {
+    name == header::HOST
+        || name == header::CONNECTION
+        || name == header::TRANSFER_ENCODING
+        || name == header::UPGRADE
+        || name == "keep-alive"
+        || name == "proxy-connection"
+}
+
+/// 响应 body 的长度怎么确定
+#[derive(Debug, PartialEq)]
+enum Framing {
+    Empty,
+    Length(u64),
+    Chunked,
+    Close,
+}
+
+/// 自己发送 HTTP/1.x 请求，响应的连接只用这一次，chunked body 的 trailer 在 body 结束后保存
+async fn send_h1(
+    stream: Box<dyn Io>,
+    req: Request,
+    trailers: Trailers,
+) -> Result<hyper::Response<reqwest::Body>> {
+    let mut stream = BufReader::new(stream);
+    let body = request_body(&req)?;
+    stream.get_mut().write_all(&request_head(&req, body.len())).await?;
+    stream.get_mut().write_all(&body).await?;
+    stream.get_mut().flush().await?;
+    // 跳过 100 Continue 等中间响应
+    let mut resp = loop {
+        let resp = read_head(&mut stream).await?;
+        if !resp.status().is_informational() {
+            break resp;
+        }
+    };
+    let framing = framing(req.method(), resp.status(), resp.headers())?;
+    let (tx, body) = channel_body();
+    tokio::spawn(async move {
+        if let Err(e) = read_body(&mut stream, framing, &tx, &trailers).await {
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+    *resp.body_mut() = body;
+    Ok(resp)
+}
+
+/// 请求行和 header，请求中没有 Host 时使用 URL 中的主机，body 的长度总是通过 Content-Length 发送
+fn request_head(req: &Request, len: usize) -> Vec<u8> {
+    let url = req.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let version = match req.version() {
+        Version::HTTP_10 => "HTTP/1.0",
+        _ => "HTTP/1.1",
+    };
+    let mut head = format!("{} {} {}\r\n", req.method(), target, version).into_bytes();
+    let mut headers = req.headers().clone();
+    if !headers.contains_key(header::HOST) {
+        let host = url.host_str().unwrap_or_default();
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+        headers.insert(header::HOST, HeaderValue::from_str(&host).unwrap());
+    }
+    if len > 0 || matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH) {
+        headers.insert(header::CONTENT_LENGTH, len.into());
+    }
+    headers.remove(header::TRANSFER_ENCODING);
+    for (name, value) in &headers {
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    head
+}
+
+/// 读取一行，去掉结尾的 CRLF
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut line = Vec::new();
+    let n = (&mut *reader).take(MAX_LINE as u64).read_until(b'\n', &mut line).await?;
+    if n == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    if !line.ends_with(b"\n") {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Line too long"));
+    }
+    while line.ends_with(b"\n") || line.ends_with(b"\r") {
+        line.pop();
+    }
+    Ok(line)
+}
+
+/// 读取并解析响应的状态行和 header
+async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<hyper::Response<reqwest::Body>> {
+    let mut head = Vec::new();
+    loop {
+        let line = read_line(reader).await.context("Failed to read the response head")?;
+        if line.is_empty() {
+            break;
+        }
+        if head.len() + line.len() > MAX_LINE {
+            return Err(anyhow!("The response head is too large"));
+        }
+        head.extend_from_slice(&line);
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    let mut headers = [httparse::EMPTY_HEADER; 128];
+    let mut parsed = httparse::Response::new(&mut headers);
+    parsed.parse(&head).context("Invalid response head")?;
+    let mut resp = hyper::Response::new(reqwest::Body::from(Vec::new()));
+    *resp.status_mut() = StatusCode::from_u16(parsed.code.unwrap_or_default())?;
+    *resp.version_mut() = match parsed.version {
+        Some(0) => Version::HTTP_10,
+        _ => Version::HTTP_11,
+    };
+    for h in parsed.headers.iter() {
+        resp.headers_mut()
+            .append(HeaderName::from_bytes(h.name.as_bytes())?, HeaderValue::from_bytes(h.value)?);
+    }
+    Ok(resp)
+}
+
+/// 按照 RFC 9112 6.3 确定响应 body 的长度
+fn framing(method: &Method, status: StatusCode, headers: &HeaderMap) -> Result<Framing> {
+    if method == Method::HEAD
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        return Ok(Framing::Empty);
+    }
+    let chunked = headers
+        .get_all(header::TRANSFER_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .last()
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+    if chunked {
+        return Ok(Framing::Chunked);
+    }
+    if headers.contains_key(header::TRANSFER_ENCODING) {
+        return Ok(Framing::Close);
+    }
+    match headers.get(header::CONTENT_LENGTH) {
+        Some(len) => len
+            .to_str()
+            .ok()
+            .and_then(|len| len.trim().parse().ok())
+            .map(Framing::Length)
+            .ok_or_else(|| anyhow!("Invalid Content-Length {:?}", len)),
+        None => Ok(Framing::Close),
+    }
+}
+
+/// 读取 body 交给 tx，chunked body 结束之后保存 trailer。body 不再被读取时停止
+async fn read_body<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    framing: Framing,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+    trailers: &Trailers,
+) -> io::Result<()> {
+    match framing {
+        Framing::Empty => Ok(()),
+        Framing::Length(len) => forward(reader, Some(len), tx).await.map(|_| ()),
+        Framing::Close => forward(reader, None, tx).await.map(|_| ()),
+        Framing::Chunked => {
+            loop {
+                let line = read_line(reader).await?;
+                let line = String::from_utf8_lossy(&line);
+                // 忽略 chunk 扩展
+                let size = line.split(';').next().unwrap_or_default().trim();
+                let size = u64::from_str_radix(size, 16).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid chunk size {:?}", size))
+                })?;
+                if size == 0 {
+                    break;
+                }
+                if !forward(reader, Some(size), tx).await? {
+                    return Ok(());
+                }
+                if !read_line(reader).await?.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "Missing CRLF after a chunk"));
+                }
+            }
+            trailers.set(read_trailers(reader).await?);
+            Ok(())
+        }
+    }
+}
+
+/// 最后一个 chunk 之后直到空行的 trailer
+async fn read_trailers<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<HeaderMap> {
+    let invalid = |line: &[u8]| {
+        let msg = format!("Invalid trailer {:?}", String::from_utf8_lossy(line));
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    };
+    let mut trailers = HeaderMap::new();
+    loop {
+        let line = read_line(reader).await?;
+        if line.is_empty() {
+            return Ok(trailers);
+        }
+        let colon = line.iter().position(|&b| b == b':').ok_or_else(|| invalid(&line))?;
+        let name = HeaderName::from_bytes(&line[..colon]).map_err(|_| invalid(&line))?;
+        let value = String::from_utf8_lossy(&line[colon + 1..]).trim().to_string();
+        let value = HeaderValue::from_str(&value).map_err(|_| invalid(&line))?;
+        trailers.append(name, value);
+    }
+}
+
+/// 把 len 个字节（None 时直到连接关闭）交给 tx，body 不再被读取时返回 false
+async fn forward<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    len: Option<u64>,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+) -> io::Result<bool> {
+    let mut left = len.unwrap_or(u64::MAX);
+    while left > 0 {
+        let mut buf = vec![0; CHUNK_SIZE.min(left as usize)];
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return match len {
+                Some(_) => Err(io::ErrorKind::UnexpectedEof.into()),
+                None => Ok(true),
+            };
+        }
+        buf.truncate(n);
+        left -= n as u64;
+        if tx.send(Ok(buf.into())).await.is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn framing_works() {
+        let headers = |pairs: &[(&str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.append(HeaderName::from_bytes(name.as_bytes()).unwrap(), value.parse().unwrap());
+            }
+            headers
+        };
+        let chunked = headers(&[("transfer-encoding", "gzip, chunked")]);
+        assert_eq!(framing(&Method::GET, StatusCode::OK, &chunked).unwrap(), Framing::Chunked);
+        assert_eq!(framing(&Method::HEAD, StatusCode::OK, &chunked).unwrap(), Framing::Empty);
+        let len = headers(&[("content-length", "12")]);
+        assert_eq!(framing(&Method::GET, StatusCode::OK, &len).unwrap(), Framing::Length(12));
+        assert_eq!(framing(&Method::GET, StatusCode::NOT_MODIFIED, &len).unwrap(), Framing::Empty);
+        assert_eq!(framing(&Method::GET, StatusCode::OK, &HeaderMap::new()).unwrap(), Framing::Close);
+        assert!(framing(&Method::GET, StatusCode::OK, &headers(&[("content-length", "x")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn chunked_trailers_works() {
+        let data = b"5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nServer-Timing: db;dur=53\r\ngrpc-status: 0\r\n\r\n";
+        let (tx, mut rx) = mpsc::channel(16);
+        let trailers = Trailers::default();
+        let mut reader = &data[..];
+        read_body(&mut reader, Framing::Chunked, &tx, &trailers).await.unwrap();
+        drop(tx);
+        let mut received = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            received.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(received, b"hello world");
+        let trailers = trailers.take();
+        assert_eq!(trailers["server-timing"], "db;dur=53");
+        assert_eq!(trailers["grpc-status"], "0");
+        // 缺少最后的空行时报错
+        let (tx, _rx) = mpsc::channel(16);
+        let mut reader = &b"0\r\nServer-Timing: db\r\n"[..];
+        assert!(read_body(&mut reader, Framing::Chunked, &tx, &Trailers::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn send_works() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            let mut head = String::new();
+            while !head.ends_with("\r\n\r\n") {
+                socket.read_line(&mut head).await.unwrap();
+            }
+            let resp = "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTrailer: grpc-status\r\n\
+                        Transfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\ngrpc-status: 0\r\n\r\n";
+            socket.get_mut().write_all(resp.as_bytes()).await.unwrap();
+            head
+        });
+        let url = format!("http://{}/a?b=1", addr);
+        let req = reqwest::Client::new().post(&url).header(header::TE, "trailers").body("hi").build().unwrap();
+        let tls = <TlsArgs as clap::Clap>::parse_from(["http"]);
+        let dns = <DnsArgs as clap::Clap>::parse_from(["http"]);
+        let resp = send(req, &tls, &dns, vec![], false).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.url().as_str(), url);
+        let trailers = Trailers::of(&resp).unwrap();
+        assert_eq!(resp.text().await.unwrap(), "ok");
+        assert_eq!(trailers.take()["grpc-status"], "0");
+        let head = server.await.unwrap();
+        assert!(head.starts_with("POST /a?b=1 HTTP/1.1\r\n"));
+        assert!(head.contains(&format!("host: {}\r\n", addr)));
+        assert!(head.contains("te: trailers\r\n"));
+        assert!(head.contains("content-length: 2\r\n"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::net::{SocketAddr, TcpListener as StdTcpListener};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use reqwest::{ClientBuilder, Proxy};
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// 请求行和 header 的最大长度
+const MAX_HEAD: usize = 64 * 1024;
+
+/// 通过 --unix-socket 发送所有请求
+/// reqwest 不能直接连接 Unix socket，所以在 127.0.0.1 上监听一个端口作为 reqwest 的代理，
+/// 把每个连接转发到 socket：http 请求的 absolute-form 改回 origin-form，https 的 CONNECT
+/// 直接回复 200，之后 TLS 握手在 socket 上进行
+pub fn apply(builder: ClientBuilder, path: &Path) -> Result<ClientBuilder> {
+    let addr = listen(path.to_path_buf())?;
+    // 每个连接只发送一个请求，只需要改写第一个请求行
+    Ok(builder
+        .proxy(Proxy::all(format!("http://{}", addr))?)
+        .pool_max_idle_per_host(0))
+}
+
+fn listen(path: PathBuf) -> Result<SocketAddr> {
+    let listener = StdTcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
+    let listener = TcpListener::from_std(listener)?;
+    tokio::spawn(async move {
+        while let Ok((conn, _)) = listener.accept().await {
+            let path = path.clone();
+            tokio::spawn(async move {
+                if let Err(e) = forward(conn, &path).await {
+                    eprintln!("http: --unix-socket {}: {:#}", path.display(), e);
+                }
+            });
+        }
+    });
+    Ok(addr)
+}
+
+#[cfg(unix)]
+async fn forward(mut conn: TcpStream, path: &Path) -> Result<()> {
+    let head = read_head(&mut conn).await?;
+    let mut socket = tokio::net::UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    if head.starts_with(b"CONNECT ") {
+        conn.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await?;
+    } else {
+        socket.write_all(&origin_form(&head)?).await?;
+    }
+    io::copy_bidirectional(&mut conn, &mut socket).await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn forward(_conn: TcpStream, _path: &Path) -> Result<()> {
+    Err(anyhow!("Unix domain sockets are not supported on this platform"))
+}
+
+/// 读取到请求 header 结束为止，可能包括一部分 body
+async fn read_head(conn: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0; 4096];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD {
+            return Err(anyhow!("The request header is too large"));
+        }
+        let n = conn.read(&mut buf).await?;
+        if n == 0 {
+            return Err(anyhow!("The connection was closed before the request was sent"));
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(head)
+}
+
+/// 把发给代理的请求行 GET http://localhost/containers/json HTTP/1.1 改为
+/// GET /containers/json HTTP/1.1，Host header 保持不变
+fn origin_form(head: &[u8]) -> Result<Vec<u8>> {
+    let end = head.windows(2).position(|w| w == b"\r\n").unwrap_or(head.len());
+    let line = std::str::from_utf8(&head[..end]).context("Malformed request line")?;
+    let mut parts = line.splitn(3, ' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => return Err(anyhow!("Malformed request line {}", line)),
+    };
+    // 去掉 scheme 和 authority，只有 query 时补上 /
+    let target = match target.split_once("://") {
+        Some((_, rest)) => match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('/') => rest[i..].to_string(),
+            Some(i) => format!("/{}", &rest[i..]),
+            None => "/".to_string(),
+        },
+        None => target.to_string(),
+    };
+    let mut request = format!("{} {} {}", method, target, version).into_bytes();
+    request.extend_from_slice(&head[end..]);
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origin_form_works() {
+        let head = b"GET http://localhost/containers/json?all=1 HTTP/1.1\r\nhost: localhost\r\n\r\n";
+        assert_eq!(
+            origin_form(head).unwrap(),
+            b"GET /containers/json?all=1 HTTP/1.1\r\nhost: localhost\r\n\r\n".to_vec()
+        );
+        let head = b"POST http://docker:2375 HTTP/1.1\r\n\r\n{}";
+        assert_eq!(origin_form(head).unwrap(), b"POST / HTTP/1.1\r\n\r\n{}".to_vec());
+        let head = b"GET http://localhost?a=1 HTTP/1.1\r\n\r\n";
+        assert_eq!(origin_form(head).unwrap(), b"GET /?a=1 HTTP/1.1\r\n\r\n".to_vec());
+        assert!(origin_form(b"GET\r\n\r\n").is_err());
+    }
+}
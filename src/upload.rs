@@ -0,0 +1,248 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use hyper::body::Bytes;
+use indicatif::ProgressBar;
+use reqwest::{header, Body, RequestBuilder};
+
+use crate::compress;
+use crate::download::progress_bar;
+use crate::throttle::RateLimit;
+
+// 内存中的 body 限速发送时每一块的大小
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// 包装上传的 body，--limit-rate 时限速发送，上传文件时显示进度条，--compress 时用 gzip 压缩，
+/// --expect-continue 时先等待一段固定的时间，让服务器有机会拒绝请求
+#[derive(Clone, Default)]
+pub struct Upload {
+    limit: Option<Arc<RateLimit>>,
+    // 上传文件的进度条，multipart 中的多个文件共用一个
+    bar: Arc<Mutex<Option<ProgressBar>>>,
+    // --compress
+    gzip: bool,
+    // --expect-continue 时发送 body 之前等待的时间
+    expect: Option<Duration>,
+    // 当前请求流式的 body 是否已经等待过，multipart 中只有第一个文件需要等待，
+    // 每个请求用 for_request 重新开始
+    waited: Arc<AtomicBool>,
+}
+
+impl Upload {
+    pub fn new(limit: Option<RateLimit>, gzip: bool, expect: Option<Duration>) -> Self {
+        Self {
+            limit: limit.map(Arc::new),
+            bar: Default::default(),
+            gzip,
+            expect,
+            waited: Default::default(),
+        }
+    }
+
+    /// 为一个新的请求包装 body，重新开始 --expect-continue 的等待
+    pub fn for_request(&self) -> Self {
+        Self {
+            waited: Default::default(),
+            ..self.clone()
+        }
+    }
+
+    pub fn gzip(&self) -> bool {
+        self.gzip
+    }
+
+    /// 包装流式的 body
+    pub fn stream<S>(&self, stream: S) -> Body
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        self.throttle(stream)
+    }
+
+    /// 包装 len 个字节的文件内容，发送时更新进度条
+    pub fn file<S>(&self, stream: S, len: u64) -> Body
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        self.throttle(self.progress(stream, len))
+    }
+
+    /// 以流作为请求的 body，知道长度 len 时显示进度条，--compress 时边发送边压缩
+    pub fn body<S>(&self, req: RequestBuilder, stream: S, len: Option<u64>) -> RequestBuilder
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        match (self.gzip, len) {
+            (false, Some(len)) => req
+                .header(header::CONTENT_LENGTH, len)
+                .body(self.file(stream, len)),
+            (false, None) => req.body(self.stream(stream)),
+            // 压缩之后的长度事先不知道，使用 chunked 编码
+            (true, Some(len)) => req
+                .header(header::CONTENT_ENCODING, "gzip")
+                .body(self.throttle(compress::gzip_stream(self.progress(stream, len)))),
+            (true, None) => req
+                .header(header::CONTENT_ENCODING, "gzip")
+                .body(self.throttle(compress::gzip_stream(stream))),
+        }
+    }
+
+    /// --compress 时压缩内存中的 body，流式的 body 在 body() 中压缩
+    pub fn compress(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        if !self.gzip {
+            return Ok(req);
+        }
+        let (client, req) = req.build_split();
+        let mut req = req?;
+        let data = match req.body().and_then(|body| body.as_bytes()) {
+            Some(data) => compress::gzip(data)?,
+            None => return Ok(RequestBuilder::from_parts(client, req)),
+        };
+        req.headers_mut()
+            .insert(header::CONTENT_ENCODING, header::HeaderValue::from_static("gzip"));
+        *req.body_mut() = Some(data.into());
+        Ok(RequestBuilder::from_parts(client, req))
+    }
+
+    /// 发送时更新进度条
+    fn progress<S>(&self, stream: S, len: u64) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        let mut bar = self.bar.lock().unwrap();
+        // 上一个进度条已经完成时重新开始
+        let bar = match *bar {
+            Some(ref bar) if !bar.is_finished() => {
+                bar.inc_length(len);
+                bar.clone()
+            }
+            _ => bar.insert(progress_bar(Some(len)).with_prefix("upload ")).clone(),
+        };
+        stream.inspect(move |chunk| {
+            if let Ok(chunk) = chunk {
+                bar.inc(chunk.len() as u64);
+                if bar.length().is_some_and(|len| bar.position() >= len) {
+                    bar.finish_and_clear();
+                }
+            }
+        })
+    }
+
+    fn throttle<S>(&self, stream: S) -> Body
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        self.send(stream, self.waited.clone())
+    }
+
+    /// --expect-continue 时第一块数据先等待固定的时间再发送，--limit-rate 时限速
+    /// hyper 会丢弃 100 Continue，所以不能等它，只能等待固定的时间，服务器在这期间返回的 4xx
+    /// 会直接作为响应，不会发送 body
+    fn send<S>(&self, stream: S, waited: Arc<AtomicBool>) -> Body
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        if self.limit.is_none() && self.expect.is_none() {
+            return Body::wrap_stream(stream);
+        }
+        let limit = self.limit.clone();
+        let expect = self.expect;
+        Body::wrap_stream(stream.then(move |chunk| {
+            let limit = limit.clone();
+            let waited = waited.clone();
+            async move {
+                if let Some(timeout) = expect {
+                    if !waited.swap(true, Ordering::Relaxed) {
+                        tokio::time::sleep(timeout).await;
+                    }
+                }
+                if let (Some(limit), Ok(chunk)) = (limit, &chunk) {
+                    limit.take(chunk.len()).await;
+                }
+                chunk
+            }
+        }))
+    }
+
+    /// --expect-continue 时有 body 的请求都加上 Expect: 100-continue
+    pub fn expect(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        if self.expect.is_none() {
+            return Ok(req);
+        }
+        let (client, req) = req.build_split();
+        let mut req = req?;
+        if req.body().is_some() {
+            req.headers_mut()
+                .insert(header::EXPECT, header::HeaderValue::from_static("100-continue"));
+        }
+        Ok(RequestBuilder::from_parts(client, req))
+    }
+
+    /// 内存中的 body 在每次发送之前才转换成限速的流，原来的请求仍然可以克隆，用于重试和重定向
+    /// 同时加上 expect 中的 Expect header
+    pub fn apply(&self, req: RequestBuilder) -> Result<RequestBuilder> {
+        if self.limit.is_none() && self.expect.is_none() {
+            return Ok(req);
+        }
+        let (client, req) = self.expect(req)?.build_split();
+        let mut req = req?;
+        let data = match req.body().and_then(|body| body.as_bytes()) {
+            Some(data) => Bytes::copy_from_slice(data),
+            None => return Ok(RequestBuilder::from_parts(client, req)),
+        };
+        req.headers_mut().insert(header::CONTENT_LENGTH, data.len().into());
+        // 每次发送都重新等待
+        let stream = futures::stream::iter(chunks(data));
+        *req.body_mut() = Some(self.send(stream, Default::default()));
+        Ok(RequestBuilder::from_parts(client, req))
+    }
+}
+
+/// 把内存中的数据切成 CHUNK_SIZE 大小的块
+fn chunks(data: Bytes) -> Vec<io::Result<Bytes>> {
+    (0..data.len())
+        .step_by(CHUNK_SIZE)
+        .map(|i| Ok(data.slice(i..data.len().min(i + CHUNK_SIZE))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_works() {
+        let data = Bytes::from(vec![7u8; CHUNK_SIZE * 2 + 10]);
+        let lens: Vec<usize> = chunks(data).into_iter().map(|c| c.unwrap().len()).collect();
+        assert_eq!(lens, vec![CHUNK_SIZE, CHUNK_SIZE, 10]);
+        assert!(chunks(Bytes::new()).is_empty());
+    }
+
+    #[test]
+    fn apply_expect_works() {
+        let upload = Upload::new(None, false, Some(Duration::from_secs(1)));
+        let req = reqwest::Client::new().post("http://abc.xyz/").body("data");
+        let req = upload.apply(req).unwrap().build().unwrap();
+        assert_eq!(req.headers()[header::EXPECT], "100-continue");
+        assert_eq!(req.headers()[header::CONTENT_LENGTH], "4");
+        // 只加上 header 时 body 仍然在内存中，可以打印
+        let req = reqwest::Client::new().post("http://abc.xyz/").body("data");
+        let req = upload.expect(req).unwrap().build().unwrap();
+        assert_eq!(req.headers()[header::EXPECT], "100-continue");
+        assert_eq!(req.body().and_then(|body| body.as_bytes()), Some(&b"data"[..]));
+        let req = upload.apply(reqwest::Client::new().get("http://abc.xyz/")).unwrap();
+        assert!(req.build().unwrap().headers().get(header::EXPECT).is_none());
+    }
+
+    #[test]
+    fn for_request_works() {
+        let upload = Upload::new(None, false, Some(Duration::from_secs(1)));
+        upload.waited.store(true, Ordering::Relaxed);
+        let next = upload.for_request();
+        assert!(!next.waited.load(Ordering::Relaxed));
+        assert_eq!(next.expect, upload.expect);
+    }
+}
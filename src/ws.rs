@@ -0,0 +1,172 @@
+use std::convert::TryFrom;
+use std::io::BufRead;
+use anyhow::{anyhow, Context as _, Result};
+use chrono::Local;
+use colored::*;
+use futures::{SinkExt, StreamExt};
+use reqwest::header::HeaderMap;
+use reqwest::Url;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::body;
+use crate::dns::DnsArgs;
+use crate::tls::TlsArgs;
+
+/// 明文或者 TLS 的连接
+pub trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// 建立 WebSocket 连接，http:// 和 https:// 分别当作 ws:// 和 wss://
+/// wss:// 使用和其它请求同样的 TLS 参数，headers 是握手请求中额外的 header（包括认证），
+/// 连接 dns 解析出的地址，URL 中的主机用作 SNI
+pub async fn connect(
+    url: &Url,
+    headers: HeaderMap,
+    tls: &TlsArgs,
+    dns: &DnsArgs,
+) -> Result<(WebSocketStream<Box<dyn Io>>, Response)> {
+    let secure = match url.scheme() {
+        "ws" | "http" => false,
+        "wss" | "https" => true,
+        scheme => return Err(anyhow!("Unsupported scheme {}, expected ws:// or wss://", scheme)),
+    };
+    let mut url = url.clone();
+    // ws 和 http 都是特殊的 scheme，可以互相转换
+    let _ = url.set_scheme(if secure { "wss" } else { "ws" });
+    let host = url.host_str().ok_or_else(|| anyhow!("Missing host in {}", url))?;
+    // IPv6 的地址带着方括号
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = url.port_or_known_default().unwrap_or(if secure { 443 } else { 80 });
+    let addrs = dns.lookup(&host, port).await?;
+    let tcp = TcpStream::connect(&addrs[..])
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+    tcp.set_nodelay(true)?;
+    let stream: Box<dyn Io> = if secure {
+        let name = rustls::ServerName::try_from(host.as_str())
+            .map_err(|_| anyhow!("Invalid TLS server name {}", host))?;
+        let connector = TlsConnector::from(tls.ws_config()?);
+        let tls = connector
+            .connect(name, tcp)
+            .await
+            .with_context(|| format!("TLS handshake with {} failed", host))?;
+        Box::new(tls)
+    } else {
+        Box::new(tcp)
+    };
+    let mut req = url.as_str().into_client_request()?;
+    req.headers_mut().extend(headers);
+    tokio_tungstenite::client_async(req, stream)
+        .await
+        .context("WebSocket handshake failed")
+}
+
+/// 先发送 --send 指定的消息，然后把 stdin 的每一行作为一条消息发送，同时打印收到的消息，
+/// 直到服务器关闭连接。stdin 读完之后发送 Close，stdin 为 false 时一直接收
+pub async fn session(
+    socket: WebSocketStream<Box<dyn Io>>,
+    messages: &[String],
+    stdin: bool,
+    print: bool,
+) -> Result<()> {
+    let (mut sink, mut stream) = socket.split();
+    for msg in messages {
+        let msg = Message::Text(msg.clone());
+        if print {
+            print_message(false, &msg);
+        }
+        sink.send(msg).await?;
+    }
+    let mut lines = match stdin {
+        true => Some(read_lines()),
+        false => None,
+    };
+    loop {
+        tokio::select! {
+            line = next_line(&mut lines) => {
+                let msg = match line {
+                    Some(line) => Message::Text(line),
+                    None => {
+                        lines = None;
+                        Message::Close(None)
+                    }
+                };
+                if print {
+                    print_message(false, &msg);
+                }
+                sink.send(msg).await?;
+            }
+            msg = stream.next() => match msg {
+                Some(msg) => {
+                    let msg = msg?;
+                    if print {
+                        print_message(true, &msg);
+                    }
+                    // 服务器在 Close 之后可能直接断开 TCP 连接，不再读取
+                    if let Message::Close(_) = msg {
+                        let _ = sink.close().await;
+                        return Ok(());
+                    }
+                }
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+// 在单独的线程中读取 stdin，tokio 的 stdin 在阻塞的读取结束之前不会让程序退出
+fn read_lines() -> mpsc::UnboundedReceiver<String> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let sent = line.map(|line| tx.send(line).is_ok());
+            if !matches!(sent, Ok(true)) {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// stdin 的下一行，读完之后返回 None，之后不再返回
+async fn next_line(lines: &mut Option<mpsc::UnboundedReceiver<String>>) -> Option<String> {
+    match lines {
+        Some(lines) => lines.recv().await,
+        None => futures::future::pending().await,
+    }
+}
+
+/// 带着时间打印一条消息，< 是收到的，> 是发送的，文本消息是 JSON 时格式化打印
+fn print_message(incoming: bool, msg: &Message) {
+    let time = Local::now().format("%H:%M:%S%.3f").to_string().dimmed();
+    let dir = match incoming {
+        true => "<".blue().bold(),
+        false => ">".green().bold(),
+    };
+    match msg {
+        Message::Text(text) => match body::format_json(text) {
+            Some(pretty) => println!("{} {} {}", time, dir, pretty.cyan()),
+            None => println!("{} {} {}", time, dir, text),
+        },
+        Message::Binary(data) => {
+            let msg = format!("+ Binary message ({} bytes) +", data.len());
+            println!("{} {} {}", time, dir, msg.yellow());
+        }
+        Message::Ping(_) => println!("{} {} {}", time, dir, "ping".dimmed()),
+        Message::Pong(_) => println!("{} {} {}", time, dir, "pong".dimmed()),
+        Message::Close(Some(frame)) => {
+            let msg = format!("close {} {}", u16::from(frame.code), frame.reason);
+            println!("{} {} {}", time, dir, msg.trim_end().yellow());
+        }
+        Message::Close(None) => println!("{} {} {}", time, dir, "close".yellow()),
+        Message::Frame(_) => {}
+    }
+}
@@ -0,0 +1,152 @@
+use colored::*;
+use mime::Mime;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+// 每一层缩进的空格数，和 JSON 一样
+const INDENT: usize = 2;
+
+/// 是否是 XML 的 body，包括 application/atom+xml 等带 +xml 后缀的类型
+pub fn is_xml(mime: Option<&Mime>) -> bool {
+    mime.is_some_and(|m| {
+        matches!(m.essence_str(), "application/xml" | "text/xml") || m.suffix() == Some(mime::XML)
+    })
+}
+
+// 上一个输出的内容，决定下一个内容是否换行
+#[derive(PartialEq)]
+enum Last {
+    Start,
+    // 紧跟在开始标签后面的文本，结束标签写在同一行
+    InlineText,
+    Other,
+}
+
+/// 重新缩进 XML 文档并高亮标签和属性，只包含文本的元素写在一行，例如 <id>1</id>
+/// 文本和属性保持原来的转义，不是合法的 XML 时返回 None
+pub fn format(s: &str) -> Option<String> {
+    let mut reader = Reader::from_str(s);
+    reader.config_mut().trim_text(true);
+    let mut out = String::new();
+    let mut depth: usize = 0;
+    let mut last = Last::Other;
+    // 至少要有一个元素
+    let mut element = false;
+    loop {
+        let event = reader.read_event().ok()?;
+        if let Event::End(_) = event {
+            depth = depth.checked_sub(1)?;
+        }
+        let inline = match event {
+            Event::End(_) => last != Last::Other,
+            Event::Text(_) => last == Last::Start,
+            _ => false,
+        };
+        if !inline && !matches!(event, Event::Eof) {
+            out.push('\n');
+            out.push_str(&" ".repeat(depth * INDENT));
+        }
+        last = Last::Other;
+        match event {
+            Event::Start(ref e) => {
+                element = true;
+                out.push_str(&start_tag(e, false));
+                depth += 1;
+                last = Last::Start;
+            }
+            Event::Empty(ref e) => {
+                element = true;
+                out.push_str(&start_tag(e, true));
+            }
+            Event::End(ref e) => {
+                let tag = format!("</{}>", String::from_utf8_lossy(e.name().as_ref()));
+                out.push_str(&tag.blue().to_string());
+            }
+            Event::Text(ref e) => {
+                out.push_str(&String::from_utf8_lossy(e));
+                if inline {
+                    last = Last::InlineText;
+                }
+            }
+            Event::CData(ref e) => {
+                let cdata = format!("<![CDATA[{}]]>", String::from_utf8_lossy(e));
+                out.push_str(&cdata.dimmed().to_string());
+            }
+            Event::Comment(ref e) => {
+                let comment = format!("<!--{}-->", String::from_utf8_lossy(e));
+                out.push_str(&comment.dimmed().to_string());
+            }
+            Event::Decl(ref e) => {
+                let decl = format!("<?{}?>", String::from_utf8_lossy(e));
+                out.push_str(&decl.dimmed().to_string());
+            }
+            Event::PI(ref e) => {
+                let pi = format!("<?{}?>", String::from_utf8_lossy(e));
+                out.push_str(&pi.dimmed().to_string());
+            }
+            Event::DocType(ref e) => {
+                let doctype = format!("<!DOCTYPE {}>", String::from_utf8_lossy(e).trim());
+                out.push_str(&doctype.dimmed().to_string());
+            }
+            Event::Eof => break,
+        }
+    }
+    // 截断的文档在没有关闭的元素之后就结束了
+    if depth > 0 || !element {
+        return None;
+    }
+    Some(out.trim_start_matches('\n').to_string())
+}
+
+/// 开始标签或者空元素的标签，标签名蓝色，属性名绿色，属性值黄色
+fn start_tag(e: &BytesStart, empty: bool) -> String {
+    let mut tag = format!("<{}", String::from_utf8_lossy(e.name().as_ref())).blue().to_string();
+    for attr in e.attributes().with_checks(false).flatten() {
+        let value = String::from_utf8_lossy(&attr.value);
+        // 原来是单引号并且值中有双引号时继续用单引号
+        let quote = if value.contains('"') { '\'' } else { '"' };
+        let value = format!("{}{}{}", quote, value, quote);
+        tag.push(' ');
+        tag.push_str(&String::from_utf8_lossy(attr.key.as_ref()).green().to_string());
+        tag.push('=');
+        tag.push_str(&value.yellow().to_string());
+    }
+    let end = if empty { "/>" } else { ">" };
+    tag.push_str(&end.blue().to_string());
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_works() {
+        colored::control::set_override(false);
+        let xml = r#"<?xml version="1.0"?><!-- orders --><orders count="2"><order id='a"1'><name>书 &amp; 笔</name><empty></empty></order><order id="2"/><![CDATA[x<y]]></orders>"#;
+        let expected = r#"<?xml version="1.0"?>
+<!-- orders -->
+<orders count="2">
+  <order id='a"1'>
+    <name>书 &amp; 笔</name>
+    <empty></empty>
+  </order>
+  <order id="2"/>
+  <![CDATA[x<y]]>
+</orders>"#;
+        assert_eq!(format(xml).unwrap(), expected);
+        assert!(format("<a><b>1</b>").is_none());
+        assert!(format("<a></b>").is_none());
+        assert!(format("plain text").is_none());
+        assert!(format("").is_none());
+    }
+
+    #[test]
+    fn is_xml_works() {
+        let mime = |s: &str| s.parse::<Mime>().unwrap();
+        assert!(is_xml(Some(&mime("application/xml; charset=utf-8"))));
+        assert!(is_xml(Some(&mime("text/xml"))));
+        assert!(is_xml(Some(&mime("application/rss+xml"))));
+        assert!(!is_xml(Some(&mime("application/json"))));
+    }
+}
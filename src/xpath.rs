@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use anyhow::{anyhow, Result};
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Element};
+use sxd_document::parser;
+use sxd_xpath::{Context, Factory, Value};
+
+/// --xpath 指定的 XPath 1.0 表达式，例如 //order/@id 或者 count(//item)
+/// 文档中声明的前缀可以直接使用，例如 SOAP 的 //soap:Body
+#[derive(Debug)]
+pub struct XPath {
+    xpath: sxd_xpath::XPath,
+    // 表达式中使用的前缀，sxd-xpath 遇到没有声明的前缀会 panic，所以先检查
+    prefixes: Vec<String>,
+}
+
+impl FromStr for XPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let xpath = Factory::new()
+            .build(s)
+            .map_err(|e| anyhow!("Invalid --xpath {}: {}", s, e))?
+            .ok_or_else(|| anyhow!("Invalid --xpath {}: empty expression", s))?;
+        Ok(Self { xpath, prefixes: used_prefixes(s) })
+    }
+}
+
+/// 表达式中的 prefix:name 使用的前缀，不包括 child:: 这样的轴和字符串中的内容
+fn used_prefixes(s: &str) -> Vec<String> {
+    let mut prefixes = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '"' || c == '\'' {
+            chars.by_ref().find(|&(_, q)| q == c);
+            continue;
+        }
+        if !(c.is_alphabetic() || c == '_') {
+            continue;
+        }
+        let mut end = s.len();
+        while let Some(&(j, c)) = chars.peek() {
+            if !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.') {
+                end = j;
+                break;
+            }
+            chars.next();
+        }
+        let rest = &s[end..];
+        if rest.starts_with(':') && !rest.starts_with("::") {
+            prefixes.push(s[i..end].to_string());
+        }
+    }
+    prefixes
+}
+
+impl XPath {
+    /// 在 XML 文档上执行表达式，选中的每个节点返回它的文本（属性是属性值），连续的空白合并成一个空格
+    /// 结果是字符串、数字或者布尔值时只返回这个值，不是合法的 XML 时返回 None
+    pub fn evaluate(&self, xml: &str) -> Result<Option<Vec<String>>> {
+        let package = match parser::parse(xml) {
+            Ok(package) => package,
+            Err(_) => return Ok(None),
+        };
+        let doc = package.as_document();
+        let mut prefixes = HashMap::new();
+        for child in doc.root().children() {
+            if let ChildOfRoot::Element(element) = child {
+                find_prefixes(element, &mut prefixes);
+            }
+        }
+        if let Some(prefix) = self.prefixes.iter().find(|p| !prefixes.contains_key(p.as_str())) {
+            return Err(anyhow!("--xpath: the prefix {} is not declared in the document", prefix));
+        }
+        let mut context = Context::new();
+        for (prefix, uri) in prefixes {
+            context.set_namespace(prefix, uri);
+        }
+        let value = self
+            .xpath
+            .evaluate(&context, doc.root())
+            .map_err(|e| anyhow!("--xpath: {}", e))?;
+        let values = match value {
+            Value::Nodeset(nodes) => nodes
+                .document_order()
+                .iter()
+                .map(|node| node.string_value().split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect(),
+            value => vec![value.into_string()],
+        };
+        Ok(Some(values))
+    }
+}
+
+/// 文档中声明的前缀，让表达式可以直接使用，同一个前缀以最先出现的声明为准
+fn find_prefixes<'d>(element: Element<'d>, prefixes: &mut HashMap<&'d str, &'d str>) {
+    for ns in element.namespaces_in_scope() {
+        prefixes.entry(ns.prefix()).or_insert_with(|| ns.uri());
+    }
+    for child in element.children() {
+        if let ChildOfElement::Element(child) = child {
+            find_prefixes(child, prefixes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_works() {
+        let xml = r#"<?xml version="1.0"?>
+            <soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+              <soap:Body>
+                <orders><order id="a1"><name>Tea
+                  cups</name></order><order id="b2"/></orders>
+              </soap:Body>
+            </soap:Envelope>"#;
+        let evaluate = |s: &str| s.parse::<XPath>().unwrap().evaluate(xml).unwrap().unwrap();
+        assert_eq!(evaluate("//order/@id"), vec!["a1", "b2"]);
+        assert_eq!(evaluate("//order/name"), vec!["Tea cups"]);
+        assert_eq!(evaluate("/soap:Envelope/soap:Body//order[2]/@id"), vec!["b2"]);
+        assert_eq!(evaluate("count(//order)"), vec!["2"]);
+        assert!(evaluate("//missing").is_empty());
+        assert!("//order[".parse::<XPath>().is_err());
+        assert_eq!(used_prefixes("child::a:b[@c:d='e:f']/g"), vec!["a", "c"]);
+        assert!("//x:order".parse::<XPath>().unwrap().evaluate(xml).is_err());
+        assert!("//order".parse::<XPath>().unwrap().evaluate("<a><b></a>").unwrap().is_none());
+    }
+}